@@ -0,0 +1,18 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use xortool::header::Header;
+
+// Reading a header fails cleanly on anything malformed, and what it
+// accepts is written back byte for byte.
+fuzz_target!(|data: &[u8]| {
+    let mut reader = data;
+    let Ok(header) = Header::read_from(&mut reader) else {
+        return;
+    };
+    let _ = header.modified();
+    let mut written = Vec::new();
+    header.write_to(&mut written).unwrap();
+    assert_eq!(written.len(), header.encoded_len());
+    assert_eq!(written, data[..data.len() - reader.len()]);
+});
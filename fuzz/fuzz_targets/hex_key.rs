@@ -0,0 +1,17 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use xortool::{core::decode_hex_key, key::parse_hex_key};
+
+// Both hex key parsers take any text without panicking and agree on it.
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    let mut buffer = [0; 4096];
+    match (parse_hex_key(text), decode_hex_key(text, &mut buffer)) {
+        (Ok(key), Ok(same)) => assert_eq!(key, same),
+        (Err(_), Err(_)) => {}
+        (parsed, decoded) => panic!("{:?}: {:?} but {:?}", text, parsed, decoded),
+    }
+});
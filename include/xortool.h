@@ -0,0 +1,90 @@
+#ifndef XORTOOL_H
+#define XORTOOL_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define XORTOOL_OK 0
+
+// A pointer was null, or a path wasn't valid UTF-8.
+#define XORTOOL_ERR_ARGUMENT 1
+
+// Reading the input or writing the output failed.
+#define XORTOOL_ERR_IO 2
+
+// The progress callback asked to stop.
+#define XORTOOL_ERR_CANCELLED 3
+
+// The library panicked; this is a bug.
+#define XORTOOL_ERR_PANIC 4
+
+// Called as a file is transformed with the bytes done so far, the total,
+// and the pointer given at registration. Returning nonzero stops the
+// transform.
+typedef int (*XortoolProgress)(uint64_t done, uint64_t total, void *user_data);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Registers `callback` to get the progress of every later
+// `xortool_encrypt_file`, with `user_data` passed back to it. A null
+// callback unregisters.
+void xortool_set_progress_callback(XortoolProgress callback, void *user_data);
+
+// XORs `len` bytes at `data` in place with the `key_len`-byte key at
+// `key`, starting at keystream position `offset`.
+//
+// # Safety
+//
+// `data` must be valid for reads and writes of `len` bytes and `key` for
+// reads of `key_len` bytes.
+int xortool_encrypt_buffer(uint8_t *data,
+                           size_t len,
+                           const uint8_t *key,
+                           size_t key_len,
+                           uint64_t offset);
+
+// Transforms the file at `input` into `output` with the `key_len`-byte
+// key at `key`, creating the output's directory if needed. A cancelled
+// or failed transform removes the partial output.
+//
+// # Safety
+//
+// `input` and `output` must be NUL-terminated strings and `key` must be
+// valid for reads of `key_len` bytes.
+int xortool_encrypt_file(const char *input,
+                         const char *output,
+                         const uint8_t *key,
+                         size_t key_len);
+
+#if defined(XORTOOL_NATIVE)
+// Transforms `input`, a file or a directory, the way the CLI does with
+// no options, writing each output to `xor/` next to its input. The
+// progress callback is called for each file in turn. Stops at the first
+// failure, removing that file's partial output. If `files_done` isn't
+// null, the number of files finished is stored there either way.
+//
+// # Safety
+//
+// `input` must be a NUL-terminated string, `key` must be valid for reads
+// of `key_len` bytes and `files_done` must be null or valid for a write.
+int xortool_run(const char *input,
+                const uint8_t *key,
+                size_t key_len,
+                bool recursive,
+                size_t *files_done);
+#endif
+
+// A description of the last error on this thread, or null if there was
+// none. The string stays valid until the next call on this thread.
+const char *xortool_last_error(void);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif // XORTOOL_H
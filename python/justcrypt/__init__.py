@@ -0,0 +1,185 @@
+"""Python bindings for xortool's transform, over its C API.
+
+The shared library is looked for in $XORTOOL_LIB, next to this package,
+and then in the crate's target/release and target/debug directories.
+Build it with ``cargo build --release``.
+
+Encrypting and decrypting are the same XOR, so the decrypt functions are
+the encrypt functions under another name.
+"""
+
+import ctypes
+import os
+import sys
+from pathlib import Path
+
+__all__ = [
+    "JustcryptError",
+    "Cancelled",
+    "encrypt_bytes",
+    "decrypt_bytes",
+    "encrypt_file",
+    "decrypt_file",
+    "run",
+]
+
+_OK = 0
+_ERR_CANCELLED = 3
+
+_PROGRESS = ctypes.CFUNCTYPE(ctypes.c_int, ctypes.c_uint64, ctypes.c_uint64, ctypes.c_void_p)
+
+
+class JustcryptError(Exception):
+    """A call into the library failed; the message says why."""
+
+    def __init__(self, message, status):
+        super().__init__(message)
+        self.status = status
+
+
+class Cancelled(JustcryptError):
+    """A progress callback returned True to stop."""
+
+
+def _library_names():
+    if sys.platform == "win32":
+        return ["xortool.dll"]
+    if sys.platform == "darwin":
+        return ["libxortool.dylib"]
+    return ["libxortool.so"]
+
+
+def _load():
+    candidates = []
+    if os.environ.get("XORTOOL_LIB"):
+        candidates.append(Path(os.environ["XORTOOL_LIB"]))
+    here = Path(__file__).resolve().parent
+    crate = here.parent.parent
+    for directory in [here, crate / "target" / "release", crate / "target" / "debug"]:
+        candidates.extend(directory / name for name in _library_names())
+    for candidate in candidates:
+        if candidate.is_file():
+            return ctypes.CDLL(str(candidate))
+    raise OSError(
+        "xortool library not found (looked in: %s); build it with `cargo build --release` "
+        "or set XORTOOL_LIB" % ", ".join(str(c) for c in candidates)
+    )
+
+
+_lib = _load()
+_lib.xortool_encrypt_buffer.argtypes = [
+    ctypes.c_void_p,
+    ctypes.c_size_t,
+    ctypes.c_char_p,
+    ctypes.c_size_t,
+    ctypes.c_uint64,
+]
+_lib.xortool_encrypt_buffer.restype = ctypes.c_int
+_lib.xortool_encrypt_file.argtypes = [
+    ctypes.c_char_p,
+    ctypes.c_char_p,
+    ctypes.c_char_p,
+    ctypes.c_size_t,
+]
+_lib.xortool_encrypt_file.restype = ctypes.c_int
+_lib.xortool_run.argtypes = [
+    ctypes.c_char_p,
+    ctypes.c_char_p,
+    ctypes.c_size_t,
+    ctypes.c_bool,
+    ctypes.POINTER(ctypes.c_size_t),
+]
+_lib.xortool_run.restype = ctypes.c_int
+_lib.xortool_set_progress_callback.argtypes = [_PROGRESS, ctypes.c_void_p]
+_lib.xortool_set_progress_callback.restype = None
+_lib.xortool_last_error.argtypes = []
+_lib.xortool_last_error.restype = ctypes.c_char_p
+
+
+def _key(key):
+    """Takes a key as bytes or as hex text like the CLI's -k, "0x1a2b"."""
+    if isinstance(key, str):
+        text = key[2:] if key[:2] in ("0x", "0X") else key
+        try:
+            key = bytes.fromhex(text)
+        except ValueError:
+            raise ValueError("Invalid hex key: %r" % key) from None
+    key = bytes(key)
+    if not key:
+        raise ValueError("Key must not be empty")
+    return key
+
+
+def _check(status):
+    if status == _OK:
+        return
+    message = (_lib.xortool_last_error() or b"").decode("utf-8", "replace")
+    if status == _ERR_CANCELLED:
+        raise Cancelled(message, status)
+    raise JustcryptError(message, status)
+
+
+def _with_progress(progress, call):
+    """Runs `call` with `progress(done, total)` registered; it returns True
+    to cancel."""
+    if progress is None:
+        return call()
+
+    def trampoline(done, total, _user_data):
+        try:
+            return 1 if progress(done, total) else 0
+        except Exception:
+            # An exception can't cross back through C; treat it as a
+            # request to stop.
+            return 1
+
+    callback = _PROGRESS(trampoline)
+    _lib.xortool_set_progress_callback(callback, None)
+    try:
+        return call()
+    finally:
+        _lib.xortool_set_progress_callback(_PROGRESS(), None)
+
+
+def encrypt_bytes(data, key, offset=0):
+    """Returns `data` XORed with the repeating `key`. `offset` is where
+    `data` sits in the whole stream, for doing a large one in pieces."""
+    key = _key(key)
+    buffer = bytearray(data)
+    if not buffer:
+        return b""
+    address = (ctypes.c_char * len(buffer)).from_buffer(buffer)
+    _check(_lib.xortool_encrypt_buffer(address, len(buffer), key, len(key), offset))
+    return bytes(buffer)
+
+
+def encrypt_file(input, output, key, progress=None):
+    """Transforms the file `input` into `output`, creating its directory.
+    `progress(done, total)` is called as it goes and may return True to
+    cancel, which raises `Cancelled` and removes the partial output."""
+    key = _key(key)
+    _with_progress(
+        progress,
+        lambda: _check(
+            _lib.xortool_encrypt_file(os.fsencode(input), os.fsencode(output), key, len(key))
+        ),
+    )
+
+
+def run(input, key, recursive=False, progress=None):
+    """Transforms `input`, a file or a directory, the way the CLI does:
+    each output goes in xor/ next to its input. Returns the number of
+    files transformed. `progress` is called for each file in turn."""
+    key = _key(key)
+    done = ctypes.c_size_t(0)
+    _with_progress(
+        progress,
+        lambda: _check(
+            _lib.xortool_run(os.fsencode(input), key, len(key), recursive, ctypes.byref(done))
+        ),
+    )
+    return done.value
+
+
+decrypt_bytes = encrypt_bytes
+decrypt_file = encrypt_file
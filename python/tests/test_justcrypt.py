@@ -0,0 +1,49 @@
+import os
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+sys.path.insert(0, str(Path(__file__).resolve().parent.parent))
+
+import justcrypt  # noqa: E402
+
+
+class JustcryptTest(unittest.TestCase):
+    def test_bytes_round_trip(self):
+        encrypted = justcrypt.encrypt_bytes(b"hello", "0x20")
+        self.assertEqual(encrypted, b"HELLO")
+        self.assertEqual(justcrypt.decrypt_bytes(encrypted, b"\x20"), b"hello")
+        # The keystream continues from `offset`.
+        self.assertEqual(justcrypt.encrypt_bytes(b"b", "0102", offset=1), bytes([ord("b") ^ 2]))
+        with self.assertRaises(ValueError):
+            justcrypt.encrypt_bytes(b"x", "")
+
+    def test_files_and_runs(self):
+        with tempfile.TemporaryDirectory() as root:
+            root = Path(root)
+            (root / "sub").mkdir()
+            (root / "a").write_bytes(b"a" * 200_000)
+            (root / "sub" / "b").write_bytes(b"b")
+
+            calls = []
+            justcrypt.encrypt_file(root / "a", root / "out" / "a", "1a", lambda d, t: calls.append((d, t)))
+            self.assertEqual(calls[-1], (200_000, 200_000))
+            justcrypt.decrypt_file(root / "out" / "a", root / "back", "1a")
+            self.assertEqual((root / "back").read_bytes(), b"a" * 200_000)
+
+            with self.assertRaises(justcrypt.Cancelled):
+                justcrypt.encrypt_file(root / "a", root / "stopped", "1a", lambda d, t: True)
+            self.assertFalse((root / "stopped").exists())
+
+            with self.assertRaises(justcrypt.JustcryptError) as failure:
+                justcrypt.encrypt_file(root / "missing", root / "x", "1a")
+            self.assertIn("missing", str(failure.exception))
+
+            os.remove(root / "back")
+            self.assertEqual(justcrypt.run(root, "1a", recursive=True), 3)
+            self.assertEqual((root / "sub" / "xor" / "b").read_bytes(), bytes([ord("b") ^ 0x1A]))
+
+
+if __name__ == "__main__":
+    unittest.main()
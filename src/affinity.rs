@@ -0,0 +1,136 @@
+use anyhow::{bail, Context, Result};
+use std::io;
+
+/// A set of CPU indices as given to `--cpus`, e.g. `0-7,12,14-15`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CpuList(pub Vec<usize>);
+
+pub fn parse_cpu_list(text: &str) -> Result<CpuList> {
+    let mut cpus = Vec::new();
+    for part in text.split(',').map(str::trim) {
+        let (first, last) = match part.split_once('-') {
+            Some((first, last)) => (first.trim(), last.trim()),
+            None => (part, part),
+        };
+        let first: usize = first
+            .parse()
+            .with_context(|| format!("Invalid CPU '{}' in '{}'", first, text))?;
+        let last: usize = last
+            .parse()
+            .with_context(|| format!("Invalid CPU '{}' in '{}'", last, text))?;
+        if first > last {
+            bail!("Invalid CPU range '{}'", part);
+        }
+        if last >= imp::MAX_CPUS {
+            bail!(
+                "CPU {} is out of range; the highest is {}",
+                last,
+                imp::MAX_CPUS - 1
+            );
+        }
+        cpus.extend(first..=last);
+    }
+
+    cpus.sort_unstable();
+    cpus.dedup();
+    Ok(CpuList(cpus))
+}
+
+/// The CPUs this process may currently run on.
+pub fn available_cpus() -> Vec<usize> {
+    imp::available_cpus().unwrap_or_else(|_| {
+        let count = std::thread::available_parallelism().map_or(1, |n| n.get());
+        (0..count).collect()
+    })
+}
+
+/// Restricts the calling thread to `cpus`. Threads spawned afterwards
+/// inherit the mask.
+pub fn pin_current_thread(cpus: &[usize]) -> io::Result<()> {
+    imp::pin_current_thread(cpus)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod imp {
+    use std::{io, mem};
+
+    /// CPUs a `cpu_set_t` has room for.
+    pub const MAX_CPUS: usize = libc::CPU_SETSIZE as usize;
+
+    pub fn available_cpus() -> io::Result<Vec<usize>> {
+        // SAFETY: cpu_set_t is plain data; the kernel fills it in.
+        unsafe {
+            let mut set: libc::cpu_set_t = mem::zeroed();
+            if libc::sched_getaffinity(0, mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok((0..MAX_CPUS)
+                .filter(|&cpu| libc::CPU_ISSET(cpu, &set))
+                .collect())
+        }
+    }
+
+    pub fn pin_current_thread(cpus: &[usize]) -> io::Result<()> {
+        // SAFETY: cpu_set_t is plain data; indices are checked below.
+        unsafe {
+            let mut set: libc::cpu_set_t = mem::zeroed();
+            for &cpu in cpus {
+                if cpu >= MAX_CPUS {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("CPU {} is out of range", cpu),
+                    ));
+                }
+                libc::CPU_SET(cpu, &mut set);
+            }
+            if libc::sched_setaffinity(0, mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+mod imp {
+    use std::io;
+
+    /// Pinning isn't supported here; this only bounds what `--cpus` takes.
+    pub const MAX_CPUS: usize = 1024;
+
+    pub fn available_cpus() -> io::Result<Vec<usize>> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    pub fn pin_current_thread(_cpus: &[usize]) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "thread pinning is not supported on this platform",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cpu_list_parsing() {
+        assert_eq!(parse_cpu_list("0-3").unwrap(), CpuList(vec![0, 1, 2, 3]));
+        assert_eq!(
+            parse_cpu_list("6, 0-1,4").unwrap(),
+            CpuList(vec![0, 1, 4, 6])
+        );
+        assert_eq!(parse_cpu_list("2,2,1-2").unwrap(), CpuList(vec![1, 2]));
+
+        assert!(parse_cpu_list("").is_err());
+        assert!(parse_cpu_list("3-1").is_err());
+        assert!(parse_cpu_list("a").is_err());
+        assert!(parse_cpu_list("0-99999999999").is_err());
+        assert!(parse_cpu_list(&imp::MAX_CPUS.to_string()).is_err());
+        assert_eq!(
+            parse_cpu_list(&(imp::MAX_CPUS - 1).to_string()).unwrap(),
+            CpuList(vec![imp::MAX_CPUS - 1])
+        );
+    }
+}
@@ -0,0 +1,174 @@
+use anyhow::{bail, Context, Result};
+use std::{
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    cipher::Cipher, cli::get_relative_path, color::Stylize, transform::read_full,
+    walk::build_output_path,
+};
+
+/// How the output of one source compared.
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Match,
+    /// The first byte at which the decrypted output differs, or where the
+    /// shorter of the two ends.
+    Differs(u64),
+    Missing,
+}
+
+/// Decrypts the output of each of `sources` in memory and compares it
+/// byte for byte with the source, printing a line for every one that
+/// doesn't match. Outputs are looked up where a run puts them, or at the
+/// same path relative to `root` under `outputs`, with a cipher from
+/// `new_cipher` for each. Fails if any differ or are missing.
+pub fn run(
+    root: &Path,
+    sources: &[PathBuf],
+    outputs: Option<&Path>,
+    new_cipher: &dyn Fn() -> Box<dyn Cipher>,
+    buffer_size: usize,
+) -> Result<()> {
+    let mut source_buffer = vec![0; buffer_size];
+    let mut output_buffer = vec![0; buffer_size];
+    let (mut matched, mut differ, mut missing) = (0, 0, 0);
+
+    for source in sources {
+        let output = match outputs {
+            Some(outputs) => outputs.join(source.strip_prefix(root).unwrap_or(source)),
+            None => build_output_path(source)?,
+        };
+        let outcome = compare(
+            source,
+            &output,
+            new_cipher().as_mut(),
+            &mut source_buffer,
+            &mut output_buffer,
+        )?;
+        let name = get_relative_path(source)?;
+        match outcome {
+            Outcome::Match => matched += 1,
+            Outcome::Differs(offset) => {
+                differ += 1;
+                println!(
+                    "{} {}: output differs at byte {}",
+                    "MISMATCH".red(),
+                    name,
+                    offset
+                );
+            }
+            Outcome::Missing => {
+                missing += 1;
+                println!(
+                    "{} {}: no output at {}",
+                    "MISSING".red(),
+                    name,
+                    output.display()
+                );
+            }
+        }
+    }
+
+    println!(
+        "Audited {} files: {} match, {} differ, {} without output",
+        sources.len(),
+        matched,
+        differ,
+        missing
+    );
+    if differ > 0 || missing > 0 {
+        bail!("Audit failed");
+    }
+    Ok(())
+}
+
+fn compare(
+    source: &Path,
+    output: &Path,
+    cipher: &mut dyn Cipher,
+    source_buffer: &mut [u8],
+    output_buffer: &mut [u8],
+) -> Result<Outcome> {
+    let mut source_file =
+        File::open(source).with_context(|| format!("Failed to open file: {}", source.display()))?;
+    let mut output_file = match File::open(output) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Outcome::Missing),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to open output: {}", output.display()))
+        }
+    };
+
+    let mut offset = 0u64;
+    loop {
+        let source_len = read_full(&mut source_file, source_buffer)
+            .with_context(|| format!("Failed to read {}", source.display()))?;
+        let output_len = read_full(&mut output_file, output_buffer)
+            .with_context(|| format!("Failed to read {}", output.display()))?;
+        let len = source_len.min(output_len);
+
+        let decrypted = &mut output_buffer[..len];
+        cipher.apply(offset, decrypted);
+        if let Some(at) = decrypted
+            .iter()
+            .zip(&source_buffer[..len])
+            .position(|(a, b)| a != b)
+        {
+            return Ok(Outcome::Differs(offset + at as u64));
+        }
+        if source_len != output_len {
+            return Ok(Outcome::Differs(offset + len as u64));
+        }
+        if len == 0 {
+            return Ok(Outcome::Match);
+        }
+        offset += len as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cipher::Xor, transform::xor_encrypt};
+    use std::fs;
+
+    #[test]
+    fn test_compare() {
+        let dir = std::env::temp_dir().join(format!("xortool-audit-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let key = [0x21, 0x43, 0x65];
+        let plain: Vec<u8> = (0..1000u32).map(|i| (i * 7) as u8).collect();
+        let mut encrypted = plain.clone();
+        xor_encrypt(&mut encrypted, &key, 0);
+        fs::write(dir.join("source"), &plain).unwrap();
+        fs::write(dir.join("output"), &encrypted).unwrap();
+
+        let check = || {
+            let (mut a, mut b) = (vec![0; 64], vec![0; 64]);
+            compare(
+                &dir.join("source"),
+                &dir.join("output"),
+                &mut Xor::new(key),
+                &mut a,
+                &mut b,
+            )
+            .unwrap()
+        };
+        assert_eq!(check(), Outcome::Match);
+
+        encrypted[700] ^= 1;
+        fs::write(dir.join("output"), &encrypted).unwrap();
+        assert_eq!(check(), Outcome::Differs(700));
+
+        fs::write(dir.join("output"), &encrypted[..300]).unwrap();
+        assert_eq!(check(), Outcome::Differs(300));
+
+        fs::remove_file(dir.join("output")).unwrap();
+        assert_eq!(check(), Outcome::Missing);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,85 @@
+use anyhow::{Context, Result};
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+use walkdir::WalkDir;
+
+use crate::{cache, pause, transform::xor_encrypt, walk::is_output_dir};
+
+/// Buffer sizes tried, smallest first so ties favour less memory.
+const CANDIDATES: [usize; 5] = [64 << 10, 256 << 10, 1 << 20, 4 << 20, 16 << 20];
+
+/// Bytes read per candidate; smaller samples are dominated by noise.
+const SAMPLE_BYTES: u64 = 64 << 20;
+
+/// A file at least this large is used as soon as the scan finds one.
+const LARGE_FILE: u64 = 16 << 20;
+
+/// Picks the file to benchmark: `input` itself, or the first large file
+/// under it (falling back to the largest one seen).
+pub fn pick_sample(input: &Path, recursive: bool) -> Option<PathBuf> {
+    if input.is_file() {
+        return Some(input.to_path_buf());
+    }
+
+    let mut best: Option<(u64, PathBuf)> = None;
+    let walker = WalkDir::new(input).max_depth(if recursive { usize::MAX } else { 1 });
+    let entries = walker.into_iter().filter_entry(|e| !is_output_dir(e));
+    for entry in entries.filter_map(|e| e.ok()) {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+        if size >= LARGE_FILE {
+            return Some(entry.into_path());
+        }
+        if best.as_ref().is_none_or(|(best_size, _)| size > *best_size) {
+            best = Some((size, entry.into_path()));
+        }
+    }
+    best.map(|(_, path)| path)
+}
+
+/// Times read + XOR + write (into a sink) over the start of `sample` for
+/// each candidate size and returns the fastest with its throughput in
+/// bytes per second. The page cache is dropped between rounds on Linux so
+/// later candidates don't just measure memory.
+pub fn choose_buffer_size(sample: &Path, key: &[u8]) -> Result<(usize, f64)> {
+    let mut file = File::open(sample)
+        .with_context(|| format!("Failed to open autotune sample: {}", sample.display()))?;
+
+    let mut best = (CANDIDATES[0], 0.0);
+    for size in CANDIDATES {
+        if pause::quit_requested() {
+            return Err(pause::Stopped.into());
+        }
+        cache::release(&file);
+        file.seek(SeekFrom::Start(0))?;
+
+        let mut buffer = vec![0u8; size];
+        let mut reader = (&mut file).take(SAMPLE_BYTES);
+        let mut sink = io::sink();
+        let mut processed = 0u64;
+        let start = Instant::now();
+
+        loop {
+            let read_count = reader.read(&mut buffer)?;
+            if read_count == 0 {
+                break;
+            }
+            xor_encrypt(&mut buffer[..read_count], key, processed);
+            sink.write_all(&buffer[..read_count])?;
+            processed += read_count as u64;
+        }
+
+        let speed = processed as f64 / start.elapsed().as_secs_f64().max(1e-9);
+        if speed > best.1 {
+            best = (size, speed);
+        }
+    }
+
+    Ok(best)
+}
@@ -0,0 +1,52 @@
+use std::fs::File;
+
+/// Tells the kernel `file` will be read once from start to end. Hints are
+/// advisory: failures are ignored and non-Linux targets do nothing.
+pub fn sequential(file: &File) {
+    #[cfg(target_os = "linux")]
+    advise(file, libc::POSIX_FADV_SEQUENTIAL);
+    #[cfg(not(target_os = "linux"))]
+    let _ = file;
+}
+
+/// Drops `file`'s pages from the cache once we're done with it. Dirty
+/// pages can't be dropped, so writeback is started first without waiting
+/// for it; whatever is already clean gets released.
+pub fn release(file: &File) {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::io::AsRawFd;
+        // SAFETY: plain syscall on a descriptor we own.
+        unsafe {
+            libc::sync_file_range(file.as_raw_fd(), 0, 0, libc::SYNC_FILE_RANGE_WRITE);
+        }
+        advise(file, libc::POSIX_FADV_DONTNEED);
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = file;
+}
+
+/// Starts reading the first `len` bytes of `file` into the cache in the
+/// background.
+pub fn will_need(file: &File, len: u64) {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::io::AsRawFd;
+        let len = libc::off_t::try_from(len).unwrap_or(libc::off_t::MAX);
+        // SAFETY: plain syscall on a descriptor we own.
+        unsafe {
+            libc::posix_fadvise(file.as_raw_fd(), 0, len, libc::POSIX_FADV_WILLNEED);
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = (file, len);
+}
+
+#[cfg(target_os = "linux")]
+fn advise(file: &File, advice: libc::c_int) {
+    use std::os::unix::io::AsRawFd;
+    // SAFETY: plain syscall on a descriptor we own.
+    unsafe {
+        libc::posix_fadvise(file.as_raw_fd(), 0, 0, advice);
+    }
+}
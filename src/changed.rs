@@ -0,0 +1,96 @@
+use anyhow::{Context, Result};
+use std::{
+    fmt, fs,
+    path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::humanize;
+
+/// Times a file is read with `--on-change retry` before giving up on it.
+pub const ATTEMPTS: usize = 3;
+
+static UNSTABLE: AtomicUsize = AtomicUsize::new(0);
+
+/// What to do about an input whose size changed while it was read, from
+/// `--on-change`.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum ChangeMode {
+    /// Keep the output, warn and mark the file unstable in the summary
+    #[default]
+    Warn,
+    /// Transform the file again, failing it if it keeps changing
+    Retry,
+    /// Fail the file
+    Fail,
+}
+
+/// An input that was a different size once read to the end than when it
+/// was opened.
+#[derive(Debug)]
+pub struct Changed {
+    pub before: u64,
+    pub after: u64,
+}
+
+impl fmt::Display for Changed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "size changed from {} to {} while it was read",
+            humanize::size(self.before),
+            humanize::size(self.after)
+        )
+    }
+}
+
+impl std::error::Error for Changed {}
+
+/// Stats `path` again after it was read as `size` bytes. A change means
+/// the output may be torn: in `Warn` mode it's counted and returned for a
+/// warning, otherwise it's the error.
+pub fn check(path: &Path, size: u64, mode: ChangeMode) -> Result<Option<Changed>> {
+    let after = fs::metadata(path)
+        .with_context(|| format!("Failed to stat {}", path.display()))?
+        .len();
+    if after == size {
+        return Ok(None);
+    }
+    let changed = Changed {
+        before: size,
+        after,
+    };
+    if mode != ChangeMode::Warn {
+        return Err(
+            anyhow::Error::new(changed).context(format!("Input {} changed", path.display()))
+        );
+    }
+    UNSTABLE.fetch_add(1, Ordering::Relaxed);
+    Ok(Some(changed))
+}
+
+/// Files kept although they changed while they were read.
+pub fn unstable() -> usize {
+    UNSTABLE.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_size() {
+        let path = std::env::temp_dir().join(format!("xortool-changed-{}", std::process::id()));
+        fs::write(&path, [0; 10]).unwrap();
+
+        assert!(check(&path, 10, ChangeMode::Fail).unwrap().is_none());
+        assert_eq!(
+            check(&path, 8, ChangeMode::Warn).unwrap().unwrap().after,
+            10
+        );
+        let error = check(&path, 8, ChangeMode::Retry).unwrap_err();
+        assert!(error.downcast_ref::<Changed>().is_some());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
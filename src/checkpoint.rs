@@ -0,0 +1,281 @@
+use anyhow::{bail, Context, Result};
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    hash::{BuildHasher, Hasher, RandomState},
+    io::{Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use crate::{checksum::Sha256, namelen, walk::CHECKPOINT_FILE_NAME};
+
+/// Name of the checkpoint in the output directory of the run.
+/// Walks skip it.
+pub const FILE_NAME: &str = CHECKPOINT_FILE_NAME;
+
+/// Extension given to the output of a file that was stopped partway, until
+/// `--resume` picks it up again.
+const PARTIAL_EXTENSION: &str = "partial";
+
+const HEADER: &str = "xortool checkpoint 2";
+
+/// Progress of the run so far, by input path: the size of each finished
+/// input, and the offset up to which each stopped one was written.
+#[derive(Default)]
+struct State {
+    done: HashMap<PathBuf, u64>,
+    partial: HashMap<PathBuf, (u64, u64)>,
+}
+
+static STATE: Mutex<Option<State>> = Mutex::new(None);
+/// What the checkpoint being resumed from recorded.
+static RESUMED: Mutex<Option<State>> = Mutex::new(None);
+
+fn with_state(f: impl FnOnce(&mut State)) {
+    f(STATE.lock().unwrap().get_or_insert_with(State::default));
+}
+
+/// Records that `input`, of `size` bytes, was transformed completely.
+pub fn done(input: &Path, size: u64) {
+    with_state(|state| {
+        state.done.insert(input.to_path_buf(), size);
+    });
+}
+
+/// Records that the output of `input`, of `size` bytes, was stopped after
+/// its first `offset` bytes were written.
+pub fn partial(input: &Path, size: u64, offset: u64) {
+    with_state(|state| {
+        state.partial.insert(input.to_path_buf(), (size, offset));
+    });
+}
+
+pub fn is_partial(input: &Path) -> bool {
+    STATE
+        .lock()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|state| state.partial.contains_key(input))
+}
+
+/// Where the output of a stopped file is kept until it is resumed.
+pub fn partial_path(output: &Path) -> PathBuf {
+    namelen::with_extension_added(output, PARTIAL_EXTENSION)
+}
+
+fn path(dir: &Path) -> PathBuf {
+    dir.join(FILE_NAME)
+}
+
+/// The line that ties a checkpoint to its key: a fresh random salt and
+/// the HMAC-SHA256 of it under the key. Without the salt a hash of a short
+/// key could be looked up or brute-forced once for every checkpoint.
+fn key_line(key: &[u8], salt: &[u8]) -> String {
+    format!(
+        "key {} {}",
+        hex::encode(salt),
+        hex::encode(hmac_sha256(key, salt))
+    )
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK: usize = 64;
+    let mut padded = [0u8; BLOCK];
+    if key.len() > BLOCK {
+        let mut hasher = Sha256::new();
+        hasher.update(key);
+        padded[..32].copy_from_slice(&hasher.finalize());
+    } else {
+        padded[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = Sha256::new();
+    inner.update(&padded.map(|byte| byte ^ 0x36));
+    inner.update(message);
+    let mut outer = Sha256::new();
+    outer.update(&padded.map(|byte| byte ^ 0x5c));
+    outer.update(&inner.finalize());
+    outer.finalize()
+}
+
+/// 16 bytes that differ from run to run, from the OS-seeded keys of
+/// `RandomState`.
+fn random_salt() -> [u8; 16] {
+    let mut salt = [0; 16];
+    for half in salt.chunks_mut(8) {
+        let random = RandomState::new().build_hasher().finish();
+        half.copy_from_slice(&random.to_le_bytes());
+    }
+    salt
+}
+
+/// Writes what the stopped run over `root` got done into its output
+/// directory `dir`, for `--resume`, and returns where. Finished files of a
+/// run it resumed are carried over.
+pub fn write(root: &Path, dir: &Path, key: &[u8]) -> Result<PathBuf> {
+    let mut state = STATE.lock().unwrap().take().unwrap_or_default();
+    if let Some(resumed) = RESUMED.lock().unwrap().as_ref() {
+        for (input, size) in &resumed.done {
+            state.done.entry(input.clone()).or_insert(*size);
+        }
+    }
+
+    let mut text = format!("{}\n{}\n", HEADER, key_line(key, &random_salt()));
+    let mut done: Vec<_> = state.done.iter().collect();
+    done.sort();
+    for (input, size) in done {
+        if let Some(name) = relative(input, root) {
+            text.push_str(&format!("done {} {}\n", size, name));
+        }
+    }
+    for (input, (size, offset)) in &state.partial {
+        if let Some(name) = relative(input, root) {
+            text.push_str(&format!("partial {} {} {}\n", size, offset, name));
+        }
+    }
+
+    let path = path(dir);
+    fs::write(&path, text)
+        .with_context(|| format!("Failed to write checkpoint: {}", path.display()))?;
+    Ok(path)
+}
+
+/// Names in the checkpoint are one per line, so a name with a line break
+/// isn't recorded and its file is redone.
+fn relative(input: &Path, root: &Path) -> Option<String> {
+    let name = input.strip_prefix(root).ok()?.to_str()?;
+    (!name.contains('\n')).then(|| name.to_string())
+}
+
+/// Loads the checkpoint an earlier run over `root` left in the output
+/// directory `dir`. Fails if there is
+/// none or it was made with another key. Returns how many files it had
+/// finished.
+pub fn load(root: &Path, dir: &Path, key: &[u8]) -> Result<usize> {
+    let path = path(dir);
+    let text = fs::read_to_string(&path).with_context(|| {
+        format!(
+            "No checkpoint to resume from at {} (one is saved when a run is stopped)",
+            path.display()
+        )
+    })?;
+    let mut lines = text.lines();
+    if lines.next() != Some(HEADER) {
+        bail!("Unrecognized checkpoint: {}", path.display());
+    }
+    let line = lines.next().unwrap_or_default();
+    let salt = line
+        .strip_prefix("key ")
+        .and_then(|rest| rest.split_once(' '))
+        .and_then(|(salt, _)| hex::decode(salt).ok());
+    let Some(salt) = salt else {
+        bail!("Unrecognized checkpoint: {}", path.display());
+    };
+    if line != key_line(key, &salt) {
+        bail!(
+            "The checkpoint at {} was made with another key",
+            path.display()
+        );
+    }
+
+    let mut state = State::default();
+    for line in lines {
+        if parse_line(line, root, &mut state).is_none() {
+            bail!("Malformed line in checkpoint {}: {}", path.display(), line);
+        }
+    }
+    let finished = state.done.len();
+    *RESUMED.lock().unwrap() = Some(state);
+    Ok(finished)
+}
+
+fn parse_line(line: &str, root: &Path, state: &mut State) -> Option<()> {
+    let (kind, rest) = line.split_once(' ')?;
+    match kind {
+        "done" => {
+            let (size, name) = rest.split_once(' ')?;
+            state.done.insert(root.join(name), size.parse().ok()?);
+        }
+        "partial" => {
+            let (size, rest) = rest.split_once(' ')?;
+            let (offset, name) = rest.split_once(' ')?;
+            state
+                .partial
+                .insert(root.join(name), (size.parse().ok()?, offset.parse().ok()?));
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+/// Whether the resumed checkpoint has `input` finished at its current
+/// `size`.
+pub fn is_done(input: &Path, size: u64) -> bool {
+    RESUMED
+        .lock()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|state| state.done.get(input) == Some(&size))
+}
+
+/// Reopens the partial output of `input` where the resumed checkpoint left
+/// it, if the input is still `size` bytes, and returns it with the offset
+/// to carry on from.
+pub fn resume_partial(input: &Path, output: &Path, size: u64) -> Option<(File, u64)> {
+    let (recorded, offset) = *RESUMED.lock().unwrap().as_ref()?.partial.get(input)?;
+    let partial = partial_path(output);
+    if recorded != size || fs::metadata(&partial).ok()?.len() < offset {
+        return None;
+    }
+    fs::rename(&partial, output).ok()?;
+    let mut file = OpenOptions::new().write(true).open(output).ok()?;
+    file.set_len(offset).ok()?;
+    file.seek(SeekFrom::Start(offset)).ok()?;
+    Some((file, offset))
+}
+
+/// Removes the checkpoint from the output directory `dir` once the run has
+/// finished.
+pub fn remove(dir: &Path) {
+    let _ = fs::remove_file(path(dir));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_round_trip() {
+        let root = std::env::temp_dir().join(format!("xortool-checkpoint-{}", std::process::id()));
+        let dir = root.join("out");
+        fs::create_dir_all(&dir).unwrap();
+        let key = [0x42, 0x17];
+
+        done(&root.join("a b.txt"), 10);
+        done(&root.join("sub/c"), 20);
+        partial(&root.join("big"), 1000, 512);
+        write(&root, &dir, &key).unwrap();
+        let text = fs::read_to_string(dir.join(FILE_NAME)).unwrap();
+        let mut plain_hash = Sha256::new();
+        plain_hash.update(&key);
+        assert!(!text.contains(&hex::encode(plain_hash.finalize())));
+
+        assert!(load(&root, &dir, &[0x42]).is_err());
+        assert_eq!(load(&root, &dir, &key).unwrap(), 2);
+        assert!(is_done(&root.join("a b.txt"), 10));
+        assert!(!is_done(&root.join("sub/c"), 21));
+        assert_eq!(
+            RESUMED.lock().unwrap().as_ref().unwrap().partial[&root.join("big")],
+            (1000, 512)
+        );
+
+        // RFC 4231, test case 2.
+        assert_eq!(
+            hex::encode(hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,325 @@
+use anyhow::{bail, Context, Result};
+use std::{
+    fs::{self, File},
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    cache,
+    cipher::Cipher,
+    namelen,
+    normalize::{self, Form},
+    transform::xor_encrypt,
+};
+
+/// Extension of the files holding a file's plaintext digest.
+const SIDECAR_EXTENSION: &str = "sha256";
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const H0: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// Incremental SHA-256 (FIPS 180-4).
+#[derive(Clone)]
+pub struct Sha256 {
+    state: [u32; 8],
+    block: [u8; 64],
+    filled: usize,
+    len: u64,
+}
+
+impl Default for Sha256 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sha256 {
+    pub fn new() -> Self {
+        Self {
+            state: H0,
+            block: [0; 64],
+            filled: 0,
+            len: 0,
+        }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.len += data.len() as u64;
+
+        if self.filled > 0 {
+            let take = (64 - self.filled).min(data.len());
+            self.block[self.filled..self.filled + take].copy_from_slice(&data[..take]);
+            self.filled += take;
+            data = &data[take..];
+            if self.filled < 64 {
+                return;
+            }
+            let block = self.block;
+            compress(&mut self.state, &block);
+            self.filled = 0;
+        }
+
+        let mut blocks = data.chunks_exact(64);
+        for block in &mut blocks {
+            compress(&mut self.state, block.try_into().unwrap());
+        }
+        let rest = blocks.remainder();
+        self.block[..rest.len()].copy_from_slice(rest);
+        self.filled = rest.len();
+    }
+
+    pub fn finalize(mut self) -> [u8; 32] {
+        let bits = self.len.wrapping_mul(8);
+        self.update(&[0x80]);
+        while self.filled != 56 {
+            self.update(&[0]);
+        }
+        self.update(&bits.to_be_bytes());
+
+        let mut digest = [0u8; 32];
+        for (out, word) in digest.chunks_exact_mut(4).zip(self.state) {
+            out.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+}
+
+fn compress(state: &mut [u32; 8], block: &[u8; 64]) {
+    let mut w = [0u32; 64];
+    for (word, bytes) in w.iter_mut().zip(block.chunks_exact(4)) {
+        *word = u32::from_be_bytes(bytes.try_into().unwrap());
+    }
+    for i in 16..64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(w[i - 7])
+            .wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for i in 0..64 {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = h
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(K[i])
+            .wrapping_add(w[i]);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = s0.wrapping_add(maj);
+
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
+
+    for (word, value) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+        *word = word.wrapping_add(value);
+    }
+}
+
+/// Digests of a file's plaintext and ciphertext, fed from the same
+/// buffers that are being transformed so neither side is read twice.
+#[derive(Clone, Default)]
+pub struct Checksums {
+    pub plain: Sha256,
+    pub cipher: Sha256,
+}
+
+impl Checksums {
+    /// Applies `cipher` to `data` in place, hashing it before and after.
+    pub fn apply(&mut self, data: &mut [u8], cipher: &mut dyn Cipher, offset: u64) {
+        self.plain.update(data);
+        cipher.apply(offset, data);
+        self.cipher.update(data);
+    }
+
+    /// Hex digests of the plaintext and ciphertext.
+    pub fn finish(self) -> (String, String) {
+        (
+            hex::encode(self.plain.finalize()),
+            hex::encode(self.cipher.finalize()),
+        )
+    }
+}
+
+/// `path` with `.sha256` appended, where its plaintext digest is kept,
+/// shortened if that makes the name too long.
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    namelen::with_extension_added(path, SIDECAR_EXTENSION)
+}
+
+/// The sidecar of `path`, found by name under any Unicode normalization
+/// in case the two were copied through a system that renamed one of them.
+fn find_sidecar(path: &Path) -> PathBuf {
+    let expected = sidecar_path(path);
+    if expected.exists() {
+        return expected;
+    }
+    let (Some(dir), Some(name)) = (expected.parent(), expected.file_name()) else {
+        return expected;
+    };
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .find(|entry| normalize::same_name(&entry.file_name(), name))
+        .map_or(expected, |entry| entry.path())
+}
+
+pub fn is_sidecar(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == SIDECAR_EXTENSION)
+}
+
+/// Writes the plaintext digest of `output`'s input next to it, in the
+/// format of `sha256sum`, so `sha256sum -c` also checks a decrypted copy.
+pub fn write_sidecar(output: &Path, plain: &str, form: Form) -> Result<()> {
+    let path = sidecar_path(output);
+    let name = output.file_name().unwrap_or_default().to_string_lossy();
+    let name = normalize::normalize(&name, form);
+    fs::write(&path, format!("{}  {}\n", plain, name))
+        .with_context(|| format!("Failed to write checksum file: {}", path.display()))
+}
+
+/// Checks `decrypted`, the digest of what `input` was turned into, against
+/// the plaintext digest in `input`'s sidecar.
+pub fn verify_sidecar(input: &Path, decrypted: &str) -> Result<()> {
+    let path = find_sidecar(input);
+    let contents = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read checksum file: {}", path.display()))?;
+    let expected = contents.split_whitespace().next().unwrap_or_default();
+    if !expected.eq_ignore_ascii_case(decrypted) {
+        bail!(
+            "Verification failed for {}: expected SHA-256 {}, got {}",
+            input.display(),
+            expected,
+            decrypted
+        );
+    }
+    Ok(())
+}
+
+/// Reads `output` back from disk, decrypts it with `key` and checks that
+/// it hashes to `plain`, the digest of the input it was made from.
+/// `buffer` is used for reading.
+pub fn verify_round_trip(output: &Path, key: &[u8], plain: &str, buffer: &mut [u8]) -> Result<()> {
+    let mut file = File::open(output)
+        .with_context(|| format!("Failed to reopen output: {}", output.display()))?;
+    // Whatever is still cached would be checked instead of the disk.
+    cache::release(&file);
+
+    let mut hasher = Sha256::new();
+    let mut offset = 0;
+    loop {
+        let read_count = file
+            .read(buffer)
+            .with_context(|| format!("Failed to read back output: {}", output.display()))?;
+        if read_count == 0 {
+            break;
+        }
+        xor_encrypt(&mut buffer[..read_count], key, offset);
+        hasher.update(&buffer[..read_count]);
+        offset += read_count as u64;
+    }
+
+    if hex::encode(hasher.finalize()) != plain {
+        bail!(
+            "Round-trip check failed: {} does not decrypt to its input",
+            output.display()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sha256_hex(data: &[u8]) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(data);
+        hex::encode(hasher.finalize())
+    }
+
+    #[test]
+    fn test_known_answers() {
+        assert_eq!(
+            sha256_hex(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            sha256_hex(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+    }
+
+    #[test]
+    fn test_sidecar_round_trip() {
+        let dir = std::env::temp_dir().join(format!("xortool-sidecar-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("data.bin");
+        let digest = sha256_hex(b"abc");
+
+        write_sidecar(&file, &digest, Form::Nfc).unwrap();
+        assert!(is_sidecar(&sidecar_path(&file)));
+        assert_eq!(
+            fs::read_to_string(sidecar_path(&file)).unwrap(),
+            format!("{}  data.bin\n", digest)
+        );
+        assert!(verify_sidecar(&file, &digest).is_ok());
+        assert!(verify_sidecar(&file, &sha256_hex(b"abd")).is_err());
+
+        write_sidecar(&dir.join("cafe\u{301}.bin"), &digest, Form::Nfc).unwrap();
+        assert!(verify_sidecar(&dir.join("caf\u{E9}.bin"), &digest).is_ok());
+
+        let key = [0x5a, 0x17];
+        let mut data = b"round trip".to_vec();
+        xor_encrypt(&mut data, &key, 0);
+        fs::write(&file, &data).unwrap();
+        let plain = sha256_hex(b"round trip");
+        let mut buffer = [0; 4];
+        assert!(verify_round_trip(&file, &key, &plain, &mut buffer).is_ok());
+        assert!(verify_round_trip(&file, &[0x5a], &plain, &mut buffer).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_incremental_updates() {
+        let data: Vec<u8> = (0..1000u32).map(|i| (i * 31) as u8).collect();
+        let expected = sha256_hex(&data);
+
+        for split in [1, 63, 64, 65, 500] {
+            let mut hasher = Sha256::new();
+            for chunk in data.chunks(split) {
+                hasher.update(chunk);
+            }
+            assert_eq!(hex::encode(hasher.finalize()), expected, "split={}", split);
+        }
+    }
+}
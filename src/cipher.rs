@@ -0,0 +1,103 @@
+use std::fmt;
+
+use crate::{
+    key::{is_identity_key, Redacted},
+    transform::xor_encrypt,
+};
+
+/// A transform applied to a stream chunk by chunk. `offset` is where
+/// `buf` starts in the stream, so chunks can be any size. A run decrypts
+/// by applying the cipher again, so it must undo itself, as keystream
+/// ciphers like XOR do.
+///
+/// Ciphers do no I/O, so async code can read chunks with its own runtime
+/// and apply one to each, the way `transform::stream_with` does for
+/// blocking readers.
+pub trait Cipher: Send {
+    fn apply(&mut self, offset: u64, buf: &mut [u8]);
+
+    /// Whether `apply` leaves data unchanged, so a plain copy will do.
+    fn is_identity(&self) -> bool {
+        false
+    }
+}
+
+/// XOR with a repeating key, the transform the CLI runs.
+#[derive(Clone)]
+pub struct Xor {
+    key: Vec<u8>,
+}
+
+impl fmt::Debug for Xor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Xor")
+            .field("key", &Redacted(&self.key))
+            .finish()
+    }
+}
+
+impl Xor {
+    pub fn new(key: impl Into<Vec<u8>>) -> Self {
+        Self { key: key.into() }
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+}
+
+impl Cipher for Xor {
+    fn apply(&mut self, offset: u64, buf: &mut [u8]) {
+        xor_encrypt(buf, &self.key, offset);
+    }
+
+    fn is_identity(&self) -> bool {
+        is_identity_key(&self.key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transform::stream_with;
+    use std::io;
+
+    /// Leaves data alone and records the chunks it was given.
+    #[derive(Default)]
+    struct Recording(Vec<(u64, usize)>);
+
+    impl Cipher for Recording {
+        fn apply(&mut self, offset: u64, buf: &mut [u8]) {
+            self.0.push((offset, buf.len()));
+        }
+    }
+
+    #[test]
+    fn test_cipher_in_stream() {
+        let mut xor: Box<dyn Cipher> = Box::new(Xor::new([0x0f]));
+        let mut data = *b"ab";
+        xor.apply(1, &mut data);
+        assert_eq!(data, [b'a' ^ 0x0f, b'b' ^ 0x0f]);
+        assert!(!xor.is_identity());
+        assert!(Xor::new([0, 0]).is_identity());
+        assert_eq!(
+            format!("{:?}", Xor::new([0xde, 0xad])),
+            "Xor { key: <2 bytes> }"
+        );
+
+        let mut recording = Recording::default();
+        let mut output = Vec::new();
+        let end = stream_with(
+            &mut &[7u8; 10][..],
+            &mut output,
+            &mut recording,
+            &mut [0; 4],
+            100,
+            |_| Ok::<_, io::Error>(()),
+        )
+        .unwrap();
+        assert_eq!(end, 110);
+        assert_eq!(output, [7; 10]);
+        assert_eq!(recording.0, [(100, 4), (104, 4), (108, 2)]);
+    }
+}
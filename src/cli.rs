@@ -0,0 +1,2784 @@
+use anyhow::{Context, Result};
+use clap::Parser;
+use crossterm::{
+    cursor, execute,
+    terminal::{self, ClearType},
+};
+use std::{
+    env,
+    fs,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use walkdir::DirEntry;
+
+#[cfg(target_os = "linux")]
+use crate::uring;
+use crate::{
+    affinity::{self, CpuList},
+    audit, autotune, cache,
+    changed::{self, ChangeMode},
+    checkpoint,
+    checksum::{self, Checksums},
+    color::{self, ColorChoice, Stylize},
+    config, container, dedup, direct,
+    encrypted::{self, EncryptedMode},
+    estimate::Throughput,
+    failure::{self, Phase, PhaseExt},
+    frame,
+    header::{self, Header},
+    heartbeat, histogram, humanize,
+    i18n::{self, Msg},
+    job::Job,
+    key::{is_identity_key, HexKey, KeyEnv, KeyFile, KeyPrompt, KeySource},
+    lifecycle,
+    lock::{self, LockMode},
+    longpath, manifest, mmap, monitor,
+    normalize::Form,
+    notify,
+    options::ProcessOptions,
+    order::{self, SortOrder},
+    pause, pipeline,
+    pool::{self, BufferPool},
+    priority,
+    progress::{FileProgress, FileResult, FileStart},
+    resize, selftest, space, sparse,
+    spec::{JobSpec, KeyRef},
+    special,
+    stats::{self, Status},
+    tar, taskbar,
+    theme::Theme,
+    throttle::RateLimiter,
+    trace,
+    transform::{self, DEFAULT_BUFFER_SIZE},
+    tui,
+    walk::{normalize_path, OUTPUT_DIR},
+    width,
+};
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+const SPINNER_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Shortest the filename in a progress line gets on narrow terminals.
+const MIN_FILENAME_WIDTH: usize = 10;
+
+/// Files below this size in a directory run are counted on a shared
+/// status line instead of each getting its own progress line.
+const SMALL_FILE_THRESHOLD: u64 = 1024 * 1024;
+
+/// Descriptors kept open by the process itself (stdin, stdout, stderr).
+const RESERVED_FDS: u64 = 3;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    /// Input file or directory path
+    #[arg(required_unless_present_any = ["selftest", "job", "info"])]
+    input: Option<PathBuf>,
+
+    /// Encryption key in hex format (e.g., 1a2b3c4d or 0xFF)
+    #[arg(
+        short,
+        long,
+        required_unless_present_any = ["selftest", "job", "info", "key_file", "key_env", "key_prompt"],
+        conflicts_with_all = ["key_file", "key_env", "key_prompt"]
+    )]
+    key: Option<String>,
+
+    /// Read the key in hex from this file
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["key_env", "key_prompt"])]
+    key_file: Option<PathBuf>,
+
+    /// Read the key in hex from this environment variable
+    #[arg(long, value_name = "VAR", conflicts_with = "key_prompt")]
+    key_env: Option<String>,
+
+    /// Ask for the key in hex on the terminal without echoing it
+    #[arg(long)]
+    key_prompt: bool,
+
+    /// Run the job described in this TOML file: its input, key source,
+    /// output directory, buffer size and walk options. A key flag takes
+    /// the place of its key, and --exclude patterns are added to its own
+    #[arg(long, value_name = "FILE", conflicts_with = "input")]
+    job: Option<PathBuf>,
+
+    /// Write the input, key source, output directory, buffer size and
+    /// walk options given to this job file instead of running. The key
+    /// source must be --key-file, --key-env or --key-prompt
+    #[arg(long, value_name = "FILE")]
+    save_job: Option<PathBuf>,
+
+    /// When to use colors and other styling
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
+    /// Print only ASCII and never move the cursor (implied by TERM=dumb)
+    #[arg(long)]
+    ascii: bool,
+
+    /// Plain output for logs and screen readers: no colors or other
+    /// styling, ASCII only and no cursor movement
+    #[arg(long, conflicts_with_all = ["color", "tui", "terminal_progress"])]
+    plain: bool,
+
+    /// Language of the output, such as `de` (defaults to LC_ALL,
+    /// LC_MESSAGES or LANG); translations are read from
+    /// ~/.config/xortool/locale/LANG.toml
+    #[arg(long, value_name = "LANG")]
+    lang: Option<String>,
+
+    /// Read settings from this file instead of the default
+    /// (~/.config/xortool/config.toml)
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Process subdirectories recursively
+    #[arg(short, long)]
+    recursive: bool,
+
+    /// Write outputs under DIR, mirroring the input directory's tree,
+    /// instead of to xor/ next to each input
+    #[arg(long, value_name = "DIR", conflicts_with = "io_uring")]
+    output_dir: Option<PathBuf>,
+
+    /// Leave out files and directories matching this pattern, such as
+    /// *.tmp or logs/**; can be repeated
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// Use memory-mapped I/O instead of buffered reads and writes
+    #[arg(long)]
+    mmap: bool,
+
+    /// Batch small-file I/O through io_uring (Linux only)
+    #[arg(long, conflicts_with = "jobs")]
+    io_uring: bool,
+
+    /// I/O buffer size, with optional K/M/G suffix (e.g., 256K, 4M);
+    /// rounded up to a multiple of 4K
+    #[arg(long, default_value = "64K", value_parser = parse_buffer_size)]
+    buffer_size: usize,
+
+    /// Bypass the OS page cache (O_DIRECT / FILE_FLAG_NO_BUFFERING)
+    #[arg(long)]
+    direct_io: bool,
+
+    /// Benchmark a few buffer sizes on a sample file and use the fastest
+    #[arg(long, conflicts_with = "buffer_size")]
+    autotune: bool,
+
+    /// Number of files to process in parallel
+    #[arg(short, long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    jobs: u16,
+
+    /// Run fewer parallel jobs if needed to keep at most this many files open
+    #[arg(long, value_name = "N")]
+    max_open_files: Option<u64>,
+
+    /// Run fewer parallel jobs if needed to keep I/O buffers under this
+    /// size, e.g. 512M
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    max_memory: Option<u64>,
+
+    /// Restrict processing to these CPUs, e.g. 0-7,12
+    #[arg(long, value_name = "LIST", value_parser = affinity::parse_cpu_list)]
+    cpus: Option<CpuList>,
+
+    /// Pin each worker thread to its own CPU (from --cpus, or all available)
+    /// and give it buffers allocated on that CPU
+    #[arg(long)]
+    pin_threads: bool,
+
+    /// Order to process files in, so runs are reproducible; with --jobs
+    /// the largest files still start first
+    #[arg(long, value_enum, value_name = "ORDER", default_value_t = SortOrder::Name)]
+    sort: SortOrder,
+
+    /// Overlap reading, transforming and writing on separate threads
+    #[arg(long, conflicts_with_all = ["mmap", "direct_io"])]
+    pipeline: bool,
+
+    /// Keep holes in sparse inputs as holes (not XORed); such outputs must
+    /// also be decrypted with --sparse
+    #[arg(long, conflicts_with_all = ["mmap", "direct_io", "io_uring"])]
+    sparse: bool,
+
+    /// Print SHA-256 digests of each input and output, computed while
+    /// transforming
+    #[arg(long, conflicts_with_all = ["mmap", "direct_io", "io_uring", "sparse"])]
+    checksum: bool,
+
+    /// Write the SHA-256 of each input next to its output, as
+    /// <output>.sha256, for checking a later decrypt with --verify
+    #[arg(long, conflicts_with_all = ["mmap", "direct_io", "io_uring", "sparse"])]
+    sidecar: bool,
+
+    /// Check that each output matches the SHA-256 in the input's .sha256
+    /// file, as written by --sidecar when the input was encrypted
+    #[arg(long, conflicts_with_all = ["mmap", "direct_io", "io_uring", "sparse", "sidecar"])]
+    verify: bool,
+
+    /// Write xor/manifest.json listing every output with its size and
+    /// SHA-256 digests, and the cipher and key length used
+    #[arg(long, conflicts_with_all = ["mmap", "direct_io", "io_uring", "sparse"])]
+    manifest: bool,
+
+    /// After writing each output, sync it, read it back from disk and
+    /// check that it decrypts to the input
+    #[arg(long, conflicts_with_all = ["mmap", "direct_io", "io_uring", "sparse"])]
+    verify_after: bool,
+
+    /// Write outputs as 1 MiB frames, each with a CRC32 of its contents,
+    /// so corruption can be traced to a frame when decrypting with
+    /// --unframe
+    #[arg(long, conflicts_with_all = ["mmap", "direct_io", "io_uring", "sparse", "pipeline", "checksum", "sidecar", "verify", "manifest", "verify_after"])]
+    framed: bool,
+
+    /// Decrypt outputs written with --framed, checking the CRC32 of each
+    /// frame and naming the first corrupt one
+    #[arg(long, conflicts_with_all = ["framed", "mmap", "direct_io", "io_uring", "sparse", "pipeline", "checksum", "sidecar", "verify", "manifest", "verify_after"])]
+    unframe: bool,
+
+    /// Start each output with a JXOR header recording the input's name,
+    /// size and modification time, which --info reads without the key
+    #[arg(long, conflicts_with_all = ["mmap", "direct_io", "io_uring", "sparse", "unframe", "checksum", "manifest", "verify_after"])]
+    header: bool,
+
+    /// Decrypt outputs written with --header, checking the header and
+    /// restoring the original modification time
+    #[arg(long, conflicts_with_all = ["header", "framed", "mmap", "direct_io", "io_uring", "sparse", "checksum", "manifest", "verify_after"])]
+    strip_header: bool,
+
+    /// Print the JXOR header of FILE, as written by --header, and exit
+    #[arg(long, value_name = "FILE", exclusive = true)]
+    info: Option<PathBuf>,
+
+    /// Write every file of the input directory into this one container
+    /// file, with an encrypted index, instead of an output per file
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["output_dir", "framed", "unframe", "header", "strip_header", "extract", "mmap", "direct_io", "io_uring", "sparse", "pipeline", "checksum", "sidecar", "verify", "manifest", "verify_after", "dedup", "resume"])]
+    container: Option<PathBuf>,
+
+    /// Restore the files of the container given as input, as written by
+    /// --container, under DIR
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["output_dir", "framed", "unframe", "header", "strip_header", "mmap", "direct_io", "io_uring", "sparse", "pipeline", "checksum", "sidecar", "verify", "manifest", "verify_after", "dedup", "resume"])]
+    extract: Option<PathBuf>,
+
+    /// Treat the input as a tar archive and transform each regular member
+    /// on its own, into xor/ next to the archive (or --output-dir) at the
+    /// member's path, or into --tar-out
+    #[arg(long, conflicts_with_all = ["container", "extract", "framed", "unframe", "header", "strip_header", "mmap", "direct_io", "io_uring", "sparse", "pipeline", "checksum", "sidecar", "verify", "manifest", "verify_after", "dedup", "resume"])]
+    tar_in: bool,
+
+    /// Write the outputs as members of a tar archive at FILE, or to
+    /// standard output if FILE is -, instead of as separate files
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["output_dir", "container", "extract", "framed", "unframe", "header", "strip_header", "mmap", "direct_io", "io_uring", "sparse", "pipeline", "checksum", "sidecar", "verify", "manifest", "verify_after", "dedup", "resume"])]
+    tar_out: Option<PathBuf>,
+
+    /// Instead of transforming, decrypt each existing output in memory and
+    /// compare it byte for byte with its input, reporting any that differ.
+    /// Outputs are looked for in xor/ next to each input, or under DIR
+    #[arg(long, value_name = "DIR", num_args = 0..=1, require_equals = true, conflicts_with_all = ["framed", "unframe", "sparse", "resume"])]
+    audit: Option<Option<PathBuf>>,
+
+    /// Carry on from where a stopped run over the same input left off,
+    /// skipping the files it finished
+    #[arg(long)]
+    resume: bool,
+
+    /// Unicode normalization of the file names written to manifests and
+    /// .sha256 files, so names from macOS and elsewhere match
+    #[arg(long, value_enum, value_name = "FORM", default_value_t = Form::Nfc)]
+    normalize: Form,
+
+    /// Only warn, instead of stopping before the run, when the outputs may
+    /// not fit in the free space
+    #[arg(long)]
+    allow_low_space: bool,
+
+    /// What to do if another run is writing to the same output directory
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = LockMode::Fail)]
+    lock: LockMode,
+
+    /// Flush each output and its directory to disk before reporting it
+    /// complete, so a power loss can't leave it empty or torn
+    #[arg(long)]
+    fsync: bool,
+
+    /// What to do when an input changes size while it is read, as a log
+    /// file that is still being written does
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = ChangeMode::Warn)]
+    on_change: ChangeMode,
+
+    /// What to do with an input that already starts with a JXOR header,
+    /// as an output of an earlier run with --header does
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = EncryptedMode::Skip)]
+    on_encrypted: EncryptedMode,
+
+    /// Stop with an error on sockets, devices, FIFOs and dangling symlinks
+    /// instead of skipping them with a warning
+    #[arg(long)]
+    strict: bool,
+
+    /// Run with a key of only zero bytes, which copies files unchanged
+    #[arg(long)]
+    force: bool,
+
+    /// Run known-answer tests and an encrypt/decrypt round trip through
+    /// each I/O path in a scratch directory, then exit
+    #[arg(long, exclusive = true)]
+    selftest: bool,
+
+    /// After the run, list the N slowest files with their size, time and
+    /// throughput (all files if N is omitted)
+    #[arg(long, value_name = "N", require_equals = true)]
+    stats: Option<Option<usize>>,
+
+    /// Write one row per file (path, bytes, seconds, MB/s, status, input
+    /// checksum) to this CSV file
+    #[arg(long, value_name = "FILE")]
+    stats_csv: Option<PathBuf>,
+
+    /// Show a desktop notification when the run finishes or fails
+    #[arg(long)]
+    notify: bool,
+
+    /// Run COMMAND through the shell at EVENT: run-start, file-start,
+    /// file-done, file-failed or run-done. Paths, byte counts and errors
+    /// are passed in XORTOOL_* environment variables; can be given more
+    /// than once
+    #[arg(long, value_name = "EVENT=COMMAND", value_parser = lifecycle::parse)]
+    hook: Vec<lifecycle::Hook>,
+
+    /// Publish progress as JSON lines to monitors connecting to this Unix
+    /// socket
+    #[arg(long, value_name = "PATH")]
+    progress_socket: Option<PathBuf>,
+
+    /// Show a full-screen dashboard with per-file progress, a throughput
+    /// graph and recent errors; `p` pauses, `s` skips the files in flight
+    /// and `q` stops the run
+    #[arg(long)]
+    tui: bool,
+
+    /// Print the distribution of throughput over the run; repeat (-vv)
+    /// for a histogram, or -vvv to also log every read and write to
+    /// xortool-trace.log (or the file named by XORTOOL_TRACE)
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Show progress in the terminal's tab title and taskbar entry
+    /// (OSC 9;4, supported by Windows Terminal, ConEmu and others)
+    #[arg(long)]
+    terminal_progress: bool,
+
+    /// Transform files with identical content once and hard-link the
+    /// outputs of the duplicates to it
+    #[arg(long, conflicts_with = "io_uring")]
+    dedup: bool,
+
+    /// Don't send page-cache hints (sequential read, drop after write)
+    #[arg(long)]
+    no_fadvise: bool,
+
+    /// Cap read/write throughput, e.g. 50M for 50 MiB/s
+    #[arg(long, value_name = "RATE", value_parser = parse_size)]
+    limit_rate: Option<u64>,
+
+    /// Lower CPU and I/O priority so foreground work wins
+    #[arg(long)]
+    nice: bool,
+
+    /// Run at idle CPU and I/O priority (background mode)
+    #[arg(long)]
+    idle: bool,
+}
+
+struct ProgressPrinter {
+    start_time: Instant,
+    last_update: Instant,
+    last_pos: u16,
+    filename: String,
+    is_tty: bool,
+    paused: bool,
+    run: Option<Arc<Mutex<RunProgress>>>,
+    aggregated: bool,
+    row: Option<usize>,
+    checksum: Option<String>,
+    /// Output to flush to disk before reporting completion, with --fsync.
+    sync: Option<PathBuf>,
+    /// Input to stat again before reporting completion, to catch one that
+    /// changed while it was read.
+    recheck: Option<(PathBuf, ChangeMode)>,
+    position: String,
+    generation: usize,
+    throughput: Throughput,
+}
+
+impl ProgressPrinter {
+    /// A printer with its own progress line, plus the overall line of
+    /// `run` under it in directory runs.
+    fn new(filename: &str, run: Option<Arc<Mutex<RunProgress>>>) -> Result<Self> {
+        let is_tty = Theme::current().live();
+        let mut stdout = io::stdout();
+
+        let mut last_pos = 0;
+        if is_tty {
+            let lines = if run.is_some() { 2 } else { 1 };
+            execute!(stdout, cursor::SavePosition)?;
+            print!("{}", "\n".repeat(lines));
+            stdout.flush()?;
+            let (_, new_pos) = cursor::position()?;
+            execute!(stdout, cursor::RestorePosition)?;
+            last_pos = new_pos + 1 - lines as u16;
+        }
+        let position = match &run {
+            Some(run) => run.lock().unwrap().start(),
+            None => String::new(),
+        };
+
+        Ok(Self {
+            start_time: Instant::now(),
+            last_update: Instant::now(),
+            last_pos,
+            filename: filename.to_string(),
+            is_tty,
+            paused: false,
+            run,
+            aggregated: false,
+            row: None,
+            checksum: None,
+            sync: None,
+            recheck: None,
+            position,
+            generation: resize::generation(),
+            throughput: Throughput::new(),
+        })
+    }
+
+    /// A printer that only reports completion, for when several files are
+    /// in flight and per-file progress lines would overwrite each other.
+    fn completion_only(filename: &str, run: Option<Arc<Mutex<RunProgress>>>) -> Self {
+        Self {
+            start_time: Instant::now(),
+            last_update: Instant::now(),
+            last_pos: 0,
+            filename: filename.to_string(),
+            is_tty: false,
+            paused: false,
+            run,
+            aggregated: false,
+            row: None,
+            checksum: None,
+            sync: None,
+            recheck: None,
+            position: String::new(),
+            generation: 0,
+            throughput: Throughput::new(),
+        }
+    }
+
+    /// A printer that draws nothing and adds the file to the shared
+    /// small-file status line when it completes.
+    fn aggregated(filename: &str, run: Arc<Mutex<RunProgress>>) -> Self {
+        run.lock().unwrap().start();
+        let mut printer = Self::completion_only(filename, Some(run));
+        printer.aggregated = true;
+        printer
+    }
+
+    /// A printer for one of several files in flight, shown as a row in the
+    /// run's block of worker rows.
+    fn worker(filename: &str, total: u64, run: Arc<Mutex<RunProgress>>) -> Self {
+        let row = run.lock().unwrap().claim_row(filename, total);
+        let mut printer = Self::completion_only(filename, Some(run));
+        printer.row = Some(row);
+        printer
+    }
+
+    fn update(&mut self, processed: u64, total: u64) -> Result<()> {
+        if let Some(run) = &self.run {
+            let mut run = run.lock().unwrap();
+            if let Some(row) = self.row {
+                let drawn = run.update_row(row, processed, self.paused);
+                run.report(run.in_flight(), self.paused);
+                return drawn;
+            }
+            run.report(run.in_flight() + processed, self.paused);
+        } else {
+            taskbar::update(processed as f64 / total.max(1) as f64 * 100.0, self.paused);
+            heartbeat::beat(processed, total, || self.filename.clone());
+        }
+        if !self.is_tty {
+            return Ok(());
+        }
+        if !self.paused {
+            self.throughput.update(processed);
+        }
+
+        let mut stdout = io::stdout();
+        if self.generation != resize::generation() {
+            // The cursor was left at the start of our first line, which
+            // the terminal may have moved while rewrapping.
+            self.generation = resize::generation();
+            self.last_pos = cursor::position()?.1;
+            execute!(
+                stdout,
+                cursor::MoveToColumn(0),
+                terminal::Clear(ClearType::FromCursorDown)
+            )?;
+        }
+        execute!(
+            stdout,
+            cursor::MoveTo(0, self.last_pos),
+            terminal::Clear(ClearType::CurrentLine)
+        )?;
+        write!(
+            stdout,
+            "{}",
+            file_progress_line(
+                processed,
+                total,
+                &self.throughput,
+                self.paused,
+                &self.position,
+                &self.filename
+            )
+        )?;
+
+        if let Some(run) = &self.run {
+            let overall = run.lock().unwrap().overall_line(processed);
+            execute!(
+                stdout,
+                cursor::MoveTo(0, self.last_pos + 1),
+                terminal::Clear(ClearType::CurrentLine)
+            )?;
+            write!(stdout, "{}", overall)?;
+        }
+
+        execute!(stdout, cursor::MoveTo(0, self.last_pos))?;
+        stdout.flush()?;
+        Ok(())
+    }
+
+    /// Like `update`, but redraws at most once per `PROGRESS_INTERVAL`
+    /// (and always on the final chunk).
+    fn on_progress(&mut self, progress: &FileProgress) -> Result<()> {
+        let FileProgress {
+            done: processed,
+            total,
+            ..
+        } = *progress;
+        let now = Instant::now();
+        if now - self.last_update > PROGRESS_INTERVAL || processed == total {
+            monitor::emit(monitor::Event::Progress {
+                file: &self.filename,
+                processed,
+                total,
+            });
+            self.update(processed, total)?;
+            self.last_update = now;
+        }
+        Ok(())
+    }
+
+    /// Shows the paused state and blocks until the run is resumed. Time
+    /// spent paused is excluded from speed and ETA.
+    fn wait_while_paused(&mut self, processed: u64, total: u64) -> Result<()> {
+        let paused_at = Instant::now();
+        self.paused = true;
+        self.update(processed, total)?;
+
+        while pause::is_paused() {
+            thread::sleep(PAUSE_POLL_INTERVAL);
+        }
+
+        self.paused = false;
+        self.start_time += paused_at.elapsed();
+        self.throughput.resume();
+        self.update(processed, total)
+    }
+
+    fn on_file_complete(&mut self, result: &FileResult) -> Result<()> {
+        let total = result.bytes;
+        let changed = match self.recheck.take() {
+            Some((path, mode)) => {
+                let changed = changed::check(&path, total, mode)?;
+                checkpoint::done(&path, total);
+                changed
+            }
+            None => None,
+        };
+        let status = match changed {
+            Some(_) => Status::Unstable,
+            None => Status::Ok,
+        };
+        self.finish(total, status)?;
+        if let Some(changed) = changed {
+            self.note(&format!(
+                "Warning: {} {}; its output may be torn",
+                self.filename, changed
+            ))?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, total: u64, status: Status) -> Result<()> {
+        if let Some(path) = self.sync.take() {
+            sync_output(&path)?;
+        }
+        let elapsed = self.start_time.elapsed();
+        trace::log(
+            &self.filename,
+            "done",
+            format_args!("size={} took={:.6}s", total, elapsed.as_secs_f64()),
+        );
+        monitor::emit(monitor::Event::Done {
+            file: &self.filename,
+            bytes: total,
+            duration: elapsed,
+        });
+        stats::record(&self.filename, total, elapsed, status, self.checksum.take());
+        let speed = total as f64 / elapsed.as_secs_f64();
+        let line = format!(
+            "{} {} {} {} {:.1}s ({}) ",
+            Theme::current().done().green(),
+            Msg::Completed.bold(),
+            humanize::size(total),
+            Msg::In,
+            elapsed.as_secs_f64(),
+            humanize::rate(speed),
+        );
+        let line = format!(
+            "{}{}",
+            line,
+            shorten_path(&self.filename, filename_budget(&line)).dim()
+        );
+
+        if let Some(run) = &self.run {
+            let mut run = run.lock().unwrap();
+            if self.aggregated {
+                return run.record(total);
+            }
+            run.complete(total);
+            if let Some(row) = self.row.take() {
+                return run.release_row(row, &line);
+            }
+            run.interrupt();
+        }
+
+        if self.is_tty {
+            execute!(
+                io::stdout(),
+                cursor::MoveTo(0, self.last_pos),
+                terminal::Clear(ClearType::FromCursorDown)
+            )?;
+        }
+        say(&line);
+
+        Ok(())
+    }
+
+    /// Prints a line of output for this file without disturbing progress
+    /// drawn for other files.
+    fn note(&self, text: &str) -> Result<()> {
+        match &self.run {
+            Some(run) => run.lock().unwrap().print_above(text),
+            None => {
+                say(text);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Drop for ProgressPrinter {
+    fn drop(&mut self) {
+        // The lock is poisoned if another worker panicked, and panicking
+        // here as well would abort.
+        if let (Some(run), Some(row)) = (&self.run, self.row.take()) {
+            if let Ok(mut run) = run.lock() {
+                run.abandon_row(row);
+            }
+        }
+    }
+}
+
+/// One file's progress: position in the run, bar, sizes, speed, ETA and
+/// name.
+fn file_progress_line(
+    processed: u64,
+    total: u64,
+    throughput: &Throughput,
+    paused: bool,
+    position: &str,
+    filename: &str,
+) -> String {
+    // An empty file is done as soon as it starts.
+    let percent = match total {
+        0 => 100.0,
+        _ => (processed as f64 / total as f64) * 100.0,
+    };
+    let remain_sec = throughput.eta(total.saturating_sub(processed));
+
+    let (status, eta) = if paused {
+        (
+            Theme::current().paused().yellow(),
+            Msg::Paused.yellow().to_string(),
+        )
+    } else {
+        (
+            Theme::current().running().cyan(),
+            format!("{}: {:>3}s", Msg::Eta, remain_sec),
+        )
+    };
+    let progress_bar = Theme::current().bar(percent as u8);
+
+    let line = format!(
+        "{}{} {:>5.1}% {} | {}/{} | {:>11} | {} | ",
+        position,
+        status,
+        percent,
+        progress_bar,
+        format!("{:>9}", humanize::size(processed)).bold(),
+        format!("{:9}", humanize::size(total)).dim(),
+        humanize::rate(throughput.rate()),
+        eta,
+    );
+    resize::fit(&format!(
+        "{}{}",
+        line,
+        shorten_path(filename, filename_budget(&line)).dim()
+    ))
+}
+
+/// Columns left for a filename after `line` on the terminal.
+fn filename_budget(line: &str) -> usize {
+    resize::width()
+        .saturating_sub(resize::visible_width(line) + 2)
+        .max(MIN_FILENAME_WIDTH)
+}
+
+/// Progress of a file that has a row in the run's block.
+struct Row {
+    position: String,
+    filename: String,
+    processed: u64,
+    total: u64,
+    throughput: Throughput,
+    paused: bool,
+}
+
+/// Shared progress of a directory run: run-wide totals, a row per file in
+/// flight when several are processed at once, and the files below
+/// `SMALL_FILE_THRESHOLD`, which share a single "N/M small files" line.
+///
+/// These are drawn as a block of lines at the bottom of the output with
+/// the cursor kept at its first line; anything else printed during the
+/// run goes above it through `print_above`.
+struct RunProgress {
+    start_time: Instant,
+    last_update: Instant,
+    files_total: usize,
+    bytes_total: u64,
+    throughput: Throughput,
+    files_started: usize,
+    files_done: usize,
+    files_failed: usize,
+    /// Finished files that were empty, which are counted apart in the
+    /// summary.
+    files_empty: usize,
+    bytes_done: u64,
+    /// Whether files run on several workers, which adds the queue to the
+    /// overall line.
+    parallel: bool,
+    small_total: usize,
+    small_done: usize,
+    small_bytes: u64,
+    rows: Vec<Option<Row>>,
+    drawn: usize,
+    is_tty: bool,
+}
+
+impl RunProgress {
+    fn new(files: &[(u64, PathBuf)], parallel: bool) -> Self {
+        Self {
+            start_time: Instant::now(),
+            last_update: Instant::now(),
+            files_total: files.len(),
+            bytes_total: files.iter().map(|(size, _)| size).sum(),
+            throughput: Throughput::new(),
+            files_started: 0,
+            files_done: 0,
+            files_failed: 0,
+            files_empty: 0,
+            bytes_done: 0,
+            parallel,
+            small_total: files
+                .iter()
+                .filter(|(size, _)| *size < SMALL_FILE_THRESHOLD)
+                .count(),
+            small_done: 0,
+            small_bytes: 0,
+            rows: Vec::new(),
+            drawn: 0,
+            is_tty: Theme::current().live(),
+        }
+    }
+
+    /// Counts a file that is starting and returns its "[12/340] " label.
+    fn start(&mut self) -> String {
+        self.files_started += 1;
+        let width = self.files_total.to_string().len();
+        format!(
+            "[{:>width$}/{}] ",
+            self.files_started,
+            self.files_total,
+            width = width
+        )
+    }
+
+    /// Counts a finished file that had its own progress line.
+    fn complete(&mut self, bytes: u64) {
+        self.files_done += 1;
+        if bytes == 0 {
+            self.files_empty += 1;
+        }
+        self.bytes_done += bytes;
+    }
+
+    /// Counts a file that was skipped, which is done without its bytes.
+    fn skip(&mut self) -> Result<()> {
+        self.files_done += 1;
+        self.refresh(true)
+    }
+
+    /// Uncounts a file that is to be transformed again, so it isn't
+    /// counted as started twice.
+    fn retry(&mut self) {
+        self.files_started -= 1;
+    }
+
+    fn fail(&mut self) -> Result<()> {
+        self.files_failed += 1;
+        self.refresh(true)
+    }
+
+    /// The "12 queued, 4 running, 1 failed" part of the overall line, in
+    /// parallel runs.
+    fn queue_status(&self) -> Option<String> {
+        if !self.parallel {
+            return None;
+        }
+        let running = self.files_started - self.files_done - self.files_failed;
+        Some(format!(
+            "{} {}, {} {}, {} {}",
+            self.files_total - self.files_started,
+            Msg::Queued,
+            running,
+            Msg::Running,
+            self.files_failed,
+            Msg::Failed
+        ))
+    }
+
+    /// Counts a finished small file and refreshes the shared line.
+    fn record(&mut self, bytes: u64) -> Result<()> {
+        self.complete(bytes);
+        self.small_done += 1;
+        self.small_bytes += bytes;
+
+        let finished = self.small_done == self.small_total;
+        self.refresh(finished)
+    }
+
+    /// Takes a free row for a file that is starting.
+    fn claim_row(&mut self, filename: &str, total: u64) -> usize {
+        let row = Row {
+            position: self.start(),
+            filename: filename.to_string(),
+            processed: 0,
+            total,
+            throughput: Throughput::new(),
+            paused: false,
+        };
+        match self.rows.iter().position(Option::is_none) {
+            Some(index) => {
+                self.rows[index] = Some(row);
+                index
+            }
+            None => {
+                self.rows.push(Some(row));
+                self.rows.len() - 1
+            }
+        }
+    }
+
+    fn update_row(&mut self, index: usize, processed: u64, paused: bool) -> Result<()> {
+        let Some(row) = self.rows[index].as_mut() else {
+            return Ok(());
+        };
+        let paused_changed = row.paused != paused;
+        if paused_changed && !paused {
+            row.throughput.resume();
+        }
+        if !paused {
+            row.throughput.update(processed);
+        }
+        row.processed = processed;
+        row.paused = paused;
+        self.refresh(paused_changed)
+    }
+
+    /// Frees the row of a file that stopped without completing.
+    fn abandon_row(&mut self, index: usize) {
+        self.rows[index] = None;
+    }
+
+    /// Frees a finished file's row, printing `line` above the block.
+    fn release_row(&mut self, index: usize, line: &str) -> Result<()> {
+        self.rows[index] = None;
+        self.print_above(line)
+    }
+
+    /// Redraws the block if `PROGRESS_INTERVAL` has passed, or right away
+    /// with `force`.
+    fn refresh(&mut self, force: bool) -> Result<()> {
+        let now = Instant::now();
+        if self.is_tty && (now - self.last_update > PROGRESS_INTERVAL || force) {
+            self.draw()?;
+            self.last_update = now;
+        }
+        Ok(())
+    }
+
+    /// Bytes done so far of the files that have rows.
+    fn in_flight(&self) -> u64 {
+        self.rows.iter().flatten().map(|row| row.processed).sum()
+    }
+
+    /// How far the run is, counting `in_flight` bytes of files not yet
+    /// done.
+    fn percent(&self, in_flight: u64) -> f64 {
+        if self.bytes_total > 0 {
+            ((self.bytes_done + in_flight) as f64 / self.bytes_total as f64 * 100.0).min(100.0)
+        } else {
+            100.0
+        }
+    }
+
+    /// Passes run-wide progress to the taskbar and the plain progress
+    /// lines. Called with the lock held, so that it never goes backwards.
+    fn report(&self, in_flight: u64, paused: bool) {
+        taskbar::update(self.percent(in_flight), paused);
+        heartbeat::beat(self.bytes_done + in_flight, self.bytes_total, || {
+            let files = format!("{}/{} {}", self.files_done, self.files_total, Msg::Files);
+            match self.queue_status() {
+                Some(queue) => format!("{} | {}", files, queue),
+                None => files,
+            }
+        });
+    }
+
+    /// The overall line, counting `in_flight` bytes of files not yet done.
+    fn overall_line(&mut self, in_flight: u64) -> String {
+        let done = self.bytes_done + in_flight;
+        let percent = self.percent(in_flight);
+        self.throughput.update(done);
+        let remain_sec = self.throughput.eta(self.bytes_total.saturating_sub(done));
+
+        let mut line = format!(
+            "{} {:>5.1}% {} | {}/{} {} | {}/{} | {}: {:>3}s",
+            Theme::current().total().cyan(),
+            percent,
+            Theme::current().bar(percent as u8),
+            self.files_done.to_string().bold(),
+            self.files_total.to_string().dim(),
+            Msg::Files,
+            format!("{:>9}", humanize::size(done)).bold(),
+            format!("{:9}", humanize::size(self.bytes_total)).dim(),
+            Msg::Eta,
+            remain_sec
+        );
+        if let Some(queue) = self.queue_status() {
+            line += &format!(" | {}", queue.dim());
+        }
+        resize::fit(&line)
+    }
+
+    fn small_files_line(&self) -> String {
+        let speed = self.small_bytes as f64 / self.start_time.elapsed().as_secs_f64();
+        resize::fit(&format!(
+            "{} {}/{} {} | {:>9} | {:>11}",
+            Theme::current().running().cyan(),
+            self.small_done.to_string().bold(),
+            self.small_total.to_string().dim(),
+            Msg::SmallFiles,
+            humanize::size(self.small_bytes),
+            humanize::rate(speed)
+        ))
+    }
+
+    /// Draws the worker rows, the small-file line and the overall line,
+    /// leaving the cursor at the start of the block.
+    fn draw(&mut self) -> Result<()> {
+        let mut lines = Vec::new();
+        let mut in_flight = 0;
+        for row in self.rows.iter().flatten() {
+            in_flight += row.processed;
+            lines.push(file_progress_line(
+                row.processed,
+                row.total,
+                &row.throughput,
+                row.paused,
+                &row.position,
+                &row.filename,
+            ));
+        }
+        if self.small_done > 0 {
+            lines.push(self.small_files_line());
+        }
+        lines.push(self.overall_line(in_flight));
+
+        let mut stdout = io::stdout();
+        self.clear()?;
+        write!(stdout, "{}", lines.join("\n"))?;
+        if lines.len() > 1 {
+            execute!(stdout, cursor::MoveToPreviousLine(lines.len() as u16 - 1))?;
+        } else {
+            execute!(stdout, cursor::MoveToColumn(0))?;
+        }
+
+        self.drawn = lines.len();
+        Ok(())
+    }
+
+    /// Erases the block, if drawn.
+    fn clear(&mut self) -> Result<()> {
+        if self.drawn > 0 {
+            execute!(
+                io::stdout(),
+                cursor::MoveToColumn(0),
+                terminal::Clear(ClearType::FromCursorDown)
+            )?;
+            self.drawn = 0;
+        }
+        Ok(())
+    }
+
+    /// Prints `text` where the block is and redraws the block under it.
+    fn print_above(&mut self, text: &str) -> Result<()> {
+        let redraw = self.drawn > 0;
+        self.clear()?;
+        say(text);
+        if redraw {
+            self.draw()?;
+        }
+        Ok(())
+    }
+
+    /// Leaves the small-file line in place and erases the rest of the
+    /// block, so output from a file processed on its own starts below.
+    fn interrupt(&mut self) {
+        if self.drawn > 0 {
+            let _ = self.draw();
+            if self.small_done > 0 {
+                let _ = execute!(io::stdout(), cursor::MoveToNextLine(1));
+            }
+            let _ = execute!(io::stdout(), terminal::Clear(ClearType::FromCursorDown));
+            self.drawn = 0;
+        }
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.clear()?;
+        if self.small_done > 0 {
+            let elapsed = self.start_time.elapsed();
+            say(&format!(
+                "{} {} {} {} ({}) {} {:.1}s ({})",
+                Theme::current().done().green(),
+                Msg::Completed.bold(),
+                self.small_done,
+                match self.small_done {
+                    1 => Msg::SmallFile,
+                    _ => Msg::SmallFiles,
+                },
+                humanize::size(self.small_bytes),
+                Msg::In,
+                elapsed.as_secs_f64(),
+                humanize::rate(self.small_bytes as f64 / elapsed.as_secs_f64())
+            ));
+        }
+        if self.files_empty > 0 {
+            let message = match self.files_empty {
+                1 => Msg::EmptyFile,
+                _ => Msg::EmptyFiles,
+            };
+            say(&message.fill(&[&self.files_empty]));
+        }
+        Ok(())
+    }
+}
+
+/// Where `--key`, `--key-file`, `--key-env` or `--key-prompt` says the
+/// key comes from.
+fn key_source(args: &Args) -> Box<dyn KeySource> {
+    if let Some(path) = &args.key_file {
+        Box::new(KeyFile(path.clone()))
+    } else if let Some(var) = &args.key_env {
+        Box::new(KeyEnv(var.clone()))
+    } else if args.key_prompt {
+        Box::new(KeyPrompt)
+    } else {
+        Box::new(HexKey(args.key.clone().unwrap_or_default()))
+    }
+}
+
+/// Fills in `args` from the job file `spec`. Flags that were given win.
+fn apply_spec(args: &mut Args, spec: JobSpec) {
+    args.input = Some(spec.input);
+    let key_given =
+        args.key.is_some() || args.key_file.is_some() || args.key_env.is_some() || args.key_prompt;
+    match spec.key {
+        Some(_) if key_given => {}
+        Some(KeyRef::Hex(hex)) => args.key = Some(hex),
+        Some(KeyRef::File(path)) => args.key_file = Some(path),
+        Some(KeyRef::Env(var)) => args.key_env = Some(var),
+        Some(KeyRef::Prompt) => args.key_prompt = true,
+        None => {}
+    }
+    let options = spec.options;
+    args.recursive |= options.recursive;
+    args.force |= options.force;
+    if args.output_dir.is_none() {
+        args.output_dir = options.output_dir;
+    }
+    args.exclude.splice(0..0, options.exclude);
+    if args.buffer_size == DEFAULT_BUFFER_SIZE {
+        args.buffer_size = options.buffer_size;
+    }
+}
+
+/// The options `args` give for the run.
+fn process_options(args: &Args) -> ProcessOptions {
+    ProcessOptions {
+        recursive: args.recursive,
+        output_dir: args.output_dir.clone(),
+        exclude: args.exclude.clone(),
+        buffer_size: args.buffer_size,
+        force: args.force,
+    }
+}
+
+/// Carries out a `--tar-in` or `--tar-out` run: members of the input
+/// archive or files of the input become outputs, each transformed from
+/// keystream position 0, which go to separate files or into one archive.
+fn run_tar(job: &Job, args: &Args) -> Result<()> {
+    let to_stdout = args.tar_out.as_deref() == Some(Path::new("-"));
+    let mut archive = match &args.tar_out {
+        None => None,
+        Some(_) if to_stdout => Some(tar::Writer::new(
+            Box::new(BufWriter::new(io::stdout().lock())) as Box<dyn Write>,
+        )),
+        Some(path) => {
+            let file = File::create(path)
+                .with_context(|| format!("Failed to create archive: {}", path.display()))?;
+            Some(tar::Writer::new(
+                Box::new(BufWriter::new(file)) as Box<dyn Write>
+            ))
+        }
+    };
+    let (mut files, mut bytes) = (0, 0);
+
+    if args.tar_in {
+        let input = job.input();
+        let file = File::open(input)
+            .with_context(|| format!("Failed to open archive: {}", input.display()))?;
+        let out_dir = match job.output_dir() {
+            Some(dir) => dir.to_path_buf(),
+            None => job.root().join(OUTPUT_DIR),
+        };
+        let mut reader = tar::Reader::new(BufReader::new(file));
+        while let Some(member) = reader
+            .next_member()
+            .with_context(|| format!("Failed to read archive: {}", input.display()))?
+        {
+            if member.kind != tar::Kind::File {
+                continue;
+            }
+            match &mut archive {
+                Some(archive) => archive.append(&member, &mut reader, job.cipher().as_mut())?,
+                None => {
+                    let path = out_dir.join(container::checked_path(&member.path)?);
+                    if let Some(parent) = path.parent() {
+                        fs::create_dir_all(parent).phase(Phase::CreateDir, parent)?;
+                    }
+                    let mut output =
+                        BufWriter::new(File::create(&path).phase(Phase::CreateFile, &path)?);
+                    let written = transform::stream_with(
+                        &mut reader,
+                        &mut output,
+                        job.cipher().as_mut(),
+                        &mut vec![0; args.buffer_size],
+                        0,
+                        |_| Ok::<_, io::Error>(()),
+                    )
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+                    if written != member.size {
+                        anyhow::bail!("{} is cut short in the archive", member.path);
+                    }
+                }
+            }
+            files += 1;
+            bytes += member.size;
+        }
+    } else {
+        // clap only lets --tar-out through here.
+        let archive = archive.as_mut().unwrap();
+        let skip = args
+            .tar_out
+            .as_deref()
+            .and_then(|path| path.canonicalize().ok());
+        for input in job.inputs()? {
+            if Some(&input) == skip.as_ref() {
+                continue;
+            }
+            let file = File::open(&input)
+                .with_context(|| format!("Failed to open file: {}", input.display()))?;
+            let metadata = file.metadata()?;
+            let relative = input.strip_prefix(job.root()).unwrap_or(&input);
+            let member = tar::Member {
+                path: relative.to_string_lossy().replace('\\', "/"),
+                kind: tar::Kind::File,
+                size: metadata.len(),
+                mode: file_mode(&metadata),
+                mtime: metadata
+                    .modified()
+                    .ok()
+                    .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map_or(0, |since| since.as_secs()),
+            };
+            archive.append(&member, &mut BufReader::new(file), job.cipher().as_mut())?;
+            files += 1;
+            bytes += member.size;
+        }
+    }
+
+    if let Some(archive) = archive {
+        archive.finish()?;
+    }
+    let summary = format!(
+        "{} {} {} ({})",
+        Msg::Processed,
+        files,
+        Msg::Files,
+        humanize::size(bytes)
+    );
+    match to_stdout {
+        true => eprintln!("{}", summary),
+        false => println!("{}", summary),
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn file_mode(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode()
+}
+
+#[cfg(not(unix))]
+fn file_mode(metadata: &fs::Metadata) -> u32 {
+    match metadata.permissions().readonly() {
+        true => 0o444,
+        false => 0o644,
+    }
+}
+
+/// Prints the JXOR header of the file at `path` for `--info`.
+fn print_info(path: &Path) -> Result<()> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open file: {}", path.display()))?;
+    let header = Header::read_from(&mut BufReader::new(&mut file))
+        .with_context(|| format!("Failed to read header of {}", path.display()))?;
+    println!("Format:   JXOR version {}", header.version);
+    println!("Cipher:   xor");
+    println!(
+        "Framed:   {}",
+        if header.is_framed() { "yes" } else { "no" }
+    );
+    println!("Name:     {}", header.name);
+    println!(
+        "Size:     {} ({} bytes)",
+        humanize::size(header.size),
+        header.size
+    );
+    match (header.mtime, header.modified()) {
+        (0, _) => println!("Modified: unknown"),
+        (mtime, Some(_)) => println!("Modified: {}", manifest::rfc3339(mtime)),
+        (mtime, None) => println!("Modified: invalid ({})", mtime),
+    }
+    Ok(())
+}
+
+/// The job file `--save-job` writes for `args`, with absolute paths so it
+/// can be moved away from where it was written. Job files are meant to be
+/// shared and kept under version control, so the key itself is never
+/// saved, only where to get it from.
+fn spec_from_args(args: &Args) -> Result<JobSpec> {
+    let absolute = |path: &Path| std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let key = if let Some(path) = &args.key_file {
+        KeyRef::File(absolute(path))
+    } else if let Some(var) = &args.key_env {
+        KeyRef::Env(var.clone())
+    } else if args.key_prompt {
+        KeyRef::Prompt
+    } else if args.key.is_some() {
+        anyhow::bail!(
+            "--save-job won't write a key given with --key into the job file; \
+             use --key-file, --key-env or --key-prompt instead"
+        );
+    } else {
+        anyhow::bail!("--save-job needs a key source: --key-file, --key-env or --key-prompt");
+    };
+    let mut options = process_options(args);
+    options.output_dir = options.output_dir.as_deref().map(absolute);
+    Ok(JobSpec {
+        input: absolute(args.input.as_deref().context("No input given")?),
+        key: Some(key),
+        options,
+    })
+}
+
+/// Parses the command line and carries out the run it describes.
+pub fn run() -> Result<()> {
+    let mut args = Args::parse();
+    color::init(if args.plain {
+        ColorChoice::Never
+    } else {
+        args.color
+    });
+    i18n::init(args.lang.as_deref())?;
+    if args.selftest {
+        return selftest::run();
+    }
+    if let Some(path) = &args.info {
+        return print_info(path);
+    }
+    if let Some(path) = &args.job {
+        let spec = JobSpec::load(path)?;
+        apply_spec(&mut args, spec);
+    }
+    if let Some(path) = &args.save_job {
+        fs::write(path, spec_from_args(&args)?.to_toml())
+            .with_context(|| format!("Failed to write job file: {}", path.display()))?;
+        println!("Wrote {}", path.display());
+        return Ok(());
+    }
+    // clap requires an input unless --selftest, --info or --job is given,
+    // and a job file has one, but a missing input is an error rather than
+    // a panic if that ever changes.
+    let input = args.input.clone().context("No input given")?;
+    let source = key_source(&args);
+    let key = source.load()?;
+    if is_identity_key(&key) && !args.force {
+        anyhow::bail!(
+            "Key {} from {} is all zero bytes, so the output would be an unencrypted copy of \
+             the input; pass --force to copy anyway",
+            hex::encode(&key),
+            source.describe()
+        );
+    }
+    let builder = Job::builder()
+        .input(input)
+        .key(key)
+        .options(process_options(&args));
+    let job = lifecycle::install(builder, &args.hook).build()?;
+    if let Some(path) = &args.container {
+        if !job.input().is_dir() {
+            anyhow::bail!("--container needs a directory as input");
+        }
+        let entries = container::write(&job, path)?;
+        println!(
+            "Wrote {} {} ({}) to {}",
+            entries.len(),
+            Msg::Files,
+            humanize::size(entries.iter().map(|entry| entry.size).sum()),
+            path.display()
+        );
+        return Ok(());
+    }
+    if args.tar_in || args.tar_out.is_some() {
+        return run_tar(&job, &args);
+    }
+    if let Some(dir) = &args.extract {
+        let entries = container::extract(&job, job.input(), dir)?;
+        println!(
+            "Extracted {} {} ({}) to {}",
+            entries.len(),
+            Msg::Files,
+            humanize::size(entries.iter().map(|entry| entry.size).sum()),
+            dir.display()
+        );
+        return Ok(());
+    }
+    let key = job.key();
+
+    let config = config::Config::load(args.config.as_deref())?;
+    let mut theme = Theme::from_config(&config)?;
+    if args.ascii || args.plain || env::var_os("TERM").is_some_and(|term| term == "dumb") {
+        theme.set_ascii();
+    }
+    theme.install();
+
+    if args.nice || args.idle {
+        if let Err(e) = priority::lower(args.idle) {
+            eprintln!("Failed to lower process priority: {}", e);
+        }
+    }
+
+    if let Some(cpus) = &args.cpus {
+        if let Err(e) = affinity::pin_current_thread(&cpus.0) {
+            eprintln!("Failed to restrict to CPUs {:?}: {}", cpus.0, e);
+        }
+    }
+    if args.pin_threads && args.jobs == 1 {
+        pin_worker(&args, 0);
+    }
+
+    let total_start = Instant::now();
+    let input_path = job.input().to_path_buf();
+    let root = job.root().to_path_buf();
+    if let Some(outputs) = &args.audit {
+        let outputs = match outputs {
+            Some(dir) => Some(normalize_path(dir).canonicalize().with_context(|| {
+                format!("Failed to resolve output directory: {}", dir.display())
+            })?),
+            None => job.output_dir().map(Path::to_path_buf),
+        };
+        let sources = match input_path.is_dir() {
+            true => {
+                let walker = order::walker(&root, args.sort)
+                    .into_iter()
+                    .filter_entry(|e| {
+                        job.includes(e)
+                            && outputs
+                                .as_deref()
+                                .is_none_or(|dir| !e.path().starts_with(dir))
+                    });
+                collect_files(walker, args.strict)?
+                    .into_iter()
+                    .map(|(_, path)| path)
+                    .collect()
+            }
+            false => vec![input_path.clone()],
+        };
+        return audit::run(
+            &root,
+            &sources,
+            outputs.as_deref(),
+            &|| job.cipher(),
+            args.buffer_size,
+        );
+    }
+    // Where the lock and checkpoint go: the run's own output directory.
+    let state_dir = job
+        .output_dir()
+        .map_or_else(|| root.join(OUTPUT_DIR), Path::to_path_buf);
+    let _lock = lock::acquire(&state_dir, args.lock)?;
+    if args.resume {
+        let finished = checkpoint::load(&root, &state_dir, key)?;
+        println!(
+            "Resuming a stopped run: {} already done",
+            match finished {
+                1 => "1 file was".to_string(),
+                n => format!("{} files were", n),
+            }
+        );
+    }
+
+    if args.autotune {
+        if let Some(sample) = autotune::pick_sample(&input_path, args.recursive) {
+            let (size, speed) = autotune::choose_buffer_size(&sample, key)?;
+            println!(
+                "Autotune: using {} buffers ({} on {})",
+                humanize::size(size as u64),
+                humanize::rate(speed),
+                get_relative_path(&sample)?
+            );
+            args.buffer_size = size;
+        }
+    }
+
+    let limiter = RateLimiter::new(args.limit_rate);
+    let pool = BufferPool::new(args.buffer_size);
+    // Started before the pause listener so that its signal handlers,
+    // which restore the screen, run before the listener's.
+    let tui = match args.tui {
+        true => Some(tui::start(&get_relative_path(&input_path)?)?),
+        false => None,
+    };
+    let pause_listener = pause::listen();
+    resize::watch();
+    if args.stats.is_some() || args.stats_csv.is_some() {
+        stats::enable();
+    }
+    if args.manifest {
+        manifest::enable();
+    }
+    if let Some(socket) = &args.progress_socket {
+        monitor::serve(socket)?;
+    }
+    let sampler = (args.verbose > 0).then(histogram::Sampler::start);
+    let trace_path = env::var_os(trace::ENV_VAR).filter(|path| !path.is_empty());
+    if trace_path.is_some() || args.verbose > 2 {
+        let path = trace_path.map_or_else(|| PathBuf::from(trace::DEFAULT_PATH), PathBuf::from);
+        trace::enable(&path)?;
+        eprintln!("Tracing I/O to {}", path.display());
+    }
+    if !Theme::current().live() && tui.is_none() {
+        heartbeat::enable();
+    }
+    if args.terminal_progress && atty::is(atty::Stream::Stdout) {
+        taskbar::enable(&format!("xortool {}", get_relative_path(&input_path)?));
+    }
+    job.hooks().run_start(&input_path);
+    let res = if input_path.is_dir() {
+        process_directory(&input_path, &job, &args, &limiter, &pool)
+    } else if !special::check_input(&input_path, args.strict)?
+        || checkpoint::is_done(&input_path, input_path.metadata()?.len())
+    {
+        Ok(())
+    } else {
+        let size = input_path.metadata()?.len();
+        space::preflight(
+            &root,
+            space_needed(size, &input_path, &job),
+            args.allow_low_space,
+        )?;
+        tui::set_totals(1, size);
+        process_file(&input_path, &job, &args, &limiter, None, &pool, None).inspect_err(|e| {
+            if e.is::<pause::Stopped>() {
+                if Theme::current().live() {
+                    // The cursor is at the start of the progress line.
+                    let _ = execute!(io::stdout(), terminal::Clear(ClearType::FromCursorDown));
+                }
+                say("Stopped");
+            }
+        })
+    };
+    drop(tui);
+    taskbar::finish();
+    if let Some(sampler) = sampler {
+        histogram::print_summary(sampler.finish(), args.verbose > 1);
+    }
+
+    if let Some(limit) = args.stats {
+        stats::print_table(limit);
+    }
+    if let Some(csv) = &args.stats_csv {
+        stats::write_csv(csv)?;
+    }
+    if res.is_ok() {
+        checkpoint::remove(&state_dir);
+    }
+    if args.manifest && res.is_ok() {
+        let path = manifest::write(&root, key, args.sparse, args.normalize)?;
+        println!("Wrote {}", get_relative_path(&path)?);
+    }
+
+    if special::skipped() > 0 {
+        let message = match special::skipped() {
+            1 => Msg::SpecialFile,
+            _ => Msg::SpecialFiles,
+        };
+        println!("{}", message.fill(&[&special::skipped()]));
+    }
+    if changed::unstable() > 0 {
+        let message = match changed::unstable() {
+            1 => Msg::UnstableFile,
+            _ => Msg::UnstableFiles,
+        };
+        println!("{}", message.fill(&[&changed::unstable()]));
+    }
+
+    let total_duration = total_start.elapsed();
+    println!("\n{}: {:.1?}", Msg::TotalTime, total_duration);
+
+    monitor::emit(monitor::Event::Finished {
+        error: res.as_ref().err().map(|e| format!("{:#}", e)),
+        duration: total_duration,
+    });
+    monitor::close();
+    job.hooks().run_complete(
+        &input_path,
+        total_duration,
+        res.as_ref().err().map(|e| &**e),
+    );
+
+    if args.notify {
+        let (title, body) = match &res {
+            Ok(()) => (
+                "xortool finished",
+                format!(
+                    "{} {} ({} {}, {}) {} {:.1?}",
+                    Msg::Processed,
+                    get_relative_path(&input_path)?,
+                    job.stats().files_done(),
+                    Msg::Files,
+                    humanize::size(job.stats().bytes_done()),
+                    Msg::In,
+                    total_duration
+                ),
+            ),
+            Err(e) => ("xortool failed", format!("{:#}", e)),
+        };
+        if let Err(e) = notify::send(title, &body) {
+            eprintln!("Failed to send notification: {:#}", e);
+        }
+    }
+
+    if res.as_ref().is_err_and(|e| e.is::<pause::Stopped>()) {
+        let path = checkpoint::write(&root, &state_dir, key)?;
+        println!(
+            "Saved {}; run again with --resume to carry on from here",
+            get_relative_path(&path)?
+        );
+        // Restores the terminal, which exiting directly would skip.
+        drop(pause_listener);
+        std::process::exit(130);
+    }
+    res
+}
+
+/// Parses a byte count such as `65536`, `64K`, `4M`, `1GiB` or `512kb`.
+/// Suffixes are binary multiples.
+fn parse_size(text: &str) -> Result<u64> {
+    let trimmed = text.trim();
+    let split = trimmed
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(trimmed.len());
+    let (digits, suffix) = trimmed.split_at(split);
+
+    let value: u64 = digits
+        .parse()
+        .with_context(|| format!("Invalid size: '{}'", text))?;
+    let multiplier: u64 = match suffix.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        "t" | "tb" | "tib" => 1 << 40,
+        other => anyhow::bail!("Unknown size suffix '{}' in '{}'", other, text),
+    };
+
+    value
+        .checked_mul(multiplier)
+        .with_context(|| format!("Size out of range: '{}'", text))
+}
+
+fn parse_buffer_size(text: &str) -> Result<usize> {
+    let size = parse_size(text)?;
+    if size == 0 {
+        anyhow::bail!("Buffer size must be greater than zero");
+    }
+    usize::try_from(size).with_context(|| format!("Buffer size too large: '{}'", text))
+}
+
+fn process_directory(
+    root: &Path,
+    job: &Job,
+    args: &Args,
+    limiter: &RateLimiter,
+    pool: &BufferPool,
+) -> Result<()> {
+    let walker = order::walker(root, args.sort)
+        .into_iter()
+        .filter_entry(|e| job.includes(e) && !(args.verify && checksum::is_sidecar(e.path())));
+
+    #[cfg(target_os = "linux")]
+    let mut ring = match args.io_uring {
+        true => match uring::Ring::new() {
+            Ok(ring) => Some(ring),
+            Err(e) => {
+                eprintln!("io_uring unavailable ({}), using buffered I/O", e);
+                None
+            }
+        },
+        false => None,
+    };
+    #[cfg(not(target_os = "linux"))]
+    if args.io_uring {
+        eprintln!("io_uring is only available on Linux, using buffered I/O");
+    }
+
+    let mut files = collect_files(walker, args.strict)?;
+    if args.resume {
+        files.retain(|(size, path)| !checkpoint::is_done(path, *size));
+    }
+    order::sort(&mut files, args.sort);
+    let mut duplicates = Vec::new();
+    if args.dedup {
+        duplicates = dedup::split_duplicates(&mut files)?;
+    }
+
+    let needed = files
+        .iter()
+        .map(|(size, path)| space_needed(*size, path, job))
+        .sum();
+    space::preflight(root, needed, args.allow_low_space)?;
+
+    let run = Arc::new(Mutex::new(RunProgress::new(&files, args.jobs > 1)));
+    tui::set_totals(files.len(), files.iter().map(|(size, _)| size).sum());
+
+    #[cfg(target_os = "linux")]
+    let processed = match &mut ring {
+        Some(ring) => process_files_uring(ring, &files, job, args, limiter, &run, pool),
+        None if args.jobs > 1 => process_files_parallel(files, job, args, limiter, &run, pool),
+        None => process_files_sequential(&files, job, args, limiter, &run, pool),
+    };
+    #[cfg(not(target_os = "linux"))]
+    let processed = if args.jobs > 1 {
+        process_files_parallel(files, job, args, limiter, &run, pool)
+    } else {
+        process_files_sequential(&files, job, args, limiter, &run, pool)
+    };
+    if let Err(e) = processed {
+        if e.is::<pause::Stopped>() {
+            let mut run = run.lock().unwrap();
+            run.finish()?;
+            say(&format!(
+                "Stopped after {} of {} files",
+                run.files_done, run.files_total
+            ));
+        }
+        return Err(e);
+    }
+
+    run.lock().unwrap().finish()?;
+
+    if !duplicates.is_empty() {
+        let mut saved = 0;
+        for (duplicate, original) in &duplicates {
+            if failure::is_failed(original) {
+                continue;
+            }
+            dedup::link_output(&job.output_path(original)?, &job.output_path(duplicate)?)?;
+            manifest::record_duplicate(original, duplicate, job.output_path(duplicate)?);
+            if args.fsync {
+                sync_dir(&job.output_path(duplicate)?)?;
+            }
+            let size = duplicate.metadata()?.len();
+            saved += size;
+            stats::record(
+                &get_relative_path(duplicate)?,
+                size,
+                Duration::ZERO,
+                Status::Linked,
+                None,
+            );
+        }
+        say(&format!(
+            "{} {} {} {} ({} {})",
+            Theme::current().done().green(),
+            Msg::Linked.bold(),
+            duplicates.len(),
+            Msg::DuplicateFiles,
+            humanize::size(saved),
+            Msg::NotRewritten
+        ));
+    }
+
+    if failure::count() > 0 {
+        anyhow::bail!(
+            "{} of {} outputs could not be written",
+            failure::count(),
+            run.lock().unwrap().files_total
+        );
+    }
+    Ok(())
+}
+
+/// Space the output of `input` will take beyond what its previous output,
+/// which it replaces, already does.
+fn space_needed(size: u64, input: &Path, job: &Job) -> u64 {
+    let previous = job
+        .output_path(input)
+        .ok()
+        .and_then(|output| output.metadata().ok())
+        .map_or(0, |metadata| metadata.len());
+    size.saturating_sub(previous)
+}
+
+/// Processes files one at a time in walk order, with one helper thread
+/// opening the next while the current one finishes.
+fn process_files_sequential(
+    files: &[(u64, PathBuf)],
+    job: &Job,
+    args: &Args,
+    limiter: &RateLimiter,
+    run: &Arc<Mutex<RunProgress>>,
+    pool: &BufferPool,
+) -> Result<()> {
+    thread::scope(|scope| {
+        // No buffer: each send waits for the file to be taken, which
+        // keeps the helper one file ahead. It stops once `opened` is
+        // dropped.
+        let (sender, opened) = mpsc::sync_channel(0);
+        let len = args.buffer_size;
+        scope.spawn(move || {
+            for (_, path) in files {
+                if sender.send(prefetch(path, len)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        for (_, path) in files {
+            let prefetched = opened.recv().ok().and_then(io::Result::ok);
+            if pause::quit_requested() {
+                return Err(pause::Stopped.into());
+            }
+            process_file(path, job, args, limiter, Some(run), pool, prefetched)?;
+        }
+        Ok(())
+    })
+}
+
+/// Opens `path` and starts reading its first `len` bytes, so on
+/// high-latency storage the open, stat and first read of the next file
+/// overlap with finishing the current one.
+fn prefetch(path: &Path, len: usize) -> io::Result<File> {
+    let file = File::open(path)?;
+    let size = file.metadata()?.len();
+    cache::will_need(&file, size.min(len as u64));
+    Ok(file)
+}
+
+/// Gathers the regular files of a walk together with their sizes, showing
+/// a spinner with running counts while the walk is slow.
+fn collect_files(
+    walker: impl Iterator<Item = walkdir::Result<DirEntry>>,
+    strict: bool,
+) -> Result<Vec<(u64, PathBuf)>> {
+    let is_tty = Theme::current().live();
+    let mut last_update = Instant::now();
+    let mut frame = 0;
+    let mut bytes = 0;
+
+    let mut files = Vec::new();
+    for entry in walker {
+        if pause::quit_requested() {
+            return Err(pause::Stopped.into());
+        }
+        let entry = entry?;
+        if entry.file_type().is_file() {
+            let size = entry.metadata()?.len();
+            bytes += size;
+            files.push((size, entry.into_path()));
+        } else if !entry.file_type().is_dir() {
+            if frame > 0 {
+                execute!(
+                    io::stdout(),
+                    cursor::MoveToColumn(0),
+                    terminal::Clear(ClearType::CurrentLine)
+                )?;
+            }
+            special::skip(&entry, strict)?;
+        }
+
+        if is_tty && last_update.elapsed() > SPINNER_INTERVAL {
+            draw_scan_status(frame, files.len(), bytes)?;
+            frame += 1;
+            last_update = Instant::now();
+        }
+    }
+
+    if frame > 0 {
+        execute!(
+            io::stdout(),
+            cursor::MoveToColumn(0),
+            terminal::Clear(ClearType::CurrentLine)
+        )?;
+    }
+    Ok(files)
+}
+
+fn draw_scan_status(frame: usize, files: usize, bytes: u64) -> Result<()> {
+    let mut stdout = io::stdout();
+    execute!(
+        stdout,
+        cursor::MoveToColumn(0),
+        terminal::Clear(ClearType::CurrentLine)
+    )?;
+    write!(
+        stdout,
+        "{} {}: {} {}, {}",
+        Theme::current().spinner(frame).cyan(),
+        Msg::Scanning,
+        files.to_string().bold(),
+        Msg::Files,
+        humanize::size(bytes)
+    )?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Processes files on `args.jobs` worker threads, largest first. Workers
+/// pull the next file from a shared queue as soon as they finish one, so
+/// the big files start early and the small ones fill in the gaps instead
+/// of one thread grinding a huge file at the end while the rest idle.
+fn process_files_parallel(
+    mut files: Vec<(u64, PathBuf)>,
+    job: &Job,
+    args: &Args,
+    limiter: &RateLimiter,
+    run: &Arc<Mutex<RunProgress>>,
+    pool: &BufferPool,
+) -> Result<()> {
+    files.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+
+    let workers = worker_limit(args)?.min(files.len().max(1) as u16);
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let first_error = Mutex::new(None);
+
+    thread::scope(|scope| {
+        for worker in 0..workers {
+            let (next, failed, first_error, files) = (&next, &failed, &first_error, &files);
+            scope.spawn(move || {
+                let local_pool;
+                let pool = if args.pin_threads {
+                    pin_worker(args, worker as usize);
+                    // Allocated after pinning so first touch lands on the
+                    // worker's own NUMA node.
+                    local_pool = BufferPool::new(args.buffer_size);
+                    &local_pool
+                } else {
+                    pool
+                };
+
+                while !failed.load(Ordering::Relaxed) {
+                    let Some((_, path)) = files.get(next.fetch_add(1, Ordering::Relaxed)) else {
+                        break;
+                    };
+                    if pause::quit_requested() {
+                        failed.store(true, Ordering::Relaxed);
+                        first_error
+                            .lock()
+                            .unwrap()
+                            .get_or_insert(pause::Stopped.into());
+                        break;
+                    }
+                    if let Err(e) = process_file(path, job, args, limiter, Some(run), pool, None) {
+                        failed.store(true, Ordering::Relaxed);
+                        first_error.lock().unwrap().get_or_insert(e);
+                    }
+                }
+            });
+        }
+    });
+
+    match first_error.into_inner().unwrap() {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Lowers `--jobs` so that all workers together stay within
+/// `--max-open-files` and `--max-memory`.
+fn worker_limit(args: &Args) -> Result<u16> {
+    // Input and output, plus the second pair direct I/O opens.
+    let files_per_job = if args.direct_io { 4 } else { 2 };
+    let buffers = if args.pipeline { pipeline::BUFFERS } else { 1 };
+    // Pool buffers plus the default BufReader and BufWriter capacity.
+    let memory_per_job =
+        (buffers * args.buffer_size.div_ceil(pool::ALIGN) * pool::ALIGN + 2 * 8192) as u64;
+
+    let mut jobs = u64::from(args.jobs);
+    if let Some(max) = args.max_open_files {
+        jobs = jobs.min(max.saturating_sub(RESERVED_FDS) / files_per_job);
+    }
+    if let Some(max) = args.max_memory {
+        jobs = jobs.min(max / memory_per_job);
+    }
+
+    if jobs == 0 {
+        anyhow::bail!(
+            "--max-open-files/--max-memory are too low for a single job \
+             (needs {} files and {})",
+            files_per_job + RESERVED_FDS,
+            humanize::size(memory_per_job)
+        );
+    }
+    if jobs < u64::from(args.jobs) {
+        eprintln!("Running {} jobs to stay within resource limits", jobs);
+    }
+    Ok(jobs as u16)
+}
+
+/// Pins the calling thread to the `worker`th CPU of `--cpus`, or of the
+/// CPUs available to the process, wrapping around if there are more
+/// workers than CPUs.
+fn pin_worker(args: &Args, worker: usize) {
+    let cpus = match &args.cpus {
+        Some(cpus) => cpus.0.clone(),
+        None => affinity::available_cpus(),
+    };
+    let cpu = cpus[worker % cpus.len()];
+    if let Err(e) = affinity::pin_current_thread(&[cpu]) {
+        eprintln!("Failed to pin worker {} to CPU {}: {}", worker, cpu, e);
+    }
+}
+
+/// Processes files in walk order, sending the small ones through the
+/// ring in batches and everything larger through the regular per-file
+/// path.
+#[cfg(target_os = "linux")]
+fn process_files_uring(
+    ring: &mut uring::Ring,
+    files: &[(u64, PathBuf)],
+    job: &Job,
+    args: &Args,
+    limiter: &RateLimiter,
+    run: &Arc<Mutex<RunProgress>>,
+    pool: &BufferPool,
+) -> Result<()> {
+    let mut batch = Vec::with_capacity(uring::BATCH_FILES);
+
+    for (size, path) in files {
+        if pause::quit_requested() {
+            return Err(pause::Stopped.into());
+        }
+        // Batched files are counted on the small-file line, so only those
+        // below its threshold go through the ring.
+        if *size >= SMALL_FILE_THRESHOLD || *size > uring::SMALL_FILE_LIMIT {
+            process_file(path, job, args, limiter, Some(run), pool, None)?;
+            continue;
+        }
+        if skip_encrypted(path, args, Some(run))? {
+            continue;
+        }
+
+        batch.push(uring::BatchItem {
+            output: job.output_path(path)?,
+            input: path.clone(),
+            size: *size,
+        });
+        if batch.len() == uring::BATCH_FILES {
+            flush_uring_batch(ring, &mut batch, job, args, limiter, run)?;
+        }
+    }
+
+    flush_uring_batch(ring, &mut batch, job, args, limiter, run)
+}
+
+#[cfg(target_os = "linux")]
+fn flush_uring_batch(
+    ring: &mut uring::Ring,
+    batch: &mut Vec<uring::BatchItem>,
+    job: &Job,
+    args: &Args,
+    limiter: &RateLimiter,
+    run: &Arc<Mutex<RunProgress>>,
+) -> Result<()> {
+    if batch.is_empty() {
+        return Ok(());
+    }
+
+    while pause::is_paused() {
+        thread::sleep(PAUSE_POLL_INTERVAL);
+    }
+    if pause::quit_requested() {
+        return Err(pause::Stopped.into());
+    }
+
+    let mut printers = Vec::with_capacity(batch.len());
+    for item in batch.iter() {
+        job.hooks().file_start(&FileStart {
+            input: &item.input,
+            output: &item.output,
+        });
+        let mut progress =
+            ProgressPrinter::aggregated(&get_relative_path(&item.input)?, Arc::clone(run));
+        progress.sync = args.fsync.then(|| item.output.clone());
+        progress.recheck = Some((item.input.clone(), args.on_change));
+        printers.push(progress);
+    }
+    let start_time = Instant::now();
+    uring::process_batch(ring, batch, &|| job.cipher())?;
+    let bytes = batch.iter().map(|item| item.size).sum();
+    limiter.consume(bytes);
+    histogram::count(bytes);
+
+    for (item, mut progress) in batch.drain(..).zip(printers) {
+        progress.start_time = start_time;
+        let result = FileResult {
+            input: &item.input,
+            output: &item.output,
+            bytes: item.size,
+            duration: start_time.elapsed(),
+            error: None,
+        };
+        job.hooks().file_complete(&result);
+        progress.on_file_complete(&result)?;
+    }
+    Ok(())
+}
+
+/// Transforms one file and records how it went. A file skipped from the
+/// dashboard isn't an error: its partial output is removed and the run
+/// goes on.
+fn process_file(
+    input_path: &Path,
+    job: &Job,
+    args: &Args,
+    limiter: &RateLimiter,
+    run: Option<&Arc<Mutex<RunProgress>>>,
+    pool: &BufferPool,
+    prefetched: Option<File>,
+) -> Result<()> {
+    if skip_encrypted(input_path, args, run)? {
+        return Ok(());
+    }
+    let filename = get_relative_path(input_path)?;
+    job.hooks().file_start(&FileStart {
+        input: input_path,
+        output: &job.output_path(input_path)?,
+    });
+    let mut result = transform_file(input_path, job, args, limiter, run, pool, prefetched);
+    for _ in 1..changed::ATTEMPTS {
+        let Some(changed) = result
+            .as_ref()
+            .err()
+            .and_then(|e| e.downcast_ref::<changed::Changed>())
+            .filter(|_| args.on_change == ChangeMode::Retry)
+        else {
+            break;
+        };
+        let message = format!("{} {}; retrying", filename, changed);
+        match run {
+            Some(run) => {
+                let mut run = run.lock().unwrap();
+                run.retry();
+                run.print_above(&message)?;
+            }
+            None => eprintln!("{}", message),
+        }
+        result = transform_file(input_path, job, args, limiter, run, pool, None);
+    }
+    match result {
+        Err(e) if e.is::<pause::Stopped>() => {
+            let output = job.output_path(input_path)?;
+            if checkpoint::is_partial(input_path) {
+                fs::rename(&output, checkpoint::partial_path(&output))
+                    .phase(Phase::Rename, &output)?;
+            } else {
+                let _ = fs::remove_file(output);
+            }
+            Err(e)
+        }
+        Err(e) if e.is::<pause::Skipped>() => {
+            let _ = fs::remove_file(job.output_path(input_path)?);
+            if let Some(run) = run {
+                run.lock().unwrap().skip()?;
+            }
+            monitor::emit(monitor::Event::Skipped { file: &filename });
+            stats::record(&filename, 0, Duration::ZERO, Status::Skipped, None);
+            Ok(())
+        }
+        Err(e) => {
+            trace::log(&filename, "failed", format_args!("error=\"{:#}\"", e));
+            job.hooks().file_complete(&FileResult {
+                input: input_path,
+                output: &job.output_path(input_path)?,
+                bytes: 0,
+                duration: Duration::ZERO,
+                error: Some(&*e),
+            });
+            // In directory runs one unwritable output doesn't end the run.
+            let unwritable = failure::find(&e).filter(|_| run.is_some());
+            monitor::emit(monitor::Event::Failed {
+                file: &filename,
+                error: &format!("{:#}", e),
+            });
+            stats::record(&filename, 0, Duration::ZERO, Status::Failed, None);
+            if let Some(run) = run {
+                let mut run = run.lock().unwrap();
+                run.fail()?;
+                if let Some(failed) = unwritable {
+                    run.print_above(&failed.to_string())?;
+                }
+            }
+            if unwritable.is_some() {
+                let _ = fs::remove_file(job.output_path(input_path)?);
+                failure::record(input_path);
+                return Ok(());
+            }
+            Err(e)
+        }
+        Ok(()) => Ok(()),
+    }
+}
+
+/// Checks for an input that already starts with a JXOR header, which
+/// would be encrypted twice, and says so. Returns whether `--on-encrypted`
+/// wants it skipped, having counted the skip.
+fn skip_encrypted(
+    input_path: &Path,
+    args: &Args,
+    run: Option<&Arc<Mutex<RunProgress>>>,
+) -> Result<bool> {
+    if args.strip_header || !encrypted::has_header(input_path) {
+        return Ok(false);
+    }
+    let filename = get_relative_path(input_path)?;
+    let message = match args.on_encrypted {
+        EncryptedMode::Skip => format!(
+            "Skipped {}: it already starts with a JXOR header (see --on-encrypted)",
+            filename
+        ),
+        EncryptedMode::Warn => format!(
+            "Warning: {} already starts with a JXOR header; encrypting it again",
+            filename
+        ),
+    };
+    match run {
+        Some(run) => run.lock().unwrap().print_above(&message)?,
+        None => eprintln!("{}", message),
+    }
+    if args.on_encrypted == EncryptedMode::Warn {
+        return Ok(false);
+    }
+    if let Some(run) = run {
+        let mut run = run.lock().unwrap();
+        run.start();
+        run.skip()?;
+    }
+    monitor::emit(monitor::Event::Skipped { file: &filename });
+    stats::record(&filename, 0, Duration::ZERO, Status::Skipped, None);
+    Ok(true)
+}
+
+fn transform_file(
+    input_path: &Path,
+    job: &Job,
+    args: &Args,
+    limiter: &RateLimiter,
+    run: Option<&Arc<Mutex<RunProgress>>>,
+    pool: &BufferPool,
+    prefetched: Option<File>,
+) -> Result<()> {
+    let filename = get_relative_path(input_path)?;
+    let output_path = job.output_path(input_path)?;
+    let mut cipher = job.cipher();
+
+    let mut file = match prefetched {
+        Some(file) => file,
+        None => File::open(input_path)
+            .with_context(|| format!("Failed to open file: {}", input_path.display()))?,
+    };
+    let total_size = file.metadata()?.len();
+    trace::log(&filename, "open", format_args!("size={}", total_size));
+
+    let mut progress = match run {
+        Some(run) if total_size < SMALL_FILE_THRESHOLD && !args.checksum => {
+            ProgressPrinter::aggregated(&filename, Arc::clone(run))
+        }
+        Some(run) if args.jobs > 1 => {
+            ProgressPrinter::worker(&filename, total_size, Arc::clone(run))
+        }
+        None if args.jobs > 1 => ProgressPrinter::completion_only(&filename, None),
+        _ => {
+            if let Some(run) = run {
+                run.lock().unwrap().interrupt();
+            }
+            ProgressPrinter::new(&filename, run.cloned())?
+        }
+    };
+    progress.sync = args.fsync.then(|| output_path.clone());
+    progress.recheck = Some((input_path.to_path_buf(), args.on_change));
+    let started = Instant::now();
+    let complete = |progress: &mut ProgressPrinter, bytes| {
+        let result = FileResult {
+            input: input_path,
+            output: &output_path,
+            bytes,
+            duration: started.elapsed(),
+            error: None,
+        };
+        job.hooks().file_complete(&result);
+        progress.on_file_complete(&result)
+    };
+
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent).phase(Phase::CreateDir, parent)?;
+    }
+
+    if args.dedup {
+        // A previous run may have left this output hard-linked to others;
+        // truncating it in place would rewrite them too.
+        let _ = fs::remove_file(&output_path);
+    }
+
+    let hashing =
+        args.checksum || args.sidecar || args.verify || args.manifest || args.verify_after;
+    let passthrough = cipher.is_identity()
+        && !hashing
+        && !args.framed
+        && !args.unframe
+        && !args.header
+        && !args.strip_header;
+    // Only the plain buffered loop can pick up a stopped output again.
+    let resumable = !(passthrough
+        || hashing
+        || args.mmap
+        || args.direct_io
+        || args.sparse
+        || args.pipeline
+        || args.framed
+        || args.unframe
+        || args.header
+        || args.strip_header);
+    let resumed = match resumable {
+        true => checkpoint::resume_partial(input_path, &output_path, total_size),
+        false => None,
+    };
+    let (mut output_file, start) = match resumed {
+        Some((output_file, offset)) => {
+            file.seek(SeekFrom::Start(offset))?;
+            (output_file, offset)
+        }
+        None => {
+            let output_file = File::create(&output_path).phase(Phase::CreateFile, &output_path)?;
+            (output_file, 0)
+        }
+    };
+
+    // Nothing to read or write; the output just has to exist. Hashing
+    // still goes through the loop below so the empty digests get recorded,
+    // and a header is written or checked even for an empty file.
+    if total_size == 0 && !hashing && !args.header && !args.strip_header {
+        complete(&mut progress, 0)?;
+        return Ok(());
+    }
+
+    if passthrough {
+        if pause::quit_requested() {
+            return Err(pause::Stopped.into());
+        }
+        copy_passthrough(&mut file, &mut output_file).with_context(|| {
+            format!(
+                "Failed to copy {} to {}",
+                input_path.display(),
+                output_path.display()
+            )
+        })?;
+        limiter.consume(total_size);
+        histogram::count(total_size);
+        complete(&mut progress, total_size)?;
+        return Ok(());
+    }
+
+    let skips = pause::skips();
+    let mut reported = start;
+    let mut report = |processed: u64| {
+        if pause::quit_requested() {
+            return Err(pause::Stopped.into());
+        }
+        if pause::skips() != skips {
+            return Err(pause::Skipped.into());
+        }
+        if pause::is_paused() {
+            progress.wait_while_paused(processed, total_size)?;
+        }
+        limiter.consume(processed.saturating_sub(reported));
+        histogram::count(processed.saturating_sub(reported));
+        trace::log(
+            &filename,
+            "progress",
+            format_args!("offset={} len={}", reported, processed - reported),
+        );
+        reported = processed;
+        let event = FileProgress {
+            path: input_path,
+            done: processed,
+            total: total_size,
+        };
+        job.hooks().progress(&event);
+        progress.on_progress(&event)
+    };
+
+    if args.mmap {
+        let mapped = mmap::transform(
+            &file,
+            &mut output_file,
+            total_size,
+            cipher.as_mut(),
+            &mut report,
+        );
+        match mapped {
+            Ok(()) => {
+                complete(&mut progress, total_size)?;
+                return Ok(());
+            }
+            // Nothing was written yet, so the streaming paths below start
+            // from a clean output.
+            Err(e) if e.is::<mmap::Unmappable>() => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    if args.direct_io {
+        let direct =
+            direct::transform(input_path, &output_path, cipher.as_mut(), pool, &mut report);
+        match direct {
+            Ok(()) => {
+                complete(&mut progress, total_size)?;
+                return Ok(());
+            }
+            Err(e) if e.is::<pause::Stopped>() || e.is::<pause::Skipped>() => return Err(e),
+            Err(e) => {
+                eprintln!("{:#}; falling back to buffered I/O", e);
+                output_file.set_len(0)?;
+                output_file.seek(SeekFrom::Start(0))?;
+            }
+        }
+    }
+
+    if args.sparse {
+        if let Some(regions) = sparse::data_regions(&file, total_size)? {
+            sparse::transform(
+                &mut file,
+                &mut output_file,
+                &regions,
+                total_size,
+                cipher.as_mut(),
+                pool,
+                &mut report,
+            )?;
+            complete(&mut progress, total_size)?;
+            return Ok(());
+        }
+    }
+
+    if !args.no_fadvise {
+        cache::sequential(&file);
+    }
+
+    let mut reader = BufReader::new(trace::Traced::new(file, &filename));
+    let mut writer = BufWriter::new(trace::Traced::new(
+        failure::Output::new(output_file, &output_path),
+        &filename,
+    ));
+    let mut checksums = hashing.then(Checksums::default);
+
+    if args.header {
+        let mut header = Header::for_file(input_path, &reader.get_ref().get_ref().metadata()?);
+        if args.framed {
+            header.flags |= header::FLAG_FRAMED;
+        }
+        header.write_to(&mut writer)?;
+    }
+    let mut restore_mtime = None;
+    if args.strip_header {
+        let header = Header::read_from(&mut reader)
+            .with_context(|| format!("Failed to read header of {}", input_path.display()))?;
+        match (header.is_framed(), args.unframe) {
+            (true, false) => anyhow::bail!(
+                "{} was written with --framed; decrypt it with --unframe as well",
+                input_path.display()
+            ),
+            (false, true) => {
+                anyhow::bail!("{} was not written with --framed", input_path.display())
+            }
+            _ => {}
+        }
+        let payload = total_size.saturating_sub(header.encoded_len() as u64);
+        let expected = match header.is_framed() {
+            true if header.size <= payload => frame::framed_len(header.size),
+            true => u64::MAX,
+            false => header.size,
+        };
+        if payload != expected {
+            anyhow::bail!(
+                "{} holds {} bytes after its header, but the header says the original was {} bytes",
+                input_path.display(),
+                payload,
+                header.size
+            );
+        }
+        restore_mtime = header.modified();
+    }
+
+    if args.framed {
+        frame::write_framed(&mut reader, &mut writer, cipher.as_mut(), &mut report)?;
+    } else if args.unframe {
+        frame::read_framed(&mut reader, &mut writer, cipher.as_mut(), &mut report)
+            .with_context(|| format!("Failed to unframe {}", input_path.display()))?;
+    } else if args.pipeline {
+        pipeline::transform(
+            &mut reader,
+            &mut writer,
+            cipher.as_mut(),
+            pool,
+            checksums.as_mut(),
+            &mut report,
+        )?;
+    } else {
+        let mut processed = start;
+        let mut buffer = pool.get();
+
+        loop {
+            let read_count = reader.read(&mut buffer)?;
+            if read_count == 0 {
+                break;
+            }
+
+            match &mut checksums {
+                Some(sums) => sums.apply(&mut buffer[..read_count], cipher.as_mut(), processed),
+                None => cipher.apply(processed, &mut buffer[..read_count]),
+            }
+            writer.write_all(&buffer[..read_count])?;
+
+            processed += read_count as u64;
+            if let Err(e) = report(processed) {
+                if e.is::<pause::Stopped>() && resumable {
+                    writer.flush()?;
+                    checkpoint::partial(input_path, total_size, processed);
+                }
+                return Err(e);
+            }
+        }
+    }
+
+    writer.flush()?;
+    if let Some(mtime) = restore_mtime {
+        if let Err(e) = writer.get_ref().get_ref().get_ref().set_modified(mtime) {
+            eprintln!(
+                "Warning: failed to restore the modification time of {}: {}",
+                output_path.display(),
+                e
+            );
+        }
+    }
+    if !args.no_fadvise {
+        cache::release(reader.get_ref().get_ref());
+        cache::release(writer.get_ref().get_ref().get_ref());
+    }
+    let digests = checksums.map(Checksums::finish);
+    if let Some((plain, cipher)) = &digests {
+        progress.checksum = Some(plain.clone());
+        if args.sidecar {
+            checksum::write_sidecar(&output_path, plain, args.normalize)?;
+        }
+        if args.verify {
+            checksum::verify_sidecar(input_path, cipher)?;
+        }
+        if args.verify_after {
+            writer
+                .get_ref()
+                .get_ref()
+                .get_ref()
+                .sync_all()
+                .with_context(|| {
+                    format!("Failed to sync output file: {}", output_path.display())
+                })?;
+            checksum::verify_round_trip(&output_path, job.key(), plain, &mut pool.get())?;
+        }
+        manifest::record(manifest::Entry {
+            input: input_path.to_path_buf(),
+            output: output_path.clone(),
+            size: total_size,
+            sha256: plain.clone(),
+            output_sha256: cipher.clone(),
+        });
+    }
+    complete(&mut progress, total_size)?;
+
+    if let Some((plain, cipher)) = digests.filter(|_| args.checksum) {
+        progress.note(&format!(
+            "{}  {}\n{}  {}",
+            plain,
+            filename,
+            cipher,
+            get_relative_path(&output_path)?
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Prints a line of output, or adds it to the dashboard's log while the
+/// dashboard is up.
+fn say(line: &str) {
+    if tui::active() {
+        tui::log(line);
+    } else {
+        println!("{}", line);
+    }
+}
+
+/// Copies without passing data through userspace: a reflink clone where
+/// the filesystem supports it, otherwise `io::copy`, which std turns into
+/// `copy_file_range`/`sendfile` between files on Linux.
+fn copy_passthrough(input: &mut File, output: &mut File) -> io::Result<u64> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::io::AsRawFd;
+        // SAFETY: FICLONE takes the source descriptor as its argument.
+        let rc = unsafe { libc::ioctl(output.as_raw_fd(), libc::FICLONE, input.as_raw_fd()) };
+        if rc == 0 {
+            return Ok(input.metadata()?.len());
+        }
+    }
+
+    io::copy(input, output)
+}
+
+pub(crate) fn get_relative_path(path: &Path) -> Result<String> {
+    let current_dir = env::current_dir()?;
+    let path = longpath::display_form(path);
+    Ok(path
+        .strip_prefix(&current_dir)
+        .unwrap_or(&path)
+        .to_string_lossy()
+        .into_owned())
+}
+
+/// Flushes the output at `path` and the directory entry naming it to disk.
+fn sync_output(path: &Path) -> Result<()> {
+    File::open(path)
+        .and_then(|file| file.sync_all())
+        .with_context(|| format!("Failed to sync output file: {}", path.display()))?;
+    sync_dir(path)
+}
+
+/// Flushes the directory holding `path` to disk, so that the entry for
+/// `path` survives a power loss. Windows can't open directories for this
+/// and commits entries itself.
+fn sync_dir(path: &Path) -> Result<()> {
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        File::open(dir)
+            .and_then(|dir| dir.sync_all())
+            .with_context(|| format!("Failed to sync directory: {}", dir.display()))?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+pub(crate) fn shorten_path(path: &str, max_len: usize) -> String {
+    let sep = std::path::MAIN_SEPARATOR;
+    let parts: Vec<&str> = path.split(sep).collect();
+    let mut result = String::new();
+
+    for part in parts.iter().rev() {
+        let current_length = width::str_width(&result);
+        let part_length = width::str_width(part);
+        let sep_length = if current_length > 0 { 1 } else { 0 };
+        let new_length = current_length + part_length + sep_length;
+
+        if new_length > max_len {
+            if result.is_empty() {
+                let available = max_len.saturating_sub(4);
+                return format!("...{}{}", sep, width::take(part, available));
+            } else {
+                return format!("...{}{}", sep, result);
+            }
+        }
+
+        result = if !result.is_empty() {
+            format!("{}{}{}", part, sep, result)
+        } else {
+            part.to_string()
+        };
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size_parsing() {
+        assert_eq!(parse_size("65536").unwrap(), 65536);
+        assert_eq!(parse_size("64K").unwrap(), 64 * 1024);
+        assert_eq!(parse_size("4M").unwrap(), 4 * 1024 * 1024);
+        assert_eq!(parse_size("1GiB").unwrap(), 1 << 30);
+        assert_eq!(parse_size("512kb").unwrap(), 512 * 1024);
+
+        assert!(parse_size("").is_err());
+        assert!(parse_size("4X").is_err());
+        assert!(parse_buffer_size("0").is_err());
+    }
+
+    #[test]
+    fn test_save_job_key() {
+        let spec = |argv: &[&str]| spec_from_args(&Args::parse_from(argv));
+
+        assert!(spec(&["xortool", "in", "-k", "ff", "--save-job", "j.toml"]).is_err());
+        let mut args = Args::parse_from(["xortool", "in", "--key-env", "K"]);
+        args.key_env = None;
+        assert!(spec_from_args(&args).is_err());
+
+        let saved = spec(&["xortool", "in", "--key-env", "K", "--save-job", "j.toml"]).unwrap();
+        assert!(matches!(saved.key, Some(KeyRef::Env(ref var)) if var == "K"));
+        assert!(!saved.to_toml().contains("hex"));
+    }
+
+    #[test]
+    fn test_worker_limit() {
+        let limit = |extra: &[&str]| {
+            let mut argv = vec!["xortool", "in", "-k", "ff", "-j", "8"];
+            argv.extend_from_slice(extra);
+            worker_limit(&Args::parse_from(argv))
+        };
+
+        assert_eq!(limit(&[]).unwrap(), 8);
+        assert_eq!(limit(&["--max-open-files", "9"]).unwrap(), 3);
+        assert_eq!(limit(&["--max-memory", "400K"]).unwrap(), 5);
+        assert_eq!(
+            limit(&["--max-memory", "24M", "--buffer-size", "1M", "--pipeline"]).unwrap(),
+            2
+        );
+        assert!(limit(&["--max-open-files", "4"]).is_err());
+        assert!(limit(&["--max-memory", "64K"]).is_err());
+    }
+
+    #[test]
+    fn test_run_positions() {
+        let files: Vec<_> = (0..12).map(|i| (i, PathBuf::from("f"))).collect();
+        let mut run = RunProgress::new(&files, true);
+        assert_eq!(run.start(), "[ 1/12] ");
+        for _ in 0..10 {
+            run.start();
+        }
+        assert_eq!(run.start(), "[12/12] ");
+
+        run.complete(5);
+        run.fail().unwrap();
+        assert_eq!(
+            run.queue_status().unwrap(),
+            "0 queued, 10 running, 1 failed"
+        );
+    }
+
+    #[test]
+    fn test_empty_file_progress() {
+        let line = file_progress_line(0, 0, &Throughput::new(), false, "", "empty");
+        assert!(line.contains("100.0%"), "{}", line);
+        assert!(!line.contains("NaN"), "{}", line);
+    }
+}
@@ -0,0 +1,80 @@
+use crossterm::style::{ContentStyle, StyledContent, Stylize as _};
+use std::{
+    env,
+    fmt::Display,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// When to style output, from `--color`.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum ColorChoice {
+    /// Style output on a terminal unless NO_COLOR is set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Decides once at startup whether output is styled.
+pub fn init(choice: ColorChoice) {
+    let enabled = match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            atty::is(atty::Stream::Stdout)
+                && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+        }
+    };
+    ENABLED.store(enabled, Ordering::Relaxed);
+    crossterm::style::force_color_output(enabled);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Styles `content` with `apply`, or leaves it plain when styling is off.
+pub fn paint<D: Display>(
+    content: D,
+    apply: impl FnOnce(ContentStyle) -> ContentStyle,
+) -> StyledContent<D> {
+    let style = if enabled() {
+        apply(ContentStyle::new())
+    } else {
+        ContentStyle::new()
+    };
+    StyledContent::new(style, content)
+}
+
+/// The handful of styles the output uses, honoring `--color` and
+/// NO_COLOR. Unlike crossterm's `Stylize`, this drops attributes such as
+/// bold as well as colors when styling is off.
+pub trait Stylize: Display + Sized {
+    fn bold(self) -> StyledContent<Self> {
+        paint(self, |style| style.bold())
+    }
+
+    fn dim(self) -> StyledContent<Self> {
+        paint(self, |style| style.dim())
+    }
+
+    fn cyan(self) -> StyledContent<Self> {
+        paint(self, |style| style.cyan())
+    }
+
+    fn green(self) -> StyledContent<Self> {
+        paint(self, |style| style.green())
+    }
+
+    fn yellow(self) -> StyledContent<Self> {
+        paint(self, |style| style.yellow())
+    }
+
+    fn red(self) -> StyledContent<Self> {
+        paint(self, |style| style.red())
+    }
+}
+
+impl<T: Display> Stylize for T {}
@@ -0,0 +1,356 @@
+use anyhow::{bail, Context, Result};
+use std::{collections::HashMap, env, fs, io, path::Path, path::PathBuf};
+
+/// A value from the config file.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+/// Settings read from a TOML config file, keyed by their dotted path
+/// (`[progress]` + `width = 40` is `progress.width`).
+///
+/// Only the subset of TOML the settings need is understood: tables,
+/// dotted keys, basic strings, integers, floats, booleans, arrays on one
+/// line and comments.
+#[derive(Debug, Default)]
+pub struct Config {
+    values: HashMap<String, Value>,
+    source: Option<PathBuf>,
+}
+
+impl Config {
+    /// Reads `path`, or the default config file if it exists.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound && !required => {
+                return Ok(Self::default())
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read config: {}", path.display()))
+            }
+        };
+
+        let mut config =
+            Self::parse(&text).with_context(|| format!("Invalid config: {}", path.display()))?;
+        config.source = Some(path);
+        Ok(config)
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut values = HashMap::new();
+        let mut table = String::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[') {
+                let Some(name) = name.strip_suffix(']') else {
+                    bail!("line {}: unterminated table header", number + 1);
+                };
+                table = parse_key(name).with_context(|| format!("line {}", number + 1))?;
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                bail!("line {}: expected `key = value`", number + 1);
+            };
+            let key = parse_key(key).with_context(|| format!("line {}", number + 1))?;
+            let key = if table.is_empty() {
+                key
+            } else {
+                format!("{}.{}", table, key)
+            };
+            let value =
+                parse_value(value.trim()).with_context(|| format!("line {}", number + 1))?;
+
+            if values.insert(key.clone(), value).is_some() {
+                bail!("line {}: duplicate key `{}`", number + 1, key);
+            }
+        }
+
+        Ok(Self {
+            values,
+            source: None,
+        })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.values.get(key)
+    }
+
+    pub fn get_str(&self, key: &str) -> Result<Option<&str>> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Value::String(s)) => Ok(Some(s)),
+            Some(_) => bail!("{}`{}` must be a string", self.location(), key),
+        }
+    }
+
+    pub fn get_int(&self, key: &str) -> Result<Option<i64>> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Value::Integer(n)) => Ok(Some(*n)),
+            Some(_) => bail!("{}`{}` must be an integer", self.location(), key),
+        }
+    }
+
+    pub fn get_bool(&self, key: &str) -> Result<Option<bool>> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Value::Bool(b)) => Ok(Some(*b)),
+            Some(_) => bail!("{}`{}` must be true or false", self.location(), key),
+        }
+    }
+
+    pub fn get_str_list(&self, key: &str) -> Result<Option<Vec<&str>>> {
+        let error = || anyhow::anyhow!("{}`{}` must be a list of strings", self.location(), key);
+        match self.get(key) {
+            None => Ok(None),
+            Some(Value::Array(items)) => items
+                .iter()
+                .map(|item| match item {
+                    Value::String(s) => Ok(s.as_str()),
+                    _ => Err(error()),
+                })
+                .collect::<Result<_>>()
+                .map(Some),
+            Some(_) => Err(error()),
+        }
+    }
+
+    /// The keys set under `table`, such as `key.file` for `key`.
+    pub fn keys_in(&self, table: &str) -> Vec<&str> {
+        let prefix = format!("{}.", table);
+        let mut keys: Vec<&str> = self
+            .values
+            .keys()
+            .filter(|key| key.starts_with(&prefix))
+            .map(String::as_str)
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    /// Every key set, sorted.
+    pub fn keys(&self) -> Vec<&str> {
+        let mut keys: Vec<&str> = self.values.keys().map(String::as_str).collect();
+        keys.sort();
+        keys
+    }
+
+    /// Prefix for error messages naming the file a bad value came from.
+    pub fn location(&self) -> String {
+        match &self.source {
+            Some(path) => format!("{}: ", path.display()),
+            None => String::new(),
+        }
+    }
+}
+
+fn default_path() -> Option<PathBuf> {
+    Some(dir()?.join("config.toml"))
+}
+
+/// `$XDG_CONFIG_HOME/xortool`, falling back to `~/.config` (`%APPDATA%`
+/// on Windows).
+pub fn dir() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+    }?;
+    Some(base.join("xortool"))
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '\\' if in_string => escaped = !escaped,
+            '"' if !escaped => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => escaped = false,
+        }
+        if c != '\\' {
+            escaped = false;
+        }
+    }
+    line
+}
+
+fn parse_key(key: &str) -> Result<String> {
+    let parts: Vec<&str> = key.split('.').map(str::trim).collect();
+    for part in &parts {
+        let bare = !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !bare {
+            bail!("invalid key `{}`", key.trim());
+        }
+    }
+    Ok(parts.join("."))
+}
+
+fn parse_value(text: &str) -> Result<Value> {
+    if let Some(rest) = text.strip_prefix('"') {
+        return parse_string(rest).map(Value::String);
+    }
+    if let Some(rest) = text.strip_prefix('[') {
+        let Some(items) = rest.trim_end().strip_suffix(']') else {
+            bail!("unterminated array");
+        };
+        return split_items(items)
+            .into_iter()
+            .map(|item| parse_value(item.trim()))
+            .collect::<Result<_>>()
+            .map(Value::Array);
+    }
+    match text {
+        "true" => return Ok(Value::Bool(true)),
+        "false" => return Ok(Value::Bool(false)),
+        _ => {}
+    }
+
+    let number = text.replace('_', "");
+    if let Ok(n) = number.parse::<i64>() {
+        return Ok(Value::Integer(n));
+    }
+    if let Ok(f) = number.parse::<f64>() {
+        return Ok(Value::Float(f));
+    }
+    bail!("invalid value `{}`", text)
+}
+
+/// The comma-separated items of an array, leaving commas in strings and
+/// a trailing comma alone.
+fn split_items(text: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let (mut start, mut in_string, mut escaped) = (0, false, false);
+    for (i, c) in text.char_indices() {
+        match c {
+            '\\' if in_string => escaped = !escaped,
+            '"' if !escaped => in_string = !in_string,
+            ',' if !in_string => {
+                items.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+        if c != '\\' {
+            escaped = false;
+        }
+    }
+    items.push(&text[start..]);
+    if items.last().is_some_and(|item| item.trim().is_empty()) {
+        items.pop();
+    }
+    items
+}
+
+/// Parses the rest of a basic string after its opening quote.
+fn parse_string(text: &str) -> Result<String> {
+    let mut out = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                if !chars.as_str().trim().is_empty() {
+                    bail!("unexpected text after string");
+                }
+                return Ok(out);
+            }
+            '\\' => match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('r') => out.push('\r'),
+                Some('"') => out.push('"'),
+                Some('\\') => out.push('\\'),
+                Some('u') => {
+                    let code: String = chars.by_ref().take(4).collect();
+                    let c = u32::from_str_radix(&code, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .with_context(|| format!("invalid escape `\\u{}`", code))?;
+                    out.push(c);
+                }
+                other => bail!("invalid escape `\\{}`", other.unwrap_or(' ')),
+            },
+            c => out.push(c),
+        }
+    }
+    bail!("unterminated string")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse(
+            r#"
+            # comment
+            top = 1
+
+            [progress]
+            style = "braille"   # trailing comment
+            width = 40
+            colors.filled = "dark_cyan"
+            label = "a # b \"c\""
+            enabled = false
+            ratio = 0.5
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.get_int("top").unwrap(), Some(1));
+        assert_eq!(config.get_str("progress.style").unwrap(), Some("braille"));
+        assert_eq!(config.get_int("progress.width").unwrap(), Some(40));
+        assert_eq!(
+            config.get_str("progress.colors.filled").unwrap(),
+            Some("dark_cyan")
+        );
+        assert_eq!(
+            config.get_str("progress.label").unwrap(),
+            Some("a # b \"c\"")
+        );
+        assert_eq!(config.get("progress.enabled"), Some(&Value::Bool(false)));
+        assert_eq!(config.get("progress.ratio"), Some(&Value::Float(0.5)));
+        assert!(config.get_str("progress.width").is_err());
+        let config = Config::parse(r#"list = ["a", "b,\"c", ]"#).unwrap();
+        assert_eq!(
+            config.get_str_list("list").unwrap(),
+            Some(vec!["a", "b,\"c"])
+        );
+        assert!(Config::parse("list = [1, \"a\"").is_err());
+        assert_eq!(config.get_str("missing").unwrap(), None);
+
+        assert!(Config::parse("[progress").is_err());
+        assert!(Config::parse("width 40").is_err());
+        assert!(Config::parse("a = 1\na = 2").is_err());
+        assert!(Config::parse("a = \"open").is_err());
+        assert!(Config::parse("a b = 1").is_err());
+    }
+}
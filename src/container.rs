@@ -0,0 +1,288 @@
+use anyhow::{bail, Context, Result};
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Component, Path, PathBuf},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use crate::{
+    frame::{self, framed_len},
+    job::Job,
+};
+
+/// Starts and ends every container, the last byte being the version.
+const MAGIC: [u8; 4] = *b"JXC\x01";
+
+/// Index offset, index length and magic.
+const TRAILER_LEN: u64 = 8 + 8 + 4;
+
+/// One file in a container. `offset` is where its frames start in the
+/// container, and `path` is relative to the input directory with `/`
+/// between components.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub path: String,
+    pub offset: u64,
+    pub size: u64,
+    pub mtime: u64,
+}
+
+/// Writes every input of `job` into one container at `output`:
+///
+/// ```text
+/// magic
+/// each file's contents, encrypted as CRC32 frames
+/// the index of the files, encrypted as CRC32 frames
+/// index offset (u64), index length (u64), magic
+/// ```
+///
+/// Each file and the index start the keystream from 0, like separate
+/// outputs would. Returns the entries written.
+pub fn write(job: &Job, output: &Path) -> Result<Vec<Entry>> {
+    let root = job.root();
+    let skip = fs::canonicalize(output).ok();
+    let inputs: Vec<_> = job
+        .inputs()?
+        .into_iter()
+        .filter(|input| Some(input) != skip.as_ref())
+        .collect();
+
+    let file = File::create(output)
+        .with_context(|| format!("Failed to create container: {}", output.display()))?;
+    let mut writer = BufWriter::new(file);
+    writer.write_all(&MAGIC)?;
+    let mut offset = MAGIC.len() as u64;
+    let mut entries = Vec::with_capacity(inputs.len());
+    for input in &inputs {
+        let file = File::open(input)
+            .with_context(|| format!("Failed to open file: {}", input.display()))?;
+        let metadata = file.metadata()?;
+        let size = metadata.len();
+        frame::write_framed(
+            BufReader::new(file),
+            &mut writer,
+            job.cipher().as_mut(),
+            |_| Ok(()),
+        )
+        .with_context(|| format!("Failed to add {} to the container", input.display()))?;
+        entries.push(Entry {
+            path: entry_path(input.strip_prefix(root).unwrap_or(input))?,
+            offset,
+            size,
+            mtime: metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |since| since.as_secs()),
+        });
+        offset += framed_len(size);
+    }
+
+    let index = encode_index(&entries)?;
+    frame::write_framed(&index[..], &mut writer, job.cipher().as_mut(), |_| Ok(()))?;
+    writer.write_all(&offset.to_le_bytes())?;
+    writer.write_all(&framed_len(index.len() as u64).to_le_bytes())?;
+    writer.write_all(&MAGIC)?;
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())?
+        .sync_all()?;
+    Ok(entries)
+}
+
+/// Restores the files in the container at `input` under `dir`, with
+/// their modification times, and returns their entries.
+pub fn extract(job: &Job, input: &Path, dir: &Path) -> Result<Vec<Entry>> {
+    let mut file = File::open(input)
+        .with_context(|| format!("Failed to open container: {}", input.display()))?;
+    let entries = read_index(job, &mut file)
+        .with_context(|| format!("{} is not a readable container", input.display()))?;
+
+    for entry in &entries {
+        let path = dir.join(checked_path(&entry.path)?);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let output = File::create(&path)
+            .with_context(|| format!("Failed to create file: {}", path.display()))?;
+        let mut writer = BufWriter::new(output);
+        file.seek(SeekFrom::Start(entry.offset))?;
+        frame::read_framed(
+            BufReader::new(&mut file).take(framed_len(entry.size)),
+            &mut writer,
+            job.cipher().as_mut(),
+            |_| Ok(()),
+        )
+        .with_context(|| format!("Failed to extract {}", entry.path))?;
+        let output = writer.into_inner().map_err(|e| e.into_error())?;
+        if output.metadata()?.len() != entry.size {
+            bail!(
+                "{} is cut short: {} of {} bytes",
+                entry.path,
+                output.metadata()?.len(),
+                entry.size
+            );
+        }
+        let mtime = UNIX_EPOCH.checked_add(Duration::from_secs(entry.mtime));
+        if let Some(mtime) = mtime.filter(|_| entry.mtime != 0) {
+            let _ = output.set_modified(mtime);
+        }
+    }
+    Ok(entries)
+}
+
+fn read_index(job: &Job, file: &mut File) -> Result<Vec<Entry>> {
+    let mut magic = [0; 4];
+    file.read_exact(&mut magic)?;
+    let len = file.metadata()?.len();
+    if magic != MAGIC || len < MAGIC.len() as u64 + TRAILER_LEN {
+        bail!("no JXC header");
+    }
+    let mut trailer = [0; TRAILER_LEN as usize];
+    file.seek(SeekFrom::End(-(TRAILER_LEN as i64)))?;
+    file.read_exact(&mut trailer)?;
+    if trailer[16..] != MAGIC {
+        bail!("no JXC trailer; the container may be truncated");
+    }
+    let index_offset = u64::from_le_bytes(trailer[..8].try_into().unwrap());
+    let index_len = u64::from_le_bytes(trailer[8..16].try_into().unwrap());
+    if index_offset.checked_add(index_len) != Some(len - TRAILER_LEN) {
+        bail!("the index is not where the trailer says");
+    }
+
+    file.seek(SeekFrom::Start(index_offset))?;
+    let mut index = Vec::new();
+    frame::read_framed(
+        BufReader::new(&mut *file).take(index_len),
+        &mut index,
+        job.cipher().as_mut(),
+        |_| Ok(()),
+    )
+    .context("Failed to read the index")?;
+    decode_index(&index)
+}
+
+/// The index as a count followed by each entry's path length (u16),
+/// path, offset, size and mtime, little-endian.
+fn encode_index(entries: &[Entry]) -> Result<Vec<u8>> {
+    let mut index = Vec::new();
+    index.extend_from_slice(&u32::try_from(entries.len())?.to_le_bytes());
+    for entry in entries {
+        let path_len = u16::try_from(entry.path.len())
+            .with_context(|| format!("Path too long for a container: {}", entry.path))?;
+        index.extend_from_slice(&path_len.to_le_bytes());
+        index.extend_from_slice(entry.path.as_bytes());
+        index.extend_from_slice(&entry.offset.to_le_bytes());
+        index.extend_from_slice(&entry.size.to_le_bytes());
+        index.extend_from_slice(&entry.mtime.to_le_bytes());
+    }
+    Ok(index)
+}
+
+fn decode_index(mut index: &[u8]) -> Result<Vec<Entry>> {
+    fn take<'a>(index: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+        if index.len() < len {
+            bail!("the index is cut short; the key may be wrong");
+        }
+        let (head, rest) = index.split_at(len);
+        *index = rest;
+        Ok(head)
+    }
+    let u64_from = |bytes: &[u8]| u64::from_le_bytes(bytes.try_into().unwrap());
+
+    let count = u32::from_le_bytes(take(&mut index, 4)?.try_into().unwrap());
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        let path_len = u16::from_le_bytes(take(&mut index, 2)?.try_into().unwrap());
+        let path = String::from_utf8(take(&mut index, path_len as usize)?.to_vec())
+            .context("the index has a path that isn't UTF-8; the key may be wrong")?;
+        entries.push(Entry {
+            path,
+            offset: u64_from(take(&mut index, 8)?),
+            size: u64_from(take(&mut index, 8)?),
+            mtime: u64_from(take(&mut index, 8)?),
+        });
+    }
+    Ok(entries)
+}
+
+/// `path` as a container entry path, with `/` between components.
+fn entry_path(path: &Path) -> Result<String> {
+    let parts: Option<Vec<_>> = path
+        .components()
+        .map(|component| component.as_os_str().to_str())
+        .collect();
+    match parts {
+        Some(parts) => Ok(parts.join("/")),
+        None => bail!("Path is not valid UTF-8: {}", path.display()),
+    }
+}
+
+/// The entry path as a relative path without `.` components, refusing
+/// ones that would land outside the directory being extracted to.
+pub fn checked_path(path: &str) -> Result<PathBuf> {
+    let mut relative = PathBuf::new();
+    for component in Path::new(path).components() {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            Component::CurDir => {}
+            _ => bail!(
+                "Refusing to extract {:?} outside the output directory",
+                path
+            ),
+        }
+    }
+    if relative.as_os_str().is_empty() {
+        bail!("Refusing to extract {:?}, which names no file", path);
+    }
+    Ok(relative)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_container_round_trip() {
+        let root = std::env::temp_dir().join(format!("xortool-jxc-{}", std::process::id()));
+        fs::create_dir_all(root.join("in/sub")).unwrap();
+        fs::write(root.join("in/a"), b"first").unwrap();
+        fs::write(root.join("in/empty"), b"").unwrap();
+        fs::write(root.join("in/sub/b"), vec![7; 3000]).unwrap();
+
+        let job = Job::builder()
+            .input(root.join("in"))
+            .key([0x42, 0x24])
+            .recursive(true)
+            .build()
+            .unwrap();
+        let container = root.join("in/all.jxc");
+        let written = write(&job, &container).unwrap();
+        let paths: Vec<_> = written.iter().map(|entry| entry.path.as_str()).collect();
+        assert_eq!(paths, ["a", "empty", "sub/b"]);
+        assert!(!fs::read(&container)
+            .unwrap()
+            .windows(5)
+            .any(|w| w == b"first"));
+
+        let extracted = extract(&job, &container, &root.join("out")).unwrap();
+        assert_eq!(extracted, written);
+        assert_eq!(fs::read(root.join("out/a")).unwrap(), b"first");
+        assert_eq!(fs::read(root.join("out/sub/b")).unwrap(), vec![7; 3000]);
+
+        let wrong = Job::builder()
+            .input(root.join("in"))
+            .key([0x43])
+            .build()
+            .unwrap();
+        assert!(extract(&wrong, &container, &root.join("wrong")).is_err());
+
+        assert!(checked_path("../escape").is_err());
+        assert!(checked_path("/etc/passwd").is_err());
+        assert_eq!(checked_path("./ok/file").unwrap(), Path::new("ok/file"));
+        assert!(checked_path(".").is_err());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,162 @@
+// Outside the tests only `core` may be used here, so the module builds
+// as it is in a `#![no_std]` crate without `alloc`.
+
+/// Where in a key of `key_len` bytes the byte at stream position `offset`
+/// starts. This is the whole key schedule: the keystream is the key
+/// repeated from the start of the stream. An empty key has only phase 0.
+pub const fn key_phase(key_len: usize, offset: u64) -> usize {
+    match key_len {
+        0 => 0,
+        len => (offset % len as u64) as usize,
+    }
+}
+
+/// XOR `data` with `key` repeated from `phase`, one byte at a time, and
+/// return the phase of the byte after it. `phase` must be less than the
+/// key's length. Every faster path gives the same result as this.
+pub fn xor_from_phase(data: &mut [u8], key: &[u8], phase: usize) -> usize {
+    let mut phase = phase;
+    for byte in data.iter_mut() {
+        *byte ^= key[phase];
+        phase += 1;
+        if phase == key.len() {
+            phase = 0;
+        }
+    }
+    phase
+}
+
+/// XOR `data` with the keystream of `key` from stream position `offset`.
+/// An empty key leaves `data` alone.
+pub fn xor(data: &mut [u8], key: &[u8], offset: u64) {
+    if !key.is_empty() {
+        xor_from_phase(data, key, key_phase(key.len(), offset));
+    }
+}
+
+/// `xor` from `input` into `output`, which must be the same length.
+///
+/// # Panics
+///
+/// If `input` and `output` differ in length.
+pub fn xor_into(input: &[u8], output: &mut [u8], key: &[u8], offset: u64) {
+    output.copy_from_slice(input);
+    xor(output, key, offset);
+}
+
+/// True when XOR with `key` leaves data unchanged.
+pub fn is_identity_key(key: &[u8]) -> bool {
+    key.iter().all(|&b| b == 0)
+}
+
+/// The keystream of a key, kept at the position after the last chunk it
+/// was applied to, for streams that arrive a piece at a time.
+#[derive(Clone, Copy, Debug)]
+pub struct KeyStream<'a> {
+    key: &'a [u8],
+    phase: usize,
+}
+
+impl<'a> KeyStream<'a> {
+    /// The keystream of `key` from stream position `offset`.
+    pub const fn new(key: &'a [u8], offset: u64) -> Self {
+        Self {
+            key,
+            phase: key_phase(key.len(), offset),
+        }
+    }
+
+    /// XOR the next `data.len()` bytes of the stream into `data`.
+    pub fn apply(&mut self, data: &mut [u8]) {
+        if !self.key.is_empty() {
+            self.phase = xor_from_phase(data, self.key, self.phase);
+        }
+    }
+}
+
+/// Why `decode_hex_key` turned down a key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HexKeyError {
+    Empty,
+    OddLength,
+    /// The byte at this index of the text isn't a hex digit.
+    InvalidDigit(usize),
+    /// The key needs this many bytes, more than the buffer has.
+    TooLong(usize),
+}
+
+/// Decodes the hex `text`, with or without a `0x` prefix, into the start
+/// of `out` and returns the part written.
+pub fn decode_hex_key<'a>(text: &str, out: &'a mut [u8]) -> Result<&'a [u8], HexKeyError> {
+    let prefixed = text.starts_with("0x") || text.starts_with("0X");
+    let start = if prefixed { 2 } else { 0 };
+    let digits = &text.as_bytes()[start..];
+    if digits.is_empty() {
+        return Err(HexKeyError::Empty);
+    }
+    if !digits.len().is_multiple_of(2) {
+        return Err(HexKeyError::OddLength);
+    }
+    let len = digits.len() / 2;
+    if len > out.len() {
+        return Err(HexKeyError::TooLong(len));
+    }
+    for (i, pair) in digits.chunks_exact(2).enumerate() {
+        let digit =
+            |j: usize| hex_value(pair[j]).ok_or(HexKeyError::InvalidDigit(start + 2 * i + j));
+        out[i] = digit(0)? << 4 | digit(1)?;
+    }
+    Ok(&out[..len])
+}
+
+const fn hex_value(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_core_matches_transform() {
+        let key = [0x13, 0x37, 0xc0, 0xde, 0x01];
+        let data: Vec<u8> = (0..300u32).map(|i| (i * 7) as u8).collect();
+        for offset in [0, 3, 1001] {
+            let mut expected = data.clone();
+            crate::transform::xor_encrypt(&mut expected, &key, offset);
+
+            let mut actual = vec![0; data.len()];
+            xor_into(&data, &mut actual, &key, offset);
+            assert_eq!(actual, expected);
+
+            let mut chunked = data.clone();
+            let mut stream = KeyStream::new(&key, offset);
+            for chunk in chunked.chunks_mut(17) {
+                stream.apply(chunk);
+            }
+            assert_eq!(chunked, expected);
+        }
+        assert_eq!(key_phase(0, 1001), 0);
+
+        let mut buffer = [0; 4];
+        assert_eq!(decode_hex_key("0x1A2b", &mut buffer), Ok(&[0x1a, 0x2b][..]));
+        assert_eq!(decode_hex_key("0x", &mut buffer), Err(HexKeyError::Empty));
+        assert_eq!(
+            decode_hex_key("abc", &mut buffer),
+            Err(HexKeyError::OddLength)
+        );
+        assert_eq!(
+            decode_hex_key("0x1g", &mut buffer),
+            Err(HexKeyError::InvalidDigit(3))
+        );
+        assert_eq!(
+            decode_hex_key("0102030405", &mut buffer),
+            Err(HexKeyError::TooLong(5))
+        );
+    }
+}
@@ -0,0 +1,82 @@
+use anyhow::{Context, Result};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use crate::{checksum::Sha256, pause};
+
+/// Removes files whose content matches an earlier entry from `files` and
+/// returns them as `(duplicate, original)` pairs. Only files sharing a
+/// size with another file are hashed.
+pub fn split_duplicates(files: &mut Vec<(u64, PathBuf)>) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let mut by_size: HashMap<u64, usize> = HashMap::new();
+    for (size, _) in files.iter() {
+        *by_size.entry(*size).or_default() += 1;
+    }
+
+    let mut originals: HashMap<(u64, [u8; 32]), PathBuf> = HashMap::new();
+    let mut unique = Vec::with_capacity(files.len());
+    let mut duplicates = Vec::new();
+
+    for (size, path) in files.drain(..) {
+        if size == 0 || by_size[&size] < 2 {
+            unique.push((size, path));
+            continue;
+        }
+        if pause::quit_requested() {
+            return Err(pause::Stopped.into());
+        }
+
+        let digest =
+            hash_file(&path).with_context(|| format!("Failed to hash file: {}", path.display()))?;
+        match originals.get(&(size, digest)) {
+            Some(original) => duplicates.push((path, original.clone())),
+            None => {
+                originals.insert((size, digest), path.clone());
+                unique.push((size, path));
+            }
+        }
+    }
+
+    *files = unique;
+    Ok(duplicates)
+}
+
+fn hash_file(path: &Path) -> io::Result<[u8; 32]> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read_count = file.read(&mut buffer)?;
+        if read_count == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read_count]);
+    }
+    Ok(hasher.finalize())
+}
+
+/// Points `link` at the already written `target`, replacing anything at
+/// `link`. Falls back to a copy where hard links aren't supported.
+pub fn link_output(target: &Path, link: &Path) -> Result<()> {
+    if let Some(parent) = link.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+    match fs::remove_file(link) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            return Err(e).with_context(|| format!("Failed to replace {}", link.display()));
+        }
+        _ => {}
+    }
+
+    if fs::hard_link(target, link).is_err() {
+        fs::copy(target, link).with_context(|| {
+            format!("Failed to copy {} to {}", target.display(), link.display())
+        })?;
+    }
+    Ok(())
+}
@@ -0,0 +1,98 @@
+use anyhow::{Context, Result};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use crate::{
+    cipher::Cipher,
+    pool::{BufferPool, ALIGN},
+};
+
+fn open_direct(path: &Path, options: &mut OpenOptions) -> io::Result<File> {
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.custom_flags(libc::O_DIRECT);
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::OpenOptionsExt;
+        const FILE_FLAG_NO_BUFFERING: u32 = 0x2000_0000;
+        options.custom_flags(FILE_FLAG_NO_BUFFERING);
+    }
+
+    let file = options.open(path)?;
+
+    #[cfg(target_os = "macos")]
+    {
+        use std::os::unix::io::AsRawFd;
+        // SAFETY: fcntl on a descriptor we own.
+        if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_NOCACHE, 1) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(file)
+}
+
+/// Streams `input` to `output` bypassing the page cache. The final partial
+/// block is written padded to [`ALIGN`] and the output truncated back to
+/// the real length afterwards.
+pub fn transform(
+    input_path: &Path,
+    output_path: &Path,
+    cipher: &mut dyn Cipher,
+    pool: &BufferPool,
+    mut progress: impl FnMut(u64) -> Result<()>,
+) -> Result<()> {
+    let mut reader = open_direct(input_path, OpenOptions::new().read(true)).with_context(|| {
+        format!(
+            "Failed to open file for direct I/O: {}",
+            input_path.display()
+        )
+    })?;
+    let mut writer = open_direct(
+        output_path,
+        OpenOptions::new().write(true).create(true).truncate(true),
+    )
+    .with_context(|| {
+        format!(
+            "Failed to create output file for direct I/O: {}",
+            output_path.display()
+        )
+    })?;
+
+    let mut buffer = pool.get();
+    let mut processed = 0u64;
+
+    loop {
+        let mut filled = 0;
+        while filled < buffer.len() {
+            let read_count = reader.read(&mut buffer[filled..])?;
+            if read_count == 0 {
+                break;
+            }
+            filled += read_count;
+        }
+        if filled == 0 {
+            break;
+        }
+
+        cipher.apply(processed, &mut buffer[..filled]);
+        let padded = filled.div_ceil(ALIGN) * ALIGN;
+        buffer[filled..padded].fill(0);
+        writer.write_all(&buffer[..padded])?;
+
+        processed += filled as u64;
+        progress(processed)?;
+
+        if filled < buffer.len() {
+            break;
+        }
+    }
+
+    writer.set_len(processed)?;
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use std::{fs::File, path::Path};
+
+use crate::header::Header;
+
+/// What to do about an input that already starts with a JXOR header, from
+/// `--on-encrypted`.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum EncryptedMode {
+    /// Leave the file alone and count it as skipped
+    #[default]
+    Skip,
+    /// Transform it anyway, with a warning
+    Warn,
+}
+
+/// Whether the file at `path` starts with a header this build can read,
+/// as outputs written with `--header` do. A file that can't be opened or
+/// read isn't taken for one; transforming it reports the error.
+pub fn has_header(path: &Path) -> bool {
+    File::open(path)
+        .and_then(|mut file| Header::read_from(&mut file))
+        .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_has_header() {
+        let path = std::env::temp_dir().join(format!("xortool-encrypted-{}", std::process::id()));
+        let mut bytes = Vec::new();
+        Header::for_file(&path, &fs::metadata(std::env::temp_dir()).unwrap())
+            .write_to(&mut bytes)
+            .unwrap();
+        bytes.extend_from_slice(b"ciphertext");
+        fs::write(&path, &bytes).unwrap();
+        assert!(has_header(&path));
+
+        fs::write(&path, b"JXOR, but plain text").unwrap();
+        assert!(!has_header(&path));
+        fs::write(&path, b"").unwrap();
+        assert!(!has_header(&path));
+
+        fs::remove_file(&path).unwrap();
+        assert!(!has_header(&path));
+    }
+}
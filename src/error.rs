@@ -0,0 +1,59 @@
+use std::{error::Error, fmt, io, path::PathBuf};
+
+/// Why a library call failed, for callers that handle some failures
+/// differently from others. The CLI turns these into `anyhow` errors.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum JustError {
+    /// The key is missing, empty, not hex, or all zero bytes without
+    /// `force`.
+    InvalidKey(String),
+    /// A setting other than the key is missing or can't be used.
+    InvalidOptions(String),
+    /// Reading or writing `path` failed.
+    Io { path: PathBuf, source: io::Error },
+    /// The run was cancelled through its token.
+    Interrupted,
+}
+
+#[cfg(feature = "native")]
+impl JustError {
+    pub(crate) fn io(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        JustError::Io {
+            path: path.into(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for JustError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JustError::InvalidKey(message) | JustError::InvalidOptions(message) => {
+                f.write_str(message)
+            }
+            JustError::Io { path, .. } => write!(f, "I/O error on {}", path.display()),
+            JustError::Interrupted => f.write_str("Cancelled"),
+        }
+    }
+}
+
+impl Error for JustError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            JustError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "cli")]
+impl From<walkdir::Error> for JustError {
+    fn from(e: walkdir::Error) -> Self {
+        let path = e.path().unwrap_or(std::path::Path::new("")).to_path_buf();
+        let source = e.into_io_error().unwrap_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "filesystem loop in walk")
+        });
+        JustError::io(path, source)
+    }
+}
@@ -0,0 +1,107 @@
+use std::time::{Duration, Instant};
+
+/// How quickly older intervals stop counting: a sample this old has about
+/// a third of the weight of a fresh one.
+const TIME_CONSTANT: f64 = 3.0;
+
+/// Intervals shorter than this are merged into the next sample, so that
+/// bursts between two redraws don't swing the estimate.
+const MIN_SAMPLE: Duration = Duration::from_millis(100);
+
+/// Throughput smoothed with an exponentially weighted moving average over
+/// recent intervals, so speed and ETA follow the current rate without
+/// jumping every time the disk stalls or catches up.
+pub struct Throughput {
+    last_bytes: u64,
+    last_time: Instant,
+    rate: Option<f64>,
+}
+
+impl Throughput {
+    pub fn new() -> Self {
+        Self {
+            last_bytes: 0,
+            last_time: Instant::now(),
+            rate: None,
+        }
+    }
+
+    /// Takes the running total of bytes done and returns the smoothed
+    /// rate in bytes per second.
+    pub fn update(&mut self, bytes: u64) -> f64 {
+        self.update_at(bytes, Instant::now())
+    }
+
+    fn update_at(&mut self, bytes: u64, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.last_time);
+        if elapsed < MIN_SAMPLE {
+            return self.rate();
+        }
+
+        let seconds = elapsed.as_secs_f64();
+        let sample = bytes.saturating_sub(self.last_bytes) as f64 / seconds;
+        self.rate = Some(match self.rate {
+            Some(rate) => {
+                let weight = 1.0 - (-seconds / TIME_CONSTANT).exp();
+                rate + weight * (sample - rate)
+            }
+            None => sample,
+        });
+        self.last_bytes = bytes;
+        self.last_time = now;
+        self.rate()
+    }
+
+    /// Starts the next interval now, leaving out time spent paused.
+    pub fn resume(&mut self) {
+        self.last_time = Instant::now();
+    }
+
+    /// The smoothed rate in bytes per second, 0 before the first sample.
+    pub fn rate(&self) -> f64 {
+        self.rate.unwrap_or(0.0)
+    }
+
+    /// Seconds left for `remaining` bytes at the smoothed rate.
+    pub fn eta(&self, remaining: u64) -> u64 {
+        let rate = self.rate();
+        if rate > 0.0 {
+            (remaining as f64 / rate) as u64
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_smoothing() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut throughput = Throughput {
+            last_bytes: 0,
+            last_time: start,
+            rate: None,
+        };
+
+        assert_eq!(throughput.update_at(1000, at(1000)), 1000.0);
+        // Too soon to count as a sample.
+        assert_eq!(throughput.update_at(5000, at(1050)), 1000.0);
+
+        // A stall pulls the rate down gradually rather than to zero.
+        let stalled = throughput.update_at(1000, at(2000));
+        assert!(stalled > 500.0 && stalled < 1000.0, "{}", stalled);
+
+        // A long run at a steady rate converges on it.
+        let mut bytes = 1000;
+        for second in 3..40 {
+            bytes += 4000;
+            throughput.update_at(bytes, at(second * 1000));
+        }
+        assert!((throughput.rate() - 4000.0).abs() < 10.0);
+        assert_eq!(throughput.eta(40_000), 10);
+    }
+}
@@ -0,0 +1,186 @@
+use std::{
+    error::Error,
+    fmt, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// Inputs whose output couldn't be written, in the order they failed.
+static FAILED: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// The step of writing an output that failed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Phase {
+    CreateDir,
+    CreateFile,
+    Write,
+    Rename,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Phase::CreateDir => "create directory",
+            Phase::CreateFile => "create file",
+            Phase::Write => "write",
+            Phase::Rename => "rename",
+        })
+    }
+}
+
+/// An output that couldn't be written, as on a read-only or full share.
+/// Directory runs report these and carry on with the next file.
+#[derive(Debug)]
+pub struct Failed {
+    pub path: PathBuf,
+    pub phase: Phase,
+    source: io::Error,
+}
+
+impl fmt::Display for Failed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Failed to {} {}: {}",
+            self.phase,
+            self.path.display(),
+            self.source
+        )
+    }
+}
+
+impl Failed {
+    pub fn into_source(self) -> io::Error {
+        self.source
+    }
+}
+
+impl Error for Failed {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Tags the error of an I/O step on an output with what it was doing and
+/// to which path.
+pub trait PhaseExt<T> {
+    fn phase(self, phase: Phase, path: &Path) -> Result<T, Failed>;
+}
+
+impl<T> PhaseExt<T> for io::Result<T> {
+    fn phase(self, phase: Phase, path: &Path) -> Result<T, Failed> {
+        self.map_err(|source| Failed {
+            path: path.to_path_buf(),
+            phase,
+            source,
+        })
+    }
+}
+
+/// An output whose write errors carry a `Failed`, so they can be told
+/// apart from read errors however deep in a transform they come up.
+pub struct Output<W> {
+    inner: W,
+    path: PathBuf,
+}
+
+impl<W> Output<W> {
+    pub fn new(inner: W, path: &Path) -> Self {
+        Self {
+            inner,
+            path: path.to_path_buf(),
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    fn tag(&self, e: io::Error) -> io::Error {
+        let kind = e.kind();
+        let failed = Failed {
+            path: self.path.clone(),
+            phase: Phase::Write,
+            source: e,
+        };
+        io::Error::new(kind, failed)
+    }
+}
+
+impl<W: io::Write> io::Write for Output<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf).map_err(|e| self.tag(e))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush().map_err(|e| self.tag(e))
+    }
+}
+
+/// The output failure somewhere in `error`'s chain, if it is one.
+pub fn find(error: &anyhow::Error) -> Option<&Failed> {
+    error.chain().find_map(|cause| {
+        cause.downcast_ref::<Failed>().or_else(|| {
+            cause
+                .downcast_ref::<io::Error>()
+                .and_then(|e| e.get_ref())
+                .and_then(|inner| inner.downcast_ref::<Failed>())
+        })
+    })
+}
+
+/// Records that the output of `input` couldn't be written.
+pub fn record(input: &Path) {
+    FAILED.lock().unwrap().push(input.to_path_buf());
+}
+
+/// Outputs that couldn't be written so far.
+pub fn count() -> usize {
+    FAILED.lock().unwrap().len()
+}
+
+pub fn is_failed(input: &Path) -> bool {
+    FAILED.lock().unwrap().iter().any(|failed| failed == input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+    use std::io::Write;
+
+    struct Full;
+
+    impl io::Write for Full {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::StorageFull, "disk full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_find_through_writers() {
+        let mut writer = io::BufWriter::new(Output::new(Full, Path::new("out/a")));
+        writer.write_all(b"data").unwrap();
+        let error = anyhow::Error::from(writer.flush().unwrap_err()).context("Transform failed");
+        let failed = find(&error).unwrap();
+        assert_eq!(failed.phase, Phase::Write);
+        assert_eq!(failed.path, Path::new("out/a"));
+        assert_eq!(failed.to_string(), "Failed to write out/a: disk full");
+
+        let created = Err::<(), _>(io::Error::from(io::ErrorKind::PermissionDenied))
+            .phase(Phase::CreateFile, Path::new("out/b"))
+            .context("Failed to transform b");
+        assert_eq!(
+            find(&created.unwrap_err()).unwrap().phase,
+            Phase::CreateFile
+        );
+
+        let read = Err::<(), _>(io::Error::from(io::ErrorKind::PermissionDenied))
+            .context("Failed to open file: b");
+        assert!(find(&read.unwrap_err()).is_none());
+    }
+}
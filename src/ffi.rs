@@ -0,0 +1,329 @@
+use anyhow::{bail, Context, Result};
+use std::{
+    cell::RefCell,
+    ffi::{c_char, c_int, c_void, CStr, CString},
+    fs::{self, File},
+    io::{BufReader, BufWriter},
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    ptr, slice,
+    sync::Mutex,
+};
+
+use crate::transform::{self, DEFAULT_BUFFER_SIZE};
+
+pub const XORTOOL_OK: c_int = 0;
+/// A pointer was null, or a path wasn't valid UTF-8.
+pub const XORTOOL_ERR_ARGUMENT: c_int = 1;
+/// Reading the input or writing the output failed.
+pub const XORTOOL_ERR_IO: c_int = 2;
+/// The progress callback asked to stop.
+pub const XORTOOL_ERR_CANCELLED: c_int = 3;
+/// The library panicked; this is a bug.
+pub const XORTOOL_ERR_PANIC: c_int = 4;
+
+/// Called as a file is transformed with the bytes done so far, the total,
+/// and the pointer given at registration. Returning nonzero stops the
+/// transform.
+pub type XortoolProgress = extern "C" fn(done: u64, total: u64, user_data: *mut c_void) -> c_int;
+
+/// The registered callback and its user data, kept as an address so the
+/// pair can be shared between threads; C owns what it points to.
+static PROGRESS: Mutex<Option<(XortoolProgress, usize)>> = Mutex::new(None);
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// The error `xortool_encrypt_file` stopped with when the progress
+/// callback asked it to.
+#[derive(Debug)]
+struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("Cancelled by the progress callback")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Registers `callback` to get the progress of every later
+/// `xortool_encrypt_file`, with `user_data` passed back to it. A null
+/// callback unregisters.
+#[no_mangle]
+pub extern "C" fn xortool_set_progress_callback(
+    callback: Option<XortoolProgress>,
+    user_data: *mut c_void,
+) {
+    *PROGRESS.lock().unwrap_or_else(|e| e.into_inner()) =
+        callback.map(|callback| (callback, user_data as usize));
+}
+
+/// XORs `len` bytes at `data` in place with the `key_len`-byte key at
+/// `key`, starting at keystream position `offset`.
+///
+/// # Safety
+///
+/// `data` must be valid for reads and writes of `len` bytes and `key` for
+/// reads of `key_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn xortool_encrypt_buffer(
+    data: *mut u8,
+    len: usize,
+    key: *const u8,
+    key_len: usize,
+    offset: u64,
+) -> c_int {
+    if (data.is_null() && len > 0) || key.is_null() {
+        return fail(XORTOOL_ERR_ARGUMENT, "data or key is null");
+    }
+    if len == 0 {
+        return XORTOOL_OK;
+    }
+    // SAFETY: the caller vouches for both ranges, and neither is null.
+    let (data, key) = unsafe {
+        (
+            slice::from_raw_parts_mut(data, len),
+            slice::from_raw_parts(key, key_len),
+        )
+    };
+    guard(|| {
+        transform::xor_encrypt(data, key, offset);
+        Ok(())
+    })
+}
+
+/// Transforms the file at `input` into `output` with the `key_len`-byte
+/// key at `key`, creating the output's directory if needed. A cancelled
+/// or failed transform removes the partial output; if the input can't be
+/// opened, an existing output is left as it was.
+///
+/// # Safety
+///
+/// `input` and `output` must be NUL-terminated strings and `key` must be
+/// valid for reads of `key_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn xortool_encrypt_file(
+    input: *const c_char,
+    output: *const c_char,
+    key: *const u8,
+    key_len: usize,
+) -> c_int {
+    if input.is_null() || output.is_null() || key.is_null() {
+        return fail(XORTOOL_ERR_ARGUMENT, "input, output or key is null");
+    }
+    // SAFETY: the caller vouches for the strings and the key.
+    let (input, output, key) = unsafe {
+        (
+            CStr::from_ptr(input).to_str(),
+            CStr::from_ptr(output).to_str(),
+            slice::from_raw_parts(key, key_len),
+        )
+    };
+    let (Ok(input), Ok(output)) = (input, output) else {
+        return fail(XORTOOL_ERR_ARGUMENT, "path is not valid UTF-8");
+    };
+    guard(|| encrypt_file(Path::new(input), Path::new(output), key))
+}
+
+/// Transforms `input`, a file or a directory, the way the CLI does with
+/// no options, writing each output to `xor/` next to its input. The
+/// progress callback is called for each file in turn. Stops at the first
+/// failure, removing that file's partial output. If `files_done` isn't
+/// null, the number of files finished is stored there either way.
+///
+/// # Safety
+///
+/// `input` must be a NUL-terminated string, `key` must be valid for reads
+/// of `key_len` bytes and `files_done` must be null or valid for a write.
+#[cfg(feature = "native")]
+#[no_mangle]
+pub unsafe extern "C" fn xortool_run(
+    input: *const c_char,
+    key: *const u8,
+    key_len: usize,
+    recursive: bool,
+    files_done: *mut usize,
+) -> c_int {
+    if input.is_null() || key.is_null() {
+        return fail(XORTOOL_ERR_ARGUMENT, "input or key is null");
+    }
+    // SAFETY: the caller vouches for the string and the key.
+    let (input, key) = unsafe {
+        (
+            CStr::from_ptr(input).to_str(),
+            slice::from_raw_parts(key, key_len),
+        )
+    };
+    let Ok(input) = input else {
+        return fail(XORTOOL_ERR_ARGUMENT, "path is not valid UTF-8");
+    };
+    let mut done = 0;
+    let status = guard(|| {
+        let job = crate::job::Job::builder()
+            .input(input)
+            .key(key)
+            .recursive(recursive)
+            .force(true)
+            .build()?;
+        for file in job.inputs()? {
+            let output = job.output_path(&file)?;
+            encrypt_file(&file, &output, key)?;
+            done += 1;
+        }
+        Ok(())
+    });
+    if !files_done.is_null() {
+        // SAFETY: the caller vouches for `files_done`.
+        unsafe { *files_done = done };
+    }
+    status
+}
+
+/// A description of the last error on this thread, or null if there was
+/// none. The string stays valid until the next call on this thread.
+#[no_mangle]
+pub extern "C" fn xortool_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+fn encrypt_file(input: &Path, output: &Path, key: &[u8]) -> Result<()> {
+    let file =
+        File::open(input).with_context(|| format!("Failed to open file: {}", input.display()))?;
+    let total = file.metadata()?.len();
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+    let output_file = File::create(output)
+        .with_context(|| format!("Failed to create output file: {}", output.display()))?;
+
+    let progress = *PROGRESS.lock().unwrap_or_else(|e| e.into_inner());
+    let mut buffer = vec![0; DEFAULT_BUFFER_SIZE];
+    // The output was created or truncated above, so a failure from here on
+    // leaves nothing worth keeping; one before it leaves the output alone.
+    transform::stream(
+        &mut BufReader::new(file),
+        &mut BufWriter::new(output_file),
+        key,
+        &mut buffer,
+        0,
+        |done| match progress {
+            Some((callback, user_data)) if callback(done, total, user_data as *mut c_void) != 0 => {
+                bail!(Cancelled)
+            }
+            _ => Ok(()),
+        },
+    )
+    .inspect_err(|_| {
+        let _ = fs::remove_file(output);
+    })?;
+    Ok(())
+}
+
+/// Runs `f`, turning its error or panic into a status code and recording
+/// the message for `xortool_last_error`. Unwinding into C is undefined.
+fn guard(f: impl FnOnce() -> Result<()>) -> c_int {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => {
+            set_last_error(None);
+            XORTOOL_OK
+        }
+        Ok(Err(e)) if e.is::<Cancelled>() => fail(XORTOOL_ERR_CANCELLED, &e.to_string()),
+        Ok(Err(e)) => fail(XORTOOL_ERR_IO, &format!("{:#}", e)),
+        Err(_) => fail(XORTOOL_ERR_PANIC, "xortool panicked"),
+    }
+}
+
+fn fail(code: c_int, message: &str) -> c_int {
+    set_last_error(Some(message));
+    code
+}
+
+fn set_last_error(message: Option<&str>) {
+    let message =
+        message.map(|message| CString::new(message.replace('\0', " ")).unwrap_or_default());
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern "C" fn stop_after_first(done: u64, _total: u64, calls: *mut c_void) -> c_int {
+        // SAFETY: the test registers a pointer to a live u64.
+        unsafe { *(calls as *mut u64) += 1 };
+        (done > 0) as c_int
+    }
+
+    #[test]
+    fn test_ffi_encrypt() {
+        let key = [0x0f, 0xf0];
+        let mut data = *b"hello";
+        // SAFETY: both pointers come from live arrays of the given lengths.
+        let status = unsafe { xortool_encrypt_buffer(data.as_mut_ptr(), 5, key.as_ptr(), 2, 0) };
+        assert_eq!(status, XORTOOL_OK);
+        assert_eq!(
+            data,
+            [
+                b'h' ^ 0x0f,
+                b'e' ^ 0xf0,
+                b'l' ^ 0x0f,
+                b'l' ^ 0xf0,
+                b'o' ^ 0x0f
+            ]
+        );
+
+        let dir = std::env::temp_dir().join(format!("xortool-ffi-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("in"), vec![7; 3 * DEFAULT_BUFFER_SIZE]).unwrap();
+        let input = CString::new(dir.join("in").to_str().unwrap()).unwrap();
+        let output = CString::new(dir.join("out/in").to_str().unwrap()).unwrap();
+
+        let mut calls = 0u64;
+        xortool_set_progress_callback(
+            Some(stop_after_first),
+            &mut calls as *mut u64 as *mut c_void,
+        );
+        // SAFETY: the strings are NUL-terminated and the key is live.
+        let status = unsafe {
+            xortool_encrypt_file(input.as_ptr(), output.as_ptr(), key.as_ptr(), key.len())
+        };
+        xortool_set_progress_callback(None, ptr::null_mut());
+        assert_eq!(status, XORTOOL_ERR_CANCELLED);
+        assert_eq!(calls, 1);
+        assert!(!dir.join("out/in").exists());
+        // SAFETY: xortool_last_error returned a live NUL-terminated string.
+        let message = unsafe { CStr::from_ptr(xortool_last_error()) };
+        assert_eq!(
+            message.to_str().unwrap(),
+            "Cancelled by the progress callback"
+        );
+
+        // SAFETY: as above.
+        let status = unsafe {
+            xortool_encrypt_file(input.as_ptr(), output.as_ptr(), key.as_ptr(), key.len())
+        };
+        assert_eq!(status, XORTOOL_OK);
+        assert!(xortool_last_error().is_null());
+        assert_eq!(
+            fs::read(dir.join("out/in")).unwrap()[..2],
+            [7 ^ 0x0f, 7 ^ 0xf0]
+        );
+
+        let missing = CString::new(dir.join("missing").to_str().unwrap()).unwrap();
+        // SAFETY: as above.
+        let status = unsafe {
+            xortool_encrypt_file(missing.as_ptr(), output.as_ptr(), key.as_ptr(), key.len())
+        };
+        assert_eq!(status, XORTOOL_ERR_IO);
+        assert!(dir.join("out/in").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
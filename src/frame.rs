@@ -0,0 +1,163 @@
+use anyhow::{bail, Context, Result};
+use std::io::{Read, Write};
+
+use crate::{cipher::Cipher, transform::read_full};
+
+/// Plaintext bytes per frame. Corruption is reported to this granularity.
+pub const FRAME_SIZE: usize = 1 << 20;
+
+/// Each frame starts with its length and the CRC32 of its ciphertext,
+/// both little-endian u32.
+const HEADER_LEN: usize = 8;
+
+/// Bytes `write_framed` writes for `size` bytes of input.
+pub const fn framed_len(size: u64) -> u64 {
+    size + size.div_ceil(FRAME_SIZE as u64) * HEADER_LEN as u64
+}
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// CRC-32 (IEEE 802.3), as used by zip and gzip.
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, &b| {
+        CRC_TABLE[((crc ^ u32::from(b)) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Transforms `reader` into framed output: the ciphertext in frames of
+/// `FRAME_SIZE` bytes, each with a checksum that can be checked without
+/// the key. `progress` gets the input bytes consumed so far.
+pub fn write_framed(
+    mut reader: impl Read,
+    writer: &mut impl Write,
+    cipher: &mut dyn Cipher,
+    mut progress: impl FnMut(u64) -> Result<()>,
+) -> Result<()> {
+    let mut buffer = vec![0; FRAME_SIZE];
+    let mut offset = 0u64;
+    loop {
+        let len = read_full(&mut reader, &mut buffer)?;
+        if len == 0 {
+            break;
+        }
+        let chunk = &mut buffer[..len];
+        cipher.apply(offset, chunk);
+        writer.write_all(&(len as u32).to_le_bytes())?;
+        writer.write_all(&crc32(chunk).to_le_bytes())?;
+        writer.write_all(chunk)?;
+
+        offset += len as u64;
+        progress(offset)?;
+    }
+    Ok(())
+}
+
+/// Checks and decrypts framed input written by `write_framed`, failing
+/// on the first frame whose checksum doesn't match. `progress` gets the
+/// input bytes consumed so far, headers included.
+pub fn read_framed(
+    mut reader: impl Read,
+    writer: &mut impl Write,
+    cipher: &mut dyn Cipher,
+    mut progress: impl FnMut(u64) -> Result<()>,
+) -> Result<()> {
+    let mut buffer = vec![0; FRAME_SIZE];
+    let mut header = [0; HEADER_LEN];
+    let mut offset = 0u64;
+    let mut consumed = 0u64;
+    for index in 1.. {
+        match read_full(&mut reader, &mut header)? {
+            0 => break,
+            HEADER_LEN => {}
+            _ => bail!("Frame {} is truncated in its header", index),
+        }
+        let len = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+        let expected = u32::from_le_bytes(header[4..].try_into().unwrap());
+        if len == 0 || len > FRAME_SIZE {
+            bail!(
+                "Frame {} at byte {} has an invalid length of {}; the input may not be framed",
+                index,
+                offset,
+                len
+            );
+        }
+
+        let chunk = &mut buffer[..len];
+        if read_full(&mut reader, chunk)? < len {
+            bail!("Frame {} (bytes {}..) is truncated", index, offset);
+        }
+        let actual = crc32(chunk);
+        if actual != expected {
+            bail!(
+                "Frame {} (bytes {}..{}) is corrupt: CRC32 is {:08x}, expected {:08x}",
+                index,
+                offset,
+                offset + len as u64,
+                actual,
+                expected
+            );
+        }
+        cipher.apply(offset, chunk);
+        writer
+            .write_all(chunk)
+            .context("Failed to write decrypted frame")?;
+
+        offset += len as u64;
+        consumed += (HEADER_LEN + len) as u64;
+        progress(consumed)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cipher::Xor;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_framed_round_trip() {
+        let mut xor = Xor::new([0x13, 0x37, 0x42]);
+        let plain: Vec<u8> = (0..FRAME_SIZE * 2 + 100).map(|i| (i % 251) as u8).collect();
+
+        let mut framed = Vec::new();
+        write_framed(&plain[..], &mut framed, &mut xor, |_| Ok(())).unwrap();
+        assert_eq!(framed.len(), plain.len() + 3 * HEADER_LEN);
+        assert_eq!(framed_len(plain.len() as u64), framed.len() as u64);
+
+        let mut decrypted = Vec::new();
+        read_framed(&framed[..], &mut decrypted, &mut xor, |_| Ok(())).unwrap();
+        assert_eq!(decrypted, plain);
+
+        framed[HEADER_LEN + FRAME_SIZE + HEADER_LEN + 5] ^= 1;
+        let error = read_framed(&framed[..], &mut Vec::new(), &mut xor, |_| Ok(())).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Frame 2 (bytes 1048576..2097152) is corrupt"));
+    }
+}
@@ -0,0 +1,157 @@
+use std::path::{Component, Path};
+
+use crate::error::JustError;
+
+/// A shell-style pattern for leaving files out of a walk. `*` matches
+/// within one path component, `**` any number of whole components, `?`
+/// one character and `[a-z]` or `[!a-z]` one character in or not in a
+/// set. A pattern without `/` matches any component, so `*.tmp` leaves
+/// out temporary files at every depth and `cache` whole directories;
+/// one with `/` in it or in front matches the path from the walk's root.
+#[derive(Clone, Debug)]
+pub struct Glob {
+    segments: Vec<Vec<char>>,
+    anchored: bool,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Result<Self, JustError> {
+        let trimmed = pattern.trim_start_matches('/').trim_end_matches('/');
+        if trimmed.is_empty() {
+            return Err(JustError::InvalidOptions(format!(
+                "Empty exclude pattern: {:?}",
+                pattern
+            )));
+        }
+        let segments: Vec<Vec<char>> = trimmed
+            .split('/')
+            .map(|segment| segment.chars().collect())
+            .collect();
+        if !segments.iter().all(|segment| classes_closed(segment)) {
+            return Err(JustError::InvalidOptions(format!(
+                "Invalid exclude pattern {:?}: unclosed [",
+                pattern
+            )));
+        }
+        Ok(Self {
+            anchored: segments.len() > 1 || pattern.starts_with('/'),
+            segments,
+        })
+    }
+
+    /// Whether `relative`, a path from the walk's root, is left out.
+    pub fn matches(&self, relative: &Path) -> bool {
+        let components: Vec<String> = relative
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+                _ => None,
+            })
+            .collect();
+        if self.anchored {
+            let components: Vec<&str> = components.iter().map(String::as_str).collect();
+            match_path(&self.segments, &components)
+        } else {
+            components
+                .last()
+                .is_some_and(|name| match_component(&self.segments[0], name))
+        }
+    }
+}
+
+fn classes_closed(segment: &[char]) -> bool {
+    (0..segment.len()).all(|i| segment[i] != '[' || class_end(segment, i).is_some())
+}
+
+fn match_path(segments: &[Vec<char>], components: &[&str]) -> bool {
+    match segments.split_first() {
+        None => components.is_empty(),
+        Some((segment, rest)) if segment.as_slice() == ['*', '*'] => {
+            (0..=components.len()).any(|skip| match_path(rest, &components[skip..]))
+        }
+        Some((segment, rest)) => match components.split_first() {
+            Some((name, tail)) => match_component(segment, name) && match_path(rest, tail),
+            None => false,
+        },
+    }
+}
+
+fn match_component(pattern: &[char], name: &str) -> bool {
+    let name: Vec<char> = name.chars().collect();
+    match_chars(pattern, &name)
+}
+
+fn match_chars(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|skip| match_chars(&pattern[1..], &name[skip..])),
+        Some('?') => !name.is_empty() && match_chars(&pattern[1..], &name[1..]),
+        Some('[') => {
+            let Some(end) = class_end(pattern, 0) else {
+                return false;
+            };
+            match name.first() {
+                Some(&c) if in_class(&pattern[1..end], c) => {
+                    match_chars(&pattern[end + 1..], &name[1..])
+                }
+                _ => false,
+            }
+        }
+        Some(&c) => name.first() == Some(&c) && match_chars(&pattern[1..], &name[1..]),
+    }
+}
+
+/// Index of the `]` closing the class that opens at `start`. A `]` right
+/// after the `[` or `[!` is part of the set.
+fn class_end(pattern: &[char], start: usize) -> Option<usize> {
+    let mut i = start + 1;
+    if pattern.get(i) == Some(&'!') {
+        i += 1;
+    }
+    if pattern.get(i) == Some(&']') {
+        i += 1;
+    }
+    (i..pattern.len()).find(|&i| pattern[i] == ']')
+}
+
+fn in_class(class: &[char], c: char) -> bool {
+    let (negated, class) = match class.first() {
+        Some('!') => (true, &class[1..]),
+        _ => (false, class),
+    };
+    let mut found = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            found |= (class[i]..=class[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            found |= class[i] == c;
+            i += 1;
+        }
+    }
+    found != negated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_matches() {
+        let matches =
+            |pattern: &str, path: &str| Glob::new(pattern).unwrap().matches(Path::new(path));
+        assert!(matches("*.tmp", "a.tmp"));
+        assert!(matches("*.tmp", "deep/down/a.tmp"));
+        assert!(!matches("*.tmp", "a.tmp.txt"));
+        assert!(matches("cache", "sub/cache"));
+        assert!(matches("file?.[a-c]", "file1.b"));
+        assert!(!matches("file?.[!a-c]", "file1.b"));
+        assert!(matches("logs/*.log", "logs/today.log"));
+        assert!(!matches("logs/*.log", "sub/logs/today.log"));
+        assert!(matches("**/logs/*.log", "sub/logs/today.log"));
+        assert!(matches("/build/**", "build/x/y"));
+        assert!(Glob::new("[abc").is_err());
+        assert!(Glob::new("/").is_err());
+    }
+}
@@ -0,0 +1,199 @@
+use std::{
+    fs::Metadata,
+    io::{self, Read, Write},
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// First bytes of every output written with a header.
+pub const MAGIC: [u8; 4] = *b"JXOR";
+
+/// Layout version written by this build; `read_from` turns down others.
+pub const VERSION: u8 = 1;
+
+/// `Header::cipher` of outputs made with XOR and a repeating key.
+pub const CIPHER_XOR: u8 = 1;
+
+/// `Header::flags` bit of outputs written as CRC32 frames.
+pub const FLAG_FRAMED: u16 = 1;
+
+/// Last second of the year 9999; later mtimes are taken to be corrupt.
+const MAX_MTIME: u64 = 253_402_300_799;
+
+/// Magic, version, cipher, flags, size, mtime and name length.
+const FIXED_LEN: usize = 4 + 1 + 1 + 2 + 8 + 8 + 2;
+
+/// What an output was made from and how, stored unencrypted in front of
+/// the ciphertext so it can be read without the key. All numbers are
+/// little-endian:
+///
+/// | bytes | field                                   |
+/// |-------|-----------------------------------------|
+/// | 4     | `JXOR`                                  |
+/// | 1     | format version                          |
+/// | 1     | cipher id                               |
+/// | 2     | flags                                   |
+/// | 8     | original size                           |
+/// | 8     | original mtime, seconds since the epoch |
+/// | 2     | name length                             |
+/// | n     | original file name, UTF-8               |
+///
+/// The keystream starts at the first byte after the header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Header {
+    pub version: u8,
+    pub cipher: u8,
+    pub flags: u16,
+    /// The input's file name, without its directory.
+    pub name: String,
+    pub size: u64,
+    /// Whole seconds since the Unix epoch, or 0 if unknown.
+    pub mtime: u64,
+}
+
+impl Header {
+    /// The header of an XOR output of the file at `path`.
+    pub fn for_file(path: &Path, metadata: &Metadata) -> Self {
+        Self {
+            version: VERSION,
+            cipher: CIPHER_XOR,
+            flags: 0,
+            name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            size: metadata.len(),
+            mtime: metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |since| since.as_secs()),
+        }
+    }
+
+    pub fn is_framed(&self) -> bool {
+        self.flags & FLAG_FRAMED != 0
+    }
+
+    /// The original modification time, if the header has a usable one.
+    /// Times after the year 9999 or past what `SystemTime` holds count as
+    /// none.
+    pub fn modified(&self) -> Option<SystemTime> {
+        if self.mtime == 0 || self.mtime > MAX_MTIME {
+            return None;
+        }
+        UNIX_EPOCH.checked_add(Duration::from_secs(self.mtime))
+    }
+
+    /// Bytes the header takes up.
+    pub fn encoded_len(&self) -> usize {
+        FIXED_LEN + self.name.len()
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        let name = self.name.as_bytes();
+        let name_len = u16::try_from(name.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "file name too long"))?;
+        let mut bytes = Vec::with_capacity(self.encoded_len());
+        bytes.extend_from_slice(&MAGIC);
+        bytes.push(self.version);
+        bytes.push(self.cipher);
+        bytes.extend_from_slice(&self.flags.to_le_bytes());
+        bytes.extend_from_slice(&self.size.to_le_bytes());
+        bytes.extend_from_slice(&self.mtime.to_le_bytes());
+        bytes.extend_from_slice(&name_len.to_le_bytes());
+        bytes.extend_from_slice(name);
+        writer.write_all(&bytes)
+    }
+
+    /// Reads a header from the start of `reader`, leaving it at the first
+    /// byte of ciphertext. A missing or unreadable header, or one naming a
+    /// cipher this build doesn't know, is an `InvalidData` error.
+    pub fn read_from(reader: &mut impl Read) -> io::Result<Self> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+        let cut_off = |e: io::Error| match e.kind() {
+            io::ErrorKind::UnexpectedEof => invalid("JXOR header is cut off".into()),
+            _ => e,
+        };
+        let mut fixed = [0; FIXED_LEN];
+        reader.read_exact(&mut fixed).map_err(cut_off)?;
+        if fixed[..4] != MAGIC {
+            return Err(invalid("No JXOR header".into()));
+        }
+        let version = fixed[4];
+        if version != VERSION {
+            return Err(invalid(format!(
+                "JXOR header version {} is not supported (expected {})",
+                version, VERSION
+            )));
+        }
+        let cipher = fixed[5];
+        if cipher != CIPHER_XOR {
+            return Err(invalid(format!(
+                "JXOR header has unknown cipher id {}",
+                cipher
+            )));
+        }
+        let u64_at = |at: usize| u64::from_le_bytes(fixed[at..at + 8].try_into().unwrap());
+        let mut name = vec![0; u16::from_le_bytes([fixed[24], fixed[25]]) as usize];
+        reader.read_exact(&mut name).map_err(cut_off)?;
+        Ok(Self {
+            version,
+            cipher,
+            flags: u16::from_le_bytes([fixed[6], fixed[7]]),
+            name: String::from_utf8(name)
+                .map_err(|_| invalid("JXOR header has a file name that isn't UTF-8".into()))?,
+            size: u64_at(8),
+            mtime: u64_at(16),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_round_trip() {
+        let header = Header {
+            version: VERSION,
+            cipher: CIPHER_XOR,
+            flags: FLAG_FRAMED,
+            name: "photo ✓.jpg".into(),
+            size: 1 << 40,
+            mtime: 1_709_294_461,
+        };
+        let mut bytes = Vec::new();
+        header.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), header.encoded_len());
+        assert_eq!(&bytes[..6], b"JXOR\x01\x01");
+        bytes.extend_from_slice(b"ciphertext");
+
+        let mut reader = &bytes[..];
+        assert_eq!(Header::read_from(&mut reader).unwrap(), header);
+        assert_eq!(reader, b"ciphertext");
+        assert!(header.is_framed());
+
+        assert!(Header::read_from(&mut &b"JXOR"[..]).is_err());
+        assert!(Header::read_from(&mut &bytes[..header.encoded_len() - 1]).is_err());
+        bytes[0] = b'X';
+        assert!(Header::read_from(&mut &bytes[..]).is_err());
+        bytes[0] = b'J';
+        bytes[4] = 2;
+        assert!(Header::read_from(&mut &bytes[..]).is_err());
+        bytes[4] = VERSION;
+        bytes[5] = 9;
+        assert!(Header::read_from(&mut &bytes[..]).is_err());
+
+        let modified = |mtime| {
+            Header {
+                mtime,
+                ..header.clone()
+            }
+            .modified()
+        };
+        assert!(modified(1_709_294_461).is_some());
+        assert!(modified(0).is_none());
+        assert!(modified(u64::MAX).is_none());
+    }
+}
@@ -0,0 +1,65 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::{estimate::Throughput, humanize, i18n::Msg};
+
+/// Longest gap between two progress lines.
+const PERIOD: Duration = Duration::from_secs(10);
+
+/// Shortest gap between two progress lines, so that a fast run doesn't
+/// print a line for every 10%.
+const MIN_GAP: Duration = Duration::from_secs(1);
+
+/// Progress steps that get a line of their own, in percent.
+const STEP: u64 = 10;
+
+struct State {
+    last_line: Instant,
+    last_step: u64,
+    throughput: Throughput,
+}
+
+static STATE: Mutex<Option<State>> = Mutex::new(None);
+
+/// Starts printing a plain progress line every `PERIOD` or `STEP` percent,
+/// for output that isn't a terminal and so gets no live progress.
+pub fn enable() {
+    *STATE.lock().unwrap() = Some(State {
+        last_line: Instant::now(),
+        last_step: 0,
+        throughput: Throughput::new(),
+    });
+}
+
+/// Reports `done` of `total` bytes; `detail` is added to the line, if one
+/// is printed.
+pub fn beat(done: u64, total: u64, detail: impl FnOnce() -> String) {
+    let mut state = STATE.lock().unwrap();
+    let Some(state) = state.as_mut() else {
+        return;
+    };
+    state.throughput.update(done);
+
+    let percent = done.saturating_mul(100) / total.max(1);
+    let step = percent / STEP;
+    let since = state.last_line.elapsed();
+    let due = since >= PERIOD || (step > state.last_step && since >= MIN_GAP);
+    if !due || percent >= 100 {
+        return;
+    }
+    state.last_line = Instant::now();
+    state.last_step = step;
+
+    println!(
+        "{:>3}% | {}/{} | {} | {}: {}s | {}",
+        percent,
+        humanize::size(done),
+        humanize::size(total),
+        humanize::rate(state.throughput.rate()),
+        Msg::Eta,
+        state.throughput.eta(total.saturating_sub(done)),
+        detail()
+    );
+}
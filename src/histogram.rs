@@ -0,0 +1,144 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{color::Stylize, humanize, i18n::Msg};
+
+/// Length of each interval the run's throughput is sampled over.
+const INTERVAL: Duration = Duration::from_millis(500);
+
+const BUCKETS: usize = 10;
+const BAR_WIDTH: usize = 40;
+
+static BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// Counts bytes transformed, for the sampler.
+pub fn count(bytes: u64) {
+    BYTES.fetch_add(bytes, Ordering::Relaxed);
+}
+
+/// Samples run-wide throughput once per `INTERVAL` on a helper thread,
+/// including intervals where nothing moved.
+pub struct Sampler {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<Vec<f64>>,
+}
+
+impl Sampler {
+    pub fn start() -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || {
+                let mut samples = Vec::new();
+                let mut last = (Instant::now(), BYTES.load(Ordering::Relaxed));
+                loop {
+                    let stopping = stop.load(Ordering::Relaxed);
+                    let now = Instant::now();
+                    let elapsed = now - last.0;
+                    if elapsed >= INTERVAL || (stopping && !elapsed.is_zero()) {
+                        let bytes = BYTES.load(Ordering::Relaxed);
+                        samples.push((bytes - last.1) as f64 / elapsed.as_secs_f64());
+                        last = (now, bytes);
+                    }
+                    if stopping {
+                        return samples;
+                    }
+                    thread::park_timeout(INTERVAL.saturating_sub(now - last.0));
+                }
+            })
+        };
+        Self { stop, thread }
+    }
+
+    /// Stops sampling and returns the rates in bytes per second, the last
+    /// one covering the partial interval up to now.
+    pub fn finish(self) -> Vec<f64> {
+        self.stop.store(true, Ordering::Relaxed);
+        self.thread.thread().unpark();
+        self.thread.join().unwrap_or_default()
+    }
+}
+
+/// Prints min, median, 90th percentile and max of `samples`, and with
+/// `histogram` their distribution over evenly sized buckets.
+pub fn print_summary(mut samples: Vec<f64>, histogram: bool) {
+    if samples.is_empty() {
+        return;
+    }
+    samples.sort_by(f64::total_cmp);
+    let (min, max) = (samples[0], samples[samples.len() - 1]);
+
+    println!(
+        "\n{} ({})",
+        Msg::Throughput.bold(),
+        Msg::Samples.fill(&[&samples.len(), &format!("{:.1}", INTERVAL.as_secs_f64())])
+    );
+    println!(
+        "  min {}  p50 {}  p90 {}  max {}",
+        humanize::rate(min),
+        humanize::rate(percentile(&samples, 50.0)),
+        humanize::rate(percentile(&samples, 90.0)),
+        humanize::rate(max)
+    );
+
+    if !histogram {
+        return;
+    }
+    let counts = bucket_counts(&samples, min, max);
+    let peak = counts.iter().copied().max().unwrap_or(0).max(1);
+    let step = (max - min) / BUCKETS as f64;
+    for (i, count) in counts.iter().enumerate() {
+        let from = min + step * i as f64;
+        println!(
+            "  {:>11} - {:>11} | {:<width$} {}",
+            humanize::rate(from),
+            humanize::rate(from + step),
+            "#".repeat(count * BAR_WIDTH / peak),
+            count,
+            width = BAR_WIDTH
+        );
+    }
+}
+
+/// Nearest-rank percentile of sorted `samples`.
+fn percentile(samples: &[f64], percent: f64) -> f64 {
+    let rank = (percent / 100.0 * samples.len() as f64).ceil() as usize;
+    samples[rank.clamp(1, samples.len()) - 1]
+}
+
+fn bucket_counts(samples: &[f64], min: f64, max: f64) -> [usize; BUCKETS] {
+    let mut counts = [0; BUCKETS];
+    let span = max - min;
+    for &sample in samples {
+        let bucket = if span > 0.0 {
+            ((sample - min) / span * BUCKETS as f64) as usize
+        } else {
+            0
+        };
+        counts[bucket.min(BUCKETS - 1)] += 1;
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distribution() {
+        let samples: Vec<f64> = (1..=10).map(f64::from).collect();
+        assert_eq!(percentile(&samples, 50.0), 5.0);
+        assert_eq!(percentile(&samples, 90.0), 9.0);
+        assert_eq!(percentile(&samples, 0.0), 1.0);
+        assert_eq!(percentile(&[7.0], 90.0), 7.0);
+
+        assert_eq!(bucket_counts(&samples, 1.0, 10.0), [1; BUCKETS]);
+        assert_eq!(bucket_counts(&[3.0, 3.0], 3.0, 3.0)[0], 2);
+    }
+}
@@ -0,0 +1,57 @@
+const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+
+/// Formats a byte count with the largest unit (powers of 1024) that keeps
+/// the value at or above 1, e.g. `1.46 GB` or `512 B`.
+pub fn size(bytes: u64) -> String {
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else if value < 10.0 {
+        format!("{:.2} {}", value, UNITS[unit])
+    } else if value < 100.0 {
+        format!("{:.1} {}", value, UNITS[unit])
+    } else {
+        format!("{:.0} {}", value, UNITS[unit])
+    }
+}
+
+/// Formats a throughput in bytes per second, e.g. `85.3 MB/s`.
+pub fn rate(bytes_per_sec: f64) -> String {
+    if !bytes_per_sec.is_finite() || bytes_per_sec < 0.0 {
+        return format!("0 {}/s", UNITS[0]);
+    }
+    format!("{}/s", size(bytes_per_sec as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size() {
+        assert_eq!(size(0), "0 B");
+        assert_eq!(size(1023), "1023 B");
+        assert_eq!(size(1024), "1.00 KB");
+        assert_eq!(size(1536), "1.50 KB");
+        assert_eq!(size(64 * 1024), "64.0 KB");
+        assert_eq!(size(512 * 1024), "512 KB");
+        assert_eq!(size(10 * 1024 * 1024), "10.0 MB");
+        assert_eq!(size(3 << 30), "3.00 GB");
+        assert_eq!(size(5 << 50), "5120 TB");
+    }
+
+    #[test]
+    fn test_rate() {
+        assert_eq!(rate(0.0), "0 B/s");
+        assert_eq!(rate(f64::INFINITY), "0 B/s");
+        assert_eq!(rate(800.0), "800 B/s");
+        assert_eq!(rate(85.3 * 1024.0 * 1024.0), "85.3 MB/s");
+        assert_eq!(rate(2.5 * (1u64 << 30) as f64), "2.50 GB/s");
+    }
+}
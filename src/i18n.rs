@@ -0,0 +1,232 @@
+use anyhow::{Context, Result};
+use std::{
+    env,
+    fmt::{self, Display},
+    sync::OnceLock,
+};
+
+use crate::config::{self, Config};
+
+macro_rules! messages {
+    ($($name:ident = $key:literal => $english:literal,)*) => {
+        /// A user-facing string, looked up in the catalog of the selected
+        /// language when displayed.
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        pub enum Msg {
+            $($name,)*
+        }
+
+        /// Catalog keys, in the order of `Msg`.
+        const KEYS: &[&str] = &[$($key,)*];
+        const ENGLISH: &[&str] = &[$($english,)*];
+    };
+}
+
+messages! {
+    Completed = "completed" => "Completed",
+    Processed = "processed" => "Processed",
+    Linked = "linked" => "Linked",
+    Skipped = "skipped" => "Skipped",
+    Failed = "failed" => "failed",
+    Queued = "queued" => "queued",
+    Running = "running" => "running",
+    Paused = "paused" => "PAUSED",
+    Eta = "eta" => "ETA",
+    In = "in" => "in",
+    Files = "files" => "files",
+    SmallFile = "small_file" => "small file",
+    SmallFiles = "small_files" => "small files",
+    DuplicateFiles = "duplicate_files" => "duplicate files",
+    NotRewritten = "not_rewritten" => "not rewritten",
+    Scanning = "scanning" => "Scanning",
+    TotalTime = "total_time" => "Total processing time",
+    InProgress = "in_progress" => "In progress",
+    Recent = "recent" => "Recent",
+    Errors = "errors" => "Errors",
+    Keys = "keys" => "[p] pause  [s] skip file  [q] quit",
+    SlowestFiles = "slowest_files" => "Slowest {} of {} files:",
+    FilesByTime = "files_by_time" => "Files by processing time:",
+    Time = "time" => "Time",
+    Share = "share" => "Share",
+    Size = "size" => "Size",
+    Speed = "speed" => "Speed",
+    File = "file" => "File",
+    Throughput = "throughput" => "Throughput",
+    Samples = "samples" => "{} samples of {}s",
+    EmptyFile = "empty_file" => "{} empty file got an empty output",
+    EmptyFiles = "empty_files" => "{} empty files got empty outputs",
+    SpecialFile = "special_file" => "Skipped {} socket, device, FIFO or dangling symlink",
+    SpecialFiles = "special_files" => "Skipped {} sockets, devices, FIFOs or dangling symlinks",
+    UnstableFile = "unstable_file" => "{} file changed size while being read and may be torn",
+    UnstableFiles = "unstable_files" => "{} files changed size while being read and may be torn",
+}
+
+/// Translations shipped with the tool. Missing keys fall back to English.
+const BUILTIN: &[(&str, &[(&str, &str)])] = &[(
+    "de",
+    &[
+        ("completed", "Fertig"),
+        ("processed", "Verarbeitet"),
+        ("linked", "Verknüpft"),
+        ("skipped", "Übersprungen"),
+        ("failed", "fehlgeschlagen"),
+        ("queued", "wartend"),
+        ("running", "laufend"),
+        ("paused", "PAUSIERT"),
+        ("eta", "Rest"),
+        ("in", "in"),
+        ("files", "Dateien"),
+        ("small_file", "kleine Datei"),
+        ("small_files", "kleine Dateien"),
+        ("duplicate_files", "doppelte Dateien"),
+        ("not_rewritten", "nicht neu geschrieben"),
+        ("scanning", "Durchsuche"),
+        ("total_time", "Gesamtdauer"),
+        ("in_progress", "In Arbeit"),
+        ("recent", "Zuletzt"),
+        ("errors", "Fehler"),
+        ("keys", "[p] Pause  [s] Datei überspringen  [q] Beenden"),
+        ("slowest_files", "Die langsamsten {} von {} Dateien:"),
+        ("files_by_time", "Dateien nach Dauer:"),
+        ("time", "Dauer"),
+        ("share", "Anteil"),
+        ("size", "Größe"),
+        ("speed", "Tempo"),
+        ("file", "Datei"),
+        ("throughput", "Durchsatz"),
+        ("samples", "{} Messungen zu je {}s"),
+        ("empty_file", "{} leere Datei ergab eine leere Ausgabe"),
+        ("empty_files", "{} leere Dateien ergaben leere Ausgaben"),
+        (
+            "special_file",
+            "{} Socket, Gerät, FIFO oder verwaister Symlink übersprungen",
+        ),
+        (
+            "special_files",
+            "{} Sockets, Geräte, FIFOs oder verwaiste Symlinks übersprungen",
+        ),
+        (
+            "unstable_file",
+            "{} Datei hat sich beim Lesen verändert und ist eventuell unvollständig",
+        ),
+        (
+            "unstable_files",
+            "{} Dateien haben sich beim Lesen verändert und sind eventuell unvollständig",
+        ),
+    ],
+)];
+
+static CATALOG: OnceLock<Vec<&'static str>> = OnceLock::new();
+
+/// Picks the language from `lang`, or else from LC_ALL, LC_MESSAGES and
+/// LANG, and loads its messages: the built-in translation if there is
+/// one, overridden by `<config dir>/locale/<lang>.toml` if that exists.
+pub fn init(lang: Option<&str>) -> Result<()> {
+    let lang = match lang {
+        Some(lang) => lang.to_string(),
+        None => ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default(),
+    };
+
+    let mut catalog = ENGLISH.to_vec();
+    for candidate in candidates(&lang) {
+        if let Some((_, builtin)) = BUILTIN.iter().find(|(name, _)| *name == candidate) {
+            for (key, text) in *builtin {
+                if let Some(index) = KEYS.iter().position(|known| known == key) {
+                    catalog[index] = text;
+                }
+            }
+        }
+
+        let Some(path) =
+            config::dir().map(|dir| dir.join("locale").join(format!("{}.toml", candidate)))
+        else {
+            continue;
+        };
+        if !path.exists() {
+            continue;
+        }
+        let file = Config::load(Some(&path))
+            .with_context(|| format!("Failed to load messages for `{}`", candidate))?;
+        for (index, key) in KEYS.iter().enumerate() {
+            if let Some(text) = file.get_str(key)? {
+                catalog[index] = Box::leak(text.to_string().into_boxed_str());
+            }
+        }
+    }
+
+    let _ = CATALOG.set(catalog);
+    Ok(())
+}
+
+/// The language to try for a locale such as `de_AT.UTF-8`, most general
+/// first so that the specific one can override it: `de`, then `de_AT`.
+fn candidates(locale: &str) -> Vec<String> {
+    let name = locale.split(['.', '@']).next().unwrap_or_default();
+    if name.is_empty() || name == "C" || name == "POSIX" {
+        return Vec::new();
+    }
+    let language = name.split(['_', '-']).next().unwrap_or(name);
+    let mut candidates = vec![language.to_string()];
+    if name != language {
+        candidates.push(name.to_string());
+    }
+    candidates
+}
+
+impl Msg {
+    pub fn text(self) -> &'static str {
+        match CATALOG.get() {
+            Some(catalog) => catalog[self as usize],
+            None => ENGLISH[self as usize],
+        }
+    }
+
+    /// The message with each `{}` replaced by the next of `args`.
+    pub fn fill(self, args: &[&dyn Display]) -> String {
+        let mut out = String::new();
+        let mut args = args.iter();
+        let mut parts = self.text().split("{}");
+        if let Some(first) = parts.next() {
+            out.push_str(first);
+        }
+        for part in parts {
+            if let Some(arg) = args.next() {
+                out.push_str(&arg.to_string());
+            }
+            out.push_str(part);
+        }
+        out
+    }
+}
+
+impl Display for Msg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(self.text())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidates() {
+        assert_eq!(candidates("de_AT.UTF-8"), ["de", "de_AT"]);
+        assert_eq!(candidates("fr"), ["fr"]);
+        assert!(candidates("C.UTF-8").is_empty());
+        assert!(candidates("").is_empty());
+    }
+
+    #[test]
+    fn test_fill() {
+        assert_eq!(Msg::SlowestFiles.fill(&[&3, &10]), "Slowest 3 of 10 files:");
+        assert_eq!(KEYS.len(), ENGLISH.len());
+        for (_, builtin) in BUILTIN {
+            assert!(builtin.iter().all(|(key, _)| KEYS.contains(key)));
+        }
+    }
+}
@@ -0,0 +1,938 @@
+use std::{
+    fmt,
+    io::{self, BufReader, BufWriter},
+    path::{self, Path, PathBuf},
+    sync::Arc,
+    time::Instant,
+};
+#[cfg(feature = "cli")]
+use walkdir::DirEntry;
+
+use crate::{
+    cancel::CancellationToken,
+    cipher::{Cipher, Xor},
+    error::JustError,
+    failure::{self, Failed},
+    glob::Glob,
+    key::Redacted,
+    options::ProcessOptions,
+    progress::{FileProgress, FileResult, FileStart, Hooks, RunResult, RunStats},
+    storage::{LocalStorage, Storage},
+    transform,
+    walk::{filter_path, normalize_path, output_next_to},
+};
+
+/// A configured run over a file or directory, the way the CLI's flags
+/// describe one:
+///
+/// ```no_run
+/// # fn main() -> anyhow::Result<()> {
+/// let outputs = xortool::job::Job::builder()
+///     .input("photos")
+///     .key([0x1a, 0x2b])
+///     .recursive(true)
+///     .output_dir("encrypted")
+///     .exclude("*.tmp")
+///     .build()?
+///     .run()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Job {
+    input: PathBuf,
+    root: PathBuf,
+    key: Vec<u8>,
+    cipher: Option<CipherFactory>,
+    recursive: bool,
+    output_dir: Option<PathBuf>,
+    exclude: Vec<Glob>,
+    buffer_size: usize,
+    hooks: Hooks,
+    cancel: Option<CancellationToken>,
+    storage: Arc<dyn Storage>,
+}
+
+/// Makes a fresh cipher for each file, so ciphers can keep state.
+#[derive(Clone)]
+struct CipherFactory(Arc<dyn Fn() -> Box<dyn Cipher> + Send + Sync>);
+
+impl fmt::Debug for CipherFactory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("CipherFactory")
+    }
+}
+
+/// Collects the settings of a `Job`; `input` is required, and so is
+/// `key` unless a `cipher` is given.
+#[derive(Default)]
+pub struct JobBuilder {
+    input: Option<PathBuf>,
+    key: Option<Vec<u8>>,
+    cipher: Option<CipherFactory>,
+    options: ProcessOptions,
+    hooks: Hooks,
+    cancel: Option<CancellationToken>,
+    storage: Option<Arc<dyn Storage>>,
+}
+
+// The key is left out of both `Debug` impls, which end up in logs.
+impl fmt::Debug for Job {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Job")
+            .field("input", &self.input)
+            .field("root", &self.root)
+            .field("key", &Redacted(&self.key))
+            .field("cipher", &self.cipher)
+            .field("recursive", &self.recursive)
+            .field("output_dir", &self.output_dir)
+            .field("exclude", &self.exclude)
+            .field("buffer_size", &self.buffer_size)
+            .field("hooks", &self.hooks)
+            .field("cancel", &self.cancel)
+            .field("storage", &self.storage)
+            .finish()
+    }
+}
+
+impl fmt::Debug for JobBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("JobBuilder")
+            .field("input", &self.input)
+            .field("key", &self.key.as_deref().map(Redacted))
+            .field("cipher", &self.cipher)
+            .field("options", &self.options)
+            .field("hooks", &self.hooks)
+            .field("cancel", &self.cancel)
+            .field("storage", &self.storage)
+            .finish()
+    }
+}
+
+impl JobBuilder {
+    /// The file or directory to transform.
+    pub fn input(mut self, input: impl Into<PathBuf>) -> Self {
+        self.input = Some(input.into());
+        self
+    }
+
+    /// The key to XOR with.
+    pub fn key(mut self, key: impl Into<Vec<u8>>) -> Self {
+        self.key = Some(key.into());
+        self
+    }
+
+    /// Transforms with the ciphers `make` returns, one per file, instead
+    /// of XOR with the key. The key is then optional; if given, it is
+    /// still what checkpoints are checked against.
+    pub fn cipher<C: Cipher + 'static>(
+        mut self,
+        make: impl Fn() -> C + Send + Sync + 'static,
+    ) -> Self {
+        self.cipher = Some(CipherFactory(Arc::new(move || {
+            Box::new(make()) as Box<dyn Cipher>
+        })));
+        self
+    }
+
+    /// Sets all of `options` at once, replacing any set before.
+    pub fn options(mut self, options: ProcessOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Whether to enter subdirectories of a directory input.
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.options.recursive = recursive;
+        self
+    }
+
+    /// Puts the outputs under `dir`, at the same paths relative to it as
+    /// the inputs have to the input directory, instead of in `xor/` next
+    /// to each input.
+    pub fn output_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.options.output_dir = Some(dir.into());
+        self
+    }
+
+    /// Leaves out files and directories matching the shell-style
+    /// `pattern`, such as `*.tmp` or `logs/**`. Can be given more than
+    /// once; an input file named directly is never left out.
+    pub fn exclude(mut self, pattern: impl Into<String>) -> Self {
+        self.options.exclude.push(pattern.into());
+        self
+    }
+
+    pub fn buffer_size(mut self, size: usize) -> Self {
+        self.options.buffer_size = size;
+        self
+    }
+
+    /// Allows a cipher that leaves data unchanged, such as a key of only
+    /// zero bytes, which copies files.
+    pub fn force(mut self, force: bool) -> Self {
+        self.options.force = force;
+        self
+    }
+
+    /// Stops the run, removing the output being written, once `token` is
+    /// cancelled. `run` then fails with `JustError::Interrupted`.
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Reads inputs from and writes outputs to `storage` instead of the
+    /// local filesystem. The input and output directory are paths in it.
+    pub fn storage(mut self, storage: impl Storage + 'static) -> Self {
+        self.storage = Some(Arc::new(storage));
+        self
+    }
+
+    /// Calls `hook` with the input before `run` transforms anything.
+    pub fn on_run_start(mut self, hook: impl Fn(&Path) + Send + Sync + 'static) -> Self {
+        self.hooks.set_run_start(hook);
+        self
+    }
+
+    /// Calls `hook` before each file is transformed.
+    pub fn on_file_start(mut self, hook: impl Fn(&FileStart) + Send + Sync + 'static) -> Self {
+        self.hooks.set_file_start(hook);
+        self
+    }
+
+    /// Calls `hook` as each file is transformed, after every chunk.
+    pub fn on_progress(mut self, hook: impl Fn(&FileProgress) + Send + Sync + 'static) -> Self {
+        self.hooks.set_progress(hook);
+        self
+    }
+
+    /// Calls `hook` when each file is done, whether or not it succeeded.
+    pub fn on_file_complete(mut self, hook: impl Fn(&FileResult) + Send + Sync + 'static) -> Self {
+        self.hooks.set_file_complete(hook);
+        self
+    }
+
+    /// Calls `hook` once the run is over, whether or not it succeeded,
+    /// with the number of files and bytes done.
+    pub fn on_run_complete(mut self, hook: impl Fn(&RunResult) + Send + Sync + 'static) -> Self {
+        self.hooks.set_run_complete(hook);
+        self
+    }
+
+    /// Checks the settings and resolves the input, which must exist.
+    pub fn build(self) -> Result<Job, JustError> {
+        let Some(input) = self.input else {
+            return Err(JustError::InvalidOptions("No input given".into()));
+        };
+        let key = match (self.key, &self.cipher) {
+            (Some(key), _) if key.is_empty() => {
+                return Err(JustError::InvalidKey("Key must not be empty".into()))
+            }
+            (Some(key), _) => key,
+            (None, Some(_)) => Vec::new(),
+            (None, None) => return Err(JustError::InvalidKey("No key given".into())),
+        };
+        let identity = match &self.cipher {
+            Some(make) => (make.0)().is_identity(),
+            None => Xor::new(key.as_slice()).is_identity(),
+        };
+        let options = self.options;
+        if identity && !options.force {
+            return Err(JustError::InvalidKey(
+                "Key is all zero bytes, so the output would be an unencrypted copy of the input"
+                    .into(),
+            ));
+        }
+        if options.buffer_size == 0 {
+            return Err(JustError::InvalidOptions(
+                "Buffer size must not be zero".into(),
+            ));
+        }
+        let exclude = options
+            .exclude
+            .iter()
+            .map(|pattern| Glob::new(pattern))
+            .collect::<Result<_, _>>()?;
+
+        let storage = self.storage.unwrap_or_else(|| Arc::new(LocalStorage));
+        let resolved = storage
+            .resolve(&input)
+            .map_err(|e| JustError::io(&input, e))?;
+        let root = match storage.is_dir(&resolved) {
+            true => resolved.clone(),
+            false => resolved.parent().unwrap_or(&resolved).to_path_buf(),
+        };
+        let output_dir = match options.output_dir {
+            Some(dir) => Some(resolve_output_dir(storage.as_ref(), &dir)?),
+            None => None,
+        };
+        Ok(Job {
+            input: resolved,
+            root,
+            key,
+            cipher: self.cipher,
+            recursive: options.recursive,
+            output_dir,
+            exclude,
+            buffer_size: options.buffer_size,
+            hooks: self.hooks,
+            cancel: self.cancel,
+            storage,
+        })
+    }
+}
+
+/// `dir` made absolute, and resolved by `storage` if it already exists,
+/// so it can be compared with the paths of a walk.
+fn resolve_output_dir(storage: &dyn Storage, dir: &Path) -> Result<PathBuf, JustError> {
+    let absolute = path::absolute(normalize_path(dir)).map_err(|e| JustError::io(dir, e))?;
+    Ok(storage.resolve(&absolute).unwrap_or(absolute))
+}
+
+impl Job {
+    pub fn builder() -> JobBuilder {
+        JobBuilder::default()
+    }
+
+    /// The input, resolved to an absolute path.
+    pub fn input(&self) -> &Path {
+        &self.input
+    }
+
+    /// The input if it is a directory, otherwise the one it is in.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// A new instance of the job's cipher, for transforming one file.
+    pub fn cipher(&self) -> Box<dyn Cipher> {
+        match &self.cipher {
+            Some(make) => (make.0)(),
+            None => Box::new(Xor::new(self.key.as_slice())),
+        }
+    }
+
+    pub fn recursive(&self) -> bool {
+        self.recursive
+    }
+
+    pub fn output_dir(&self) -> Option<&Path> {
+        self.output_dir.as_deref()
+    }
+
+    pub fn buffer_size(&self) -> usize {
+        self.buffer_size
+    }
+
+    pub fn storage(&self) -> &dyn Storage {
+        self.storage.as_ref()
+    }
+
+    /// Totals of the current or last run, which keep counting while it
+    /// goes on.
+    pub fn stats(&self) -> Arc<RunStats> {
+        Arc::clone(self.hooks.stats())
+    }
+
+    #[cfg(feature = "cli")]
+    pub(crate) fn hooks(&self) -> &Hooks {
+        &self.hooks
+    }
+
+    /// Fails with `JustError::Interrupted` if the job's token has been
+    /// cancelled.
+    pub fn check_cancelled(&self) -> Result<(), JustError> {
+        match &self.cancel {
+            Some(token) if token.is_cancelled() => Err(JustError::Interrupted),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether a walk of the root should visit `entry`: as `filter_entry`
+    /// decides, and then only if no exclude pattern matches it and it
+    /// isn't in the output directory.
+    #[cfg(feature = "cli")]
+    pub fn includes(&self, entry: &DirEntry) -> bool {
+        self.includes_path(entry.path(), entry.file_type().is_dir())
+    }
+
+    /// `includes` for a path from the storage's listing of the root.
+    fn includes_path(&self, path: &Path, is_dir: bool) -> bool {
+        if !filter_path(path, is_dir, &self.root, self.recursive) {
+            return false;
+        }
+        if path == self.root {
+            return true;
+        }
+        if self
+            .output_dir
+            .as_deref()
+            .is_some_and(|dir| path.starts_with(dir))
+        {
+            return false;
+        }
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        !self.exclude.iter().any(|glob| glob.matches(relative))
+    }
+
+    /// Where the output of `input`, one of the files this job transforms,
+    /// goes.
+    pub fn output_path(&self, input: &Path) -> Result<PathBuf, JustError> {
+        match &self.output_dir {
+            Some(dir) => {
+                let relative = match input.strip_prefix(&self.root) {
+                    Ok(relative) => relative,
+                    Err(_) => Path::new(input.file_name().ok_or_else(|| {
+                        JustError::InvalidOptions(format!("{} has no file name", input.display()))
+                    })?),
+                };
+                Ok(dir.join(relative))
+            }
+            None => {
+                let resolved = self
+                    .storage
+                    .resolve(input)
+                    .map_err(|e| JustError::io(input, e))?;
+                output_next_to(&resolved)
+            }
+        }
+    }
+
+    /// The files `run` transforms, in order: the input itself if it is a
+    /// file, otherwise the files under it.
+    pub fn inputs(&self) -> Result<Vec<PathBuf>, JustError> {
+        if !self.storage.is_dir(&self.input) {
+            return Ok(vec![self.input.clone()]);
+        }
+        self.storage
+            .list(&self.input, &|path, is_dir| {
+                self.includes_path(path, is_dir)
+            })
+            .map_err(|e| JustError::io(&self.input, e))
+    }
+
+    /// Transforms every input, stopping at the first failure or when
+    /// cancelled, and reports to the hooks as it goes. The output of a
+    /// file that fails is removed. Returns the outputs written, in walk
+    /// order.
+    pub fn run(&self) -> Result<Vec<PathBuf>, JustError> {
+        let start = Instant::now();
+        self.hooks.run_start(&self.input);
+        let result = self.run_files();
+        self.hooks.run_complete(
+            &self.input,
+            start.elapsed(),
+            result.as_ref().err().map(|e| e as _),
+        );
+        result
+    }
+
+    fn run_files(&self) -> Result<Vec<PathBuf>, JustError> {
+        let mut outputs = Vec::new();
+        for file in self.inputs()? {
+            self.check_cancelled()?;
+            outputs.push(self.run_one(&file)?);
+        }
+        Ok(outputs)
+    }
+
+    /// `run` with the files spread over `workers` threads. Results are
+    /// collected in walk order whatever order the files finish in: the
+    /// outputs as `run` returns them, or the error of the first file in
+    /// walk order that failed. Once a file fails no later ones are started,
+    /// but every earlier one still runs, so which error comes back doesn't
+    /// depend on timing.
+    /// Hooks are called from the worker threads.
+    #[cfg(feature = "parallel")]
+    pub fn run_parallel(&self, workers: usize) -> Result<Vec<PathBuf>, JustError> {
+        let start = Instant::now();
+        self.hooks.run_start(&self.input);
+        let result = self.run_files_parallel(workers.max(1));
+        self.hooks.run_complete(
+            &self.input,
+            start.elapsed(),
+            result.as_ref().err().map(|e| e as _),
+        );
+        result
+    }
+
+    #[cfg(feature = "parallel")]
+    fn run_files_parallel(&self, workers: usize) -> Result<Vec<PathBuf>, JustError> {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Mutex,
+        };
+
+        let files = self.inputs()?;
+        let results: Vec<Mutex<Option<Result<PathBuf, JustError>>>> =
+            files.iter().map(|_| Mutex::new(None)).collect();
+        let next = AtomicUsize::new(0);
+        // Index of the earliest file known to have failed. Only files after
+        // it are left out, so the one it ends up at is the earliest of all.
+        let first_failed = AtomicUsize::new(usize::MAX);
+        std::thread::scope(|scope| {
+            for _ in 0..workers.min(files.len()) {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    if index >= files.len() || index > first_failed.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    let result = self
+                        .check_cancelled()
+                        .and_then(|()| self.run_one(&files[index]));
+                    if result.is_err() {
+                        first_failed.fetch_min(index, Ordering::Relaxed);
+                    }
+                    *results[index].lock().unwrap() = Some(result);
+                });
+            }
+        });
+
+        // Every file before the earliest failure has a result, and the
+        // files after it that have none were never started.
+        let mut outputs = Vec::new();
+        for result in results.into_iter().filter_map(|r| r.into_inner().unwrap()) {
+            outputs.push(result?);
+        }
+        Ok(outputs)
+    }
+
+    /// Transforms one input, reporting it to the hooks, and returns its
+    /// output. The output of a file that fails is removed.
+    fn run_one(&self, file: &Path) -> Result<PathBuf, JustError> {
+        let output = self.output_path(file)?;
+        self.hooks.file_start(&FileStart {
+            input: file,
+            output: &output,
+        });
+        let start = Instant::now();
+        let result = transform_file(
+            self.storage.as_ref(),
+            file,
+            &output,
+            self.cipher().as_mut(),
+            self.buffer_size,
+            |done, total| {
+                self.hooks.progress(&FileProgress {
+                    path: file,
+                    done,
+                    total,
+                });
+                self.check_cancelled()
+            },
+        );
+        self.hooks.file_complete(&FileResult {
+            input: file,
+            output: &output,
+            bytes: *result.as_ref().unwrap_or(&0),
+            duration: start.elapsed(),
+            error: result.as_ref().err().map(|e| e as _),
+        });
+        result.map(|_| output)
+    }
+}
+
+/// Transforms the file at `input` into `output` with `key`, creating the
+/// output's directory if needed, and removing the output if the transform
+/// fails partway. Returns the bytes transformed.
+pub fn run_file(
+    input: &Path,
+    output: &Path,
+    key: &[u8],
+    buffer_size: usize,
+) -> Result<u64, JustError> {
+    transform_file(
+        &LocalStorage,
+        input,
+        output,
+        &mut Xor::new(key),
+        buffer_size,
+        |_, _| Ok(()),
+    )
+}
+
+/// `run_file` with `cipher` and files in `storage`, calling `progress`
+/// with the bytes done and the total after each chunk. A failure after the
+/// output was created removes it; one before leaves what was there alone.
+fn transform_file(
+    storage: &dyn Storage,
+    input: &Path,
+    output: &Path,
+    cipher: &mut dyn Cipher,
+    buffer_size: usize,
+    mut progress: impl FnMut(u64, u64) -> Result<(), JustError>,
+) -> Result<u64, JustError> {
+    let (file, total) = storage.open(input).map_err(|e| JustError::io(input, e))?;
+    let output_file = storage
+        .create(output)
+        .map_err(|e| JustError::io(output, e))?;
+
+    let mut buffer = vec![0; buffer_size];
+    // Errors come back as io::Error: the output's are tagged by
+    // failure::Output and the progress callback's carry a JustError, so
+    // each can be put back with the right path.
+    transform::stream_with(
+        &mut BufReader::new(file),
+        &mut BufWriter::new(failure::Output::new(output_file, output)),
+        cipher,
+        &mut buffer,
+        0,
+        |done| progress(done, total).map_err(io::Error::other),
+    )
+    .map_err(|e| {
+        if e.get_ref().is_none() {
+            return JustError::io(input, e);
+        }
+        let kind = e.kind();
+        let inner = e.into_inner().unwrap();
+        match inner.downcast::<JustError>() {
+            Ok(error) => *error,
+            Err(inner) => match inner.downcast::<Failed>() {
+                Ok(failed) => JustError::io(output, failed.into_source()),
+                Err(inner) => JustError::io(input, io::Error::new(kind, inner)),
+            },
+        }
+    })
+    .inspect_err(|_| {
+        let _ = storage.remove(output);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_run_round_trip() {
+        let root = std::env::temp_dir().join(format!("xortool-job-{}", std::process::id()));
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("a"), b"first file").unwrap();
+        fs::write(root.join("sub/b"), b"second file").unwrap();
+        fs::write(root.join("sub/c.tmp"), b"scratch").unwrap();
+        let key = [0x5a, 0xa5];
+
+        let job = Job::builder()
+            .input(&root)
+            .key(key)
+            .recursive(true)
+            .exclude("*.tmp")
+            .build()
+            .unwrap();
+        let outputs = job.run().unwrap();
+        assert_eq!(outputs.len(), 2);
+        assert_ne!(fs::read(&outputs[0]).unwrap(), b"first file");
+
+        let restored = root.join("restored");
+        run_file(&outputs[1], &restored, &key, 4).unwrap();
+        assert_eq!(fs::read(&restored).unwrap(), b"second file");
+
+        // With an output directory inside the input, the walk leaves it
+        // alone and mirrors the tree into it.
+        let job = Job::builder()
+            .input(&root)
+            .key(key)
+            .recursive(true)
+            .output_dir(root.join("out"))
+            .exclude("/restored")
+            .build()
+            .unwrap();
+        let outputs = job.run().unwrap();
+        let root = job.root().to_path_buf();
+        assert_eq!(
+            outputs,
+            [
+                root.join("out/a"),
+                root.join("out/sub/b"),
+                root.join("out/sub/c.tmp")
+            ]
+        );
+        assert_eq!(job.run().unwrap().len(), 3);
+
+        let builder = Job::builder().input(&root).key([0xde, 0xad, 0xbe]);
+        let debug = format!("{:?} {:?}", builder, job);
+        assert!(debug.contains("<3 bytes>") && debug.contains("<2 bytes>"));
+        assert!(
+            !debug.contains("222, 173") && !debug.contains("90, 165"),
+            "{}",
+            debug
+        );
+
+        assert!(matches!(
+            Job::builder().input(&root).build(),
+            Err(JustError::InvalidKey(_))
+        ));
+        assert!(matches!(
+            Job::builder().input(&root).key([0, 0]).build(),
+            Err(JustError::InvalidKey(_))
+        ));
+        assert!(Job::builder()
+            .input(&root)
+            .cipher(|| Xor::new([1]))
+            .build()
+            .is_ok());
+        assert!(matches!(
+            Job::builder().input(root.join("missing")).key([1]).build(),
+            Err(JustError::Io { .. })
+        ));
+        assert!(Job::builder()
+            .input(&root)
+            .key([0, 0])
+            .force(true)
+            .build()
+            .is_ok());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_hooks() {
+        use std::sync::{Arc, Mutex};
+
+        let root = std::env::temp_dir().join(format!("xortool-hooks-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("a"), vec![1; 10]).unwrap();
+        fs::write(root.join("b"), vec![2; 3]).unwrap();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let (start, progress, complete, finish) = (
+            Arc::clone(&events),
+            Arc::clone(&events),
+            Arc::clone(&events),
+            Arc::clone(&events),
+        );
+        let job = Job::builder()
+            .input(&root)
+            .key([7])
+            .buffer_size(4)
+            .on_file_start(move |_| start.lock().unwrap().push((0, 0)))
+            .on_progress(move |p| progress.lock().unwrap().push((p.done, p.total)))
+            .on_file_complete(move |r| {
+                assert!(r.error.is_none());
+                complete.lock().unwrap().push((r.bytes, u64::MAX));
+            })
+            .on_run_complete(move |r| {
+                assert!(r.error.is_none() && r.failed == 0);
+                finish.lock().unwrap().push((r.files, r.bytes));
+            })
+            .build()
+            .unwrap();
+        job.run().unwrap();
+        let stats = job.stats();
+        assert_eq!((stats.files_done(), stats.files_failed()), (2, 0));
+        assert_eq!(stats.bytes_done(), 13);
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                (0, 0),
+                (4, 10),
+                (8, 10),
+                (10, 10),
+                (10, u64::MAX),
+                (0, 0),
+                (3, 3),
+                (3, u64::MAX),
+                (2, 13)
+            ]
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_cancellation() {
+        let root = std::env::temp_dir().join(format!("xortool-cancel-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("a"), vec![1; 100]).unwrap();
+        fs::write(root.join("b"), vec![2; 100]).unwrap();
+
+        let token = CancellationToken::new();
+        let cancel = token.clone();
+        let job = Job::builder()
+            .input(&root)
+            .key([7])
+            .buffer_size(10)
+            .cancellation(token)
+            .on_progress(move |p| {
+                if p.done >= 50 {
+                    cancel.cancel();
+                }
+            })
+            .build()
+            .unwrap();
+        let error = job.run().unwrap_err();
+        assert!(matches!(error, JustError::Interrupted));
+        assert!(!job.output_path(&job.root().join("a")).unwrap().exists());
+        assert!(!job.output_path(&job.root().join("b")).unwrap().exists());
+
+        // An input that can't be read leaves an earlier output alone.
+        fs::write(root.join("kept"), b"earlier").unwrap();
+        assert!(run_file(&root.join("missing"), &root.join("kept"), &[7], 10).is_err());
+        assert_eq!(fs::read(root.join("kept")).unwrap(), b"earlier");
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_run_parallel() {
+        let root = std::env::temp_dir().join(format!("xortool-parallel-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        for i in 0..20u8 {
+            fs::write(root.join(format!("{:02}", i)), vec![i; 100 * i as usize]).unwrap();
+        }
+
+        let job = Job::builder()
+            .input(&root)
+            .key([0x3c])
+            .output_dir(root.join("out"))
+            .buffer_size(64)
+            .build()
+            .unwrap();
+        let outputs = job.run_parallel(4).unwrap();
+        let expected: Vec<_> = (0..20)
+            .map(|i| job.root().join(format!("out/{:02}", i)))
+            .collect();
+        assert_eq!(outputs, expected);
+        assert_eq!(fs::read(&outputs[3]).unwrap(), vec![3 ^ 0x3c; 300]);
+        assert_eq!(job.stats().files_done(), 20);
+        assert_eq!(job.stats().bytes_done(), 100 * (0..20).sum::<u64>());
+        assert_eq!(job.run_parallel(0).unwrap(), expected);
+
+        // Outputs that can't be created, since directories are in the way:
+        // the earlier one is always the error.
+        fs::remove_file(&outputs[5]).unwrap();
+        fs::remove_file(&outputs[12]).unwrap();
+        fs::create_dir(&outputs[5]).unwrap();
+        fs::create_dir(&outputs[12]).unwrap();
+        for _ in 0..10 {
+            match job.run_parallel(4).unwrap_err() {
+                JustError::Io { path, .. } => assert_eq!(path, outputs[5]),
+                e => panic!("unexpected error: {}", e),
+            }
+        }
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_storage() {
+        use std::{
+            collections::BTreeMap,
+            io::{Cursor, Read, Write},
+            sync::Mutex,
+        };
+
+        /// Files in a map, written when the writer is dropped.
+        #[derive(Debug, Default)]
+        struct Memory(Arc<Mutex<BTreeMap<PathBuf, Vec<u8>>>>);
+
+        struct Writer(PathBuf, Vec<u8>, Arc<Mutex<BTreeMap<PathBuf, Vec<u8>>>>);
+
+        impl Write for Writer {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.1.write(buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        impl Drop for Writer {
+            fn drop(&mut self) {
+                let data = std::mem::take(&mut self.1);
+                self.2.lock().unwrap().insert(self.0.clone(), data);
+            }
+        }
+
+        impl Storage for Memory {
+            fn resolve(&self, path: &Path) -> io::Result<PathBuf> {
+                match self.is_dir(path) || self.0.lock().unwrap().contains_key(path) {
+                    true => Ok(path.to_path_buf()),
+                    false => Err(io::ErrorKind::NotFound.into()),
+                }
+            }
+
+            fn is_dir(&self, path: &Path) -> bool {
+                let files = self.0.lock().unwrap();
+                files
+                    .keys()
+                    .any(|file| file != path && file.starts_with(path))
+            }
+
+            fn open(&self, path: &Path) -> io::Result<(Box<dyn Read + Send>, u64)> {
+                let data = self.0.lock().unwrap().get(path).cloned();
+                let data = data.ok_or(io::ErrorKind::NotFound)?;
+                let len = data.len() as u64;
+                Ok((Box::new(Cursor::new(data)), len))
+            }
+
+            fn create(&self, path: &Path) -> io::Result<Box<dyn Write + Send>> {
+                Ok(Box::new(Writer(
+                    path.to_path_buf(),
+                    Vec::new(),
+                    Arc::clone(&self.0),
+                )))
+            }
+
+            fn remove(&self, path: &Path) -> io::Result<()> {
+                self.0.lock().unwrap().remove(path);
+                Ok(())
+            }
+
+            fn list(
+                &self,
+                root: &Path,
+                include: &dyn Fn(&Path, bool) -> bool,
+            ) -> io::Result<Vec<PathBuf>> {
+                let files = self.0.lock().unwrap();
+                Ok(files
+                    .keys()
+                    .filter(|file| {
+                        file.strip_prefix(root).is_ok_and(|relative| {
+                            let mut path = root.to_path_buf();
+                            let mut parts = relative.components().peekable();
+                            while let Some(part) = parts.next() {
+                                path.push(part);
+                                if !include(&path, parts.peek().is_some()) {
+                                    return false;
+                                }
+                            }
+                            true
+                        })
+                    })
+                    .cloned()
+                    .collect())
+            }
+        }
+
+        let files = Arc::new(Mutex::new(BTreeMap::new()));
+        files
+            .lock()
+            .unwrap()
+            .insert(PathBuf::from("/bucket/a"), b"in memory".to_vec());
+        files
+            .lock()
+            .unwrap()
+            .insert(PathBuf::from("/bucket/skip.tmp"), b"left out".to_vec());
+
+        let outputs = Job::builder()
+            .input("/bucket")
+            .key([0x21])
+            .exclude("*.tmp")
+            .storage(Memory(Arc::clone(&files)))
+            .build()
+            .unwrap()
+            .run()
+            .unwrap();
+        assert_eq!(outputs, [PathBuf::from("/bucket/xor/a")]);
+        let mut data = files.lock().unwrap()[&outputs[0]].clone();
+        Xor::new([0x21]).apply(0, &mut data);
+        assert_eq!(data, b"in memory");
+    }
+}
@@ -0,0 +1,241 @@
+use std::{env, fs, path::PathBuf};
+
+use crate::error::JustError;
+
+/// Where the key for a run comes from. The CLI picks one from its flags
+/// and loads it once before anything else runs, so a new source only
+/// needs a type here and a flag to select it.
+pub trait KeySource {
+    /// What the key is read from, for messages about it.
+    fn describe(&self) -> String;
+
+    fn load(&self) -> Result<Vec<u8>, JustError>;
+}
+
+/// A key given directly in hex.
+#[derive(Clone, Debug)]
+pub struct HexKey(pub String);
+
+impl KeySource for HexKey {
+    fn describe(&self) -> String {
+        "the command line".to_string()
+    }
+
+    fn load(&self) -> Result<Vec<u8>, JustError> {
+        parse_hex_key(&self.0)
+    }
+}
+
+/// A file holding the key in hex. Surrounding whitespace, such as the
+/// newline an editor adds, is ignored.
+#[derive(Clone, Debug)]
+pub struct KeyFile(pub PathBuf);
+
+impl KeySource for KeyFile {
+    fn describe(&self) -> String {
+        self.0.display().to_string()
+    }
+
+    fn load(&self) -> Result<Vec<u8>, JustError> {
+        let text = fs::read_to_string(&self.0).map_err(|source| JustError::Io {
+            path: self.0.clone(),
+            source,
+        })?;
+        parse_secret_key(text.trim())
+    }
+}
+
+/// An environment variable holding the key in hex, which keeps it out of
+/// the process list.
+#[derive(Clone, Debug)]
+pub struct KeyEnv(pub String);
+
+impl KeySource for KeyEnv {
+    fn describe(&self) -> String {
+        format!("environment variable {}", self.0)
+    }
+
+    fn load(&self) -> Result<Vec<u8>, JustError> {
+        let value = env::var(&self.0).map_err(|e| {
+            JustError::InvalidKey(format!("Environment variable {}: {}", self.0, e))
+        })?;
+        parse_secret_key(value.trim())
+    }
+}
+
+/// Asks for the key in hex on the terminal without echoing it.
+#[cfg(feature = "native")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct KeyPrompt;
+
+#[cfg(feature = "native")]
+impl KeySource for KeyPrompt {
+    fn describe(&self) -> String {
+        "the prompt".to_string()
+    }
+
+    #[cfg(feature = "cli")]
+    fn load(&self) -> Result<Vec<u8>, JustError> {
+        let text = prompt::read_hidden("Key (hex): ")
+            .map_err(|e| JustError::InvalidKey(format!("Can't read the key: {}", e)))?;
+        parse_secret_key(text.trim())
+    }
+
+    /// Reading without echo needs the terminal support of the `cli`
+    /// feature, and echoing a key isn't worth the fallback.
+    #[cfg(not(feature = "cli"))]
+    fn load(&self) -> Result<Vec<u8>, JustError> {
+        Err(JustError::InvalidKey(
+            "Prompting for the key needs the `cli` feature".into(),
+        ))
+    }
+}
+
+#[cfg(feature = "cli")]
+mod prompt {
+    use crossterm::{
+        event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+        terminal,
+    };
+    use std::io::{self, Write};
+
+    /// Reads a line from the terminal in raw mode, so nothing typed is
+    /// shown. The prompt goes to stderr to keep stdout for output.
+    pub fn read_hidden(prompt: &str) -> io::Result<String> {
+        let mut stderr = io::stderr();
+        write!(stderr, "{}", prompt)?;
+        stderr.flush()?;
+        terminal::enable_raw_mode()?;
+        let line = read_line();
+        let _ = terminal::disable_raw_mode();
+        writeln!(stderr)?;
+        line
+    }
+
+    fn read_line() -> io::Result<String> {
+        let mut line = String::new();
+        loop {
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Enter => return Ok(line),
+                KeyCode::Backspace => {
+                    line.pop();
+                }
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted"));
+                }
+                KeyCode::Char(c) => line.push(c),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Parses a key given in hex, with or without a `0x` prefix.
+pub fn parse_hex_key(hex_str: &str) -> Result<Vec<u8>, JustError> {
+    let original = hex_str;
+    let hex_str = strip_hex_prefix(hex_str);
+
+    if hex_str.is_empty() {
+        return Err(JustError::InvalidKey(format!(
+            "Key must not be empty (original: '{}')",
+            original
+        )));
+    }
+
+    hex::decode(hex_str).map_err(|e| {
+        JustError::InvalidKey(format!(
+            "Invalid hex key (parsed: '{}', original: '{}'): {}",
+            hex_str, original, e
+        ))
+    })
+}
+
+/// `parse_hex_key` for a key that wasn't typed on the command line, so
+/// may never have been seen by anyone. Errors give its length or the
+/// position of the bad digit, never the text itself.
+fn parse_secret_key(text: &str) -> Result<Vec<u8>, JustError> {
+    let digits = strip_hex_prefix(text);
+    if digits.is_empty() {
+        return Err(JustError::InvalidKey("Key must not be empty".into()));
+    }
+
+    hex::decode(digits).map_err(|e| {
+        JustError::InvalidKey(match e {
+            hex::FromHexError::InvalidHexCharacter { index, .. } => format!(
+                "Invalid hex key: character {} of {} is not a hex digit",
+                text.len() - digits.len() + index + 1,
+                text.len()
+            ),
+            hex::FromHexError::OddLength => {
+                format!("Invalid hex key: {} digits is an odd number", digits.len())
+            }
+            hex::FromHexError::InvalidStringLength => "Invalid hex key".into(),
+        })
+    })
+}
+
+fn strip_hex_prefix(text: &str) -> &str {
+    text.strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .unwrap_or(text)
+}
+
+pub use crate::core::is_identity_key;
+
+/// Stands in for a key in `Debug` output, showing only its length.
+pub(crate) struct Redacted<'a>(pub &'a [u8]);
+
+impl std::fmt::Debug for Redacted<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "<{} bytes>", self.0.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_parsing() {
+        // Valid keys
+        assert!(parse_hex_key("0x1a2b").is_ok());
+        assert!(parse_hex_key("0X1A2B").is_ok());
+        assert!(parse_hex_key("1a2b").is_ok());
+        assert!(parse_hex_key("1234abcd").is_ok());
+
+        // Invalid keys
+        assert!(parse_hex_key("0x").is_err());
+        assert!(parse_hex_key("0xgh").is_err());
+        assert!(parse_hex_key("xyz").is_err());
+    }
+
+    #[test]
+    fn test_key_sources() {
+        let path = env::temp_dir().join(format!("xortool-key-{}", std::process::id()));
+        fs::write(&path, "0x1a2b\n").unwrap();
+        assert_eq!(KeyFile(path.clone()).load().unwrap(), [0x1a, 0x2b]);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(KeyFile(path).load(), Err(JustError::Io { .. })));
+
+        let var = format!("XORTOOL_TEST_KEY_{}", std::process::id());
+        assert!(matches!(
+            KeyEnv(var.clone()).load(),
+            Err(JustError::InvalidKey(_))
+        ));
+        env::set_var(&var, "0x5ecre7");
+        let error = KeyEnv(var.clone()).load().unwrap_err().to_string();
+        assert!(!error.contains("5ecre7"), "{}", error);
+        assert!(error.contains("character 6 of 8"), "{}", error);
+        env::set_var(&var, "ff");
+        assert_eq!(KeyEnv(var.clone()).load().unwrap(), [0xff]);
+        env::remove_var(&var);
+
+        assert_eq!(HexKey("0a".into()).load().unwrap(), [0x0a]);
+    }
+}
@@ -0,0 +1,141 @@
+pub mod cancel;
+pub mod cipher;
+#[cfg(feature = "cli")]
+pub mod cli;
+pub mod core;
+pub mod error;
+pub mod ffi;
+#[cfg(feature = "native")]
+pub mod header;
+/// Internals the fuzz targets in `fuzz/` drive directly.
+#[cfg(all(fuzzing, feature = "cli"))]
+#[doc(hidden)]
+pub mod fuzz {
+    pub use crate::frame::{read_framed, write_framed};
+}
+#[cfg(feature = "native")]
+pub mod job;
+pub mod key;
+#[cfg(feature = "native")]
+pub mod options;
+#[cfg(feature = "native")]
+pub mod progress;
+#[cfg(feature = "native")]
+pub mod schema;
+#[cfg(feature = "native")]
+pub mod spec;
+#[cfg(feature = "native")]
+pub mod storage;
+pub mod transform;
+#[cfg(feature = "native")]
+pub mod walk;
+
+#[cfg(feature = "cli")]
+mod affinity;
+#[cfg(feature = "cli")]
+mod audit;
+#[cfg(feature = "cli")]
+mod autotune;
+#[cfg(feature = "cli")]
+mod cache;
+#[cfg(feature = "cli")]
+mod changed;
+#[cfg(feature = "cli")]
+mod checkpoint;
+#[cfg(feature = "cli")]
+mod checksum;
+#[cfg(feature = "cli")]
+mod color;
+#[cfg(feature = "native")]
+// Parts of it only the CLI uses.
+#[cfg_attr(not(feature = "cli"), allow(dead_code))]
+mod config;
+#[cfg(feature = "cli")]
+mod container;
+#[cfg(feature = "cli")]
+mod dedup;
+#[cfg(feature = "cli")]
+mod direct;
+#[cfg(feature = "cli")]
+mod encrypted;
+#[cfg(feature = "cli")]
+mod estimate;
+#[cfg(feature = "native")]
+// Parts of it only the CLI uses.
+#[cfg_attr(not(feature = "cli"), allow(dead_code))]
+mod failure;
+#[cfg(feature = "cli")]
+mod frame;
+#[cfg(feature = "native")]
+mod glob;
+#[cfg(feature = "cli")]
+mod heartbeat;
+#[cfg(feature = "cli")]
+mod histogram;
+#[cfg(feature = "cli")]
+mod humanize;
+#[cfg(feature = "cli")]
+mod i18n;
+#[cfg(feature = "native")]
+mod json;
+#[cfg(feature = "cli")]
+mod lifecycle;
+#[cfg(feature = "cli")]
+mod lock;
+#[cfg(feature = "native")]
+// Parts of it only the CLI uses.
+#[cfg_attr(not(feature = "cli"), allow(dead_code))]
+mod longpath;
+#[cfg(feature = "cli")]
+mod manifest;
+#[cfg(feature = "cli")]
+mod mmap;
+#[cfg(feature = "cli")]
+mod monitor;
+#[cfg(feature = "cli")]
+mod namelen;
+#[cfg(feature = "cli")]
+mod normalize;
+#[cfg(feature = "cli")]
+mod notify;
+#[cfg(feature = "cli")]
+mod order;
+#[cfg(feature = "cli")]
+mod pause;
+#[cfg(feature = "cli")]
+mod pipeline;
+#[cfg(feature = "cli")]
+mod pool;
+#[cfg(feature = "cli")]
+mod priority;
+#[cfg(feature = "cli")]
+mod resize;
+#[cfg(feature = "cli")]
+mod selftest;
+mod simd;
+#[cfg(feature = "cli")]
+mod space;
+#[cfg(feature = "cli")]
+mod sparse;
+#[cfg(feature = "cli")]
+mod special;
+#[cfg(feature = "cli")]
+mod stats;
+#[cfg(feature = "cli")]
+mod tar;
+#[cfg(feature = "cli")]
+mod taskbar;
+#[cfg(feature = "cli")]
+mod theme;
+#[cfg(feature = "cli")]
+mod throttle;
+#[cfg(feature = "cli")]
+mod trace;
+#[cfg(feature = "cli")]
+mod tui;
+#[cfg(all(feature = "cli", target_os = "linux"))]
+mod uring;
+#[cfg(any(target_family = "wasm", test))]
+mod wasm;
+#[cfg(feature = "cli")]
+mod width;
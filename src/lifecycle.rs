@@ -0,0 +1,202 @@
+use clap::ValueEnum;
+use std::{
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::{
+    job::JobBuilder,
+    progress::{FileResult, RunResult},
+};
+
+/// When a `--hook` command runs.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    /// Before the first file
+    RunStart,
+    /// Before each file
+    FileStart,
+    /// After each file whose output is complete
+    FileDone,
+    /// After each file that failed
+    FileFailed,
+    /// After the run, whether or not it succeeded
+    RunDone,
+}
+
+/// A command to run through the shell at `event`, from `--hook
+/// EVENT=COMMAND`.
+#[derive(Clone, Debug)]
+pub struct Hook {
+    pub event: Event,
+    pub command: String,
+}
+
+/// Parses `--hook EVENT=COMMAND`.
+pub fn parse(text: &str) -> Result<Hook, String> {
+    let Some((event, command)) = text.split_once('=') else {
+        return Err("expected EVENT=COMMAND".into());
+    };
+    let event = Event::from_str(event.trim(), true).map_err(|_| {
+        let names: Vec<_> = Event::value_variants()
+            .iter()
+            .filter_map(|event| event.to_possible_value())
+            .map(|value| value.get_name().to_string())
+            .collect();
+        format!("unknown event {:?} (expected {})", event, names.join(", "))
+    })?;
+    if command.trim().is_empty() {
+        return Err("empty command".into());
+    }
+    Ok(Hook {
+        event,
+        command: command.to_string(),
+    })
+}
+
+/// Registers `hooks` on `builder` as job callbacks, so each command runs
+/// wherever the run reports its event. Commands get the details in
+/// `XORTOOL_*` environment variables.
+pub fn install(mut builder: JobBuilder, hooks: &[Hook]) -> JobBuilder {
+    let commands = |event| -> Vec<String> {
+        hooks
+            .iter()
+            .filter(|hook| hook.event == event)
+            .map(|hook| hook.command.clone())
+            .collect()
+    };
+
+    let run_start = commands(Event::RunStart);
+    if !run_start.is_empty() {
+        builder = builder.on_run_start(move |input| {
+            run_all(
+                &run_start,
+                Event::RunStart,
+                &[("XORTOOL_INPUT", path(input))],
+            );
+        });
+    }
+    let file_start = commands(Event::FileStart);
+    if !file_start.is_empty() {
+        builder = builder.on_file_start(move |start| {
+            run_all(
+                &file_start,
+                Event::FileStart,
+                &[
+                    ("XORTOOL_INPUT", path(start.input)),
+                    ("XORTOOL_OUTPUT", path(start.output)),
+                ],
+            );
+        });
+    }
+    let (file_done, file_failed) = (commands(Event::FileDone), commands(Event::FileFailed));
+    if !file_done.is_empty() || !file_failed.is_empty() {
+        builder = builder.on_file_complete(move |result| match result.error {
+            None => run_all(&file_done, Event::FileDone, &file_vars(result)),
+            Some(_) => run_all(&file_failed, Event::FileFailed, &file_vars(result)),
+        });
+    }
+    let run_done = commands(Event::RunDone);
+    if !run_done.is_empty() {
+        builder = builder.on_run_complete(move |result| {
+            run_all(&run_done, Event::RunDone, &run_vars(result));
+        });
+    }
+    builder
+}
+
+fn file_vars(result: &FileResult) -> Vec<(&'static str, String)> {
+    let mut vars = vec![
+        ("XORTOOL_INPUT", path(result.input)),
+        ("XORTOOL_OUTPUT", path(result.output)),
+        ("XORTOOL_BYTES", result.bytes.to_string()),
+    ];
+    if let Some(error) = result.error {
+        vars.push(("XORTOOL_ERROR", error.to_string()));
+    }
+    vars
+}
+
+fn run_vars(result: &RunResult) -> Vec<(&'static str, String)> {
+    let mut vars = vec![
+        ("XORTOOL_INPUT", path(result.input)),
+        ("XORTOOL_FILES", result.files.to_string()),
+        ("XORTOOL_FAILED", result.failed.to_string()),
+        ("XORTOOL_BYTES", result.bytes.to_string()),
+        (
+            "XORTOOL_STATUS",
+            (if result.error.is_some() {
+                "failed"
+            } else {
+                "ok"
+            })
+            .to_string(),
+        ),
+    ];
+    if let Some(error) = result.error {
+        vars.push(("XORTOOL_ERROR", error.to_string()));
+    }
+    vars
+}
+
+fn path(path: &Path) -> String {
+    path.display().to_string()
+}
+
+/// Runs each command in turn and waits for it. A command that can't be
+/// started or fails is reported but doesn't stop the run.
+fn run_all(commands: &[String], event: Event, vars: &[(&str, String)]) {
+    let name = event.to_possible_value().unwrap();
+    for command in commands {
+        let mut shell = shell(command);
+        shell
+            .env("XORTOOL_EVENT", name.get_name())
+            .envs(vars.iter().map(|(key, value)| (key, value)))
+            .stdin(Stdio::null());
+        match shell.status() {
+            Ok(status) if status.success() => {}
+            Ok(status) => eprintln!(
+                "Warning: {} hook {:?} exited with {}",
+                name.get_name(),
+                command,
+                status
+            ),
+            Err(e) => eprintln!(
+                "Warning: failed to run {} hook {:?}: {}",
+                name.get_name(),
+                command,
+                e
+            ),
+        }
+    }
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+#[cfg(not(windows))]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hook() {
+        let hook = parse("file-done=upload \"$XORTOOL_OUTPUT\"").unwrap();
+        assert_eq!(hook.event, Event::FileDone);
+        assert_eq!(hook.command, "upload \"$XORTOOL_OUTPUT\"");
+        assert_eq!(parse("RUN-DONE=a=b").unwrap().command, "a=b");
+        assert!(parse("file-done").is_err());
+        assert!(parse("later=true").is_err());
+        assert!(parse("run-start= ").is_err());
+    }
+}
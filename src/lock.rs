@@ -0,0 +1,79 @@
+use anyhow::{bail, Context, Result};
+use std::{
+    fs::{self, File, OpenOptions, TryLockError},
+    path::Path,
+};
+
+use crate::walk::LOCK_FILE_NAME;
+
+/// Name of the lock file in the output directory. Walks skip it, so it
+/// isn't taken for an input when the outputs are decrypted.
+pub const FILE_NAME: &str = LOCK_FILE_NAME;
+
+/// What to do when another run holds the lock, from `--lock`.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum LockMode {
+    /// Wait for the other run to finish
+    Wait,
+    /// Stop with an error
+    #[default]
+    Fail,
+    /// Don't take the lock
+    None,
+}
+
+/// Takes an advisory lock on the run writing to the output directory
+/// `dir`, held until the returned file is dropped. The lock file is left in place: removing it could let
+/// a run that just opened it lock a file nobody else sees.
+pub fn acquire(dir: &Path, mode: LockMode) -> Result<Option<File>> {
+    if mode == LockMode::None {
+        return Ok(None);
+    }
+
+    fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create directory: {}", dir.display()))?;
+    let path = dir.join(FILE_NAME);
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open lock file: {}", path.display()))?;
+
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) if mode == LockMode::Wait => {
+            eprintln!("Waiting for another run on {} to finish", dir.display());
+            file.lock()
+                .with_context(|| format!("Failed to lock {}", path.display()))?;
+        }
+        Err(TryLockError::WouldBlock) => bail!(
+            "Another run is writing to {} (use --lock wait to wait for it)",
+            dir.display()
+        ),
+        Err(TryLockError::Error(e)) => {
+            return Err(e).with_context(|| format!("Failed to lock {}", path.display()))
+        }
+    }
+    Ok(Some(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_second_lock_fails() {
+        let root = std::env::temp_dir().join(format!("xortool-lock-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+
+        let held = acquire(&root, LockMode::Fail).unwrap();
+        assert!(held.is_some());
+        assert!(acquire(&root, LockMode::Fail).is_err());
+        assert!(acquire(&root, LockMode::None).unwrap().is_none());
+        drop(held);
+        assert!(acquire(&root, LockMode::Fail).is_ok());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
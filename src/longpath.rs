@@ -0,0 +1,95 @@
+use std::{io, path::Path, path::PathBuf};
+
+/// `path` in the form every later open and create should start from. On
+/// Windows that is absolute and extended-length (`\\?\C:\...`), which
+/// lifts the 260-character `MAX_PATH` limit for it and every path joined
+/// onto it; elsewhere `path` is returned as is.
+pub fn extended(path: &Path) -> io::Result<PathBuf> {
+    imp::extended(path)
+}
+
+/// `path` without an extended-length prefix, for showing to people.
+pub fn display_form(path: &Path) -> PathBuf {
+    imp::display_form(path)
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::{
+        ffi::OsString,
+        io,
+        os::windows::ffi::{OsStrExt, OsStringExt},
+        path::{self, Component, Path, PathBuf, Prefix},
+    };
+
+    const VERBATIM: &str = r"\\?\";
+    const VERBATIM_UNC: &str = r"\\?\UNC\";
+
+    pub fn extended(path: &Path) -> io::Result<PathBuf> {
+        // Resolves `.`, `..` and forward slashes, which extended-length
+        // paths take literally.
+        let absolute = path::absolute(path)?;
+        let Some(Component::Prefix(prefix)) = absolute.components().next() else {
+            return Ok(absolute);
+        };
+        Ok(match prefix.kind() {
+            Prefix::Disk(_) => join_wide(VERBATIM, &absolute, 0),
+            // `\\server\share\...` becomes `\\?\UNC\server\share\...`.
+            Prefix::UNC(..) => join_wide(VERBATIM_UNC, &absolute, 2),
+            _ => absolute,
+        })
+    }
+
+    pub fn display_form(path: &Path) -> PathBuf {
+        let Some(Component::Prefix(prefix)) = path.components().next() else {
+            return path.to_path_buf();
+        };
+        match prefix.kind() {
+            Prefix::VerbatimDisk(_) => join_wide("", path, VERBATIM.len()),
+            Prefix::VerbatimUNC(..) => join_wide(r"\\", path, VERBATIM_UNC.len()),
+            _ => path.to_path_buf(),
+        }
+    }
+
+    /// `prefix` followed by `path` less its first `skip` UTF-16 units.
+    fn join_wide(prefix: &str, path: &Path, skip: usize) -> PathBuf {
+        let wide: Vec<u16> = prefix
+            .encode_utf16()
+            .chain(path.as_os_str().encode_wide().skip(skip))
+            .collect();
+        PathBuf::from(OsString::from_wide(&wide))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_extended_round_trip() {
+            let disk = extended(Path::new(r"C:\data\..\logs/app.log")).unwrap();
+            assert_eq!(disk, Path::new(r"\\?\C:\logs\app.log"));
+            assert_eq!(display_form(&disk), Path::new(r"C:\logs\app.log"));
+
+            let unc = extended(Path::new(r"\\server\share\dir")).unwrap();
+            assert_eq!(unc, Path::new(r"\\?\UNC\server\share\dir"));
+            assert_eq!(display_form(&unc), Path::new(r"\\server\share\dir"));
+            assert_eq!(extended(&unc).unwrap(), unc);
+        }
+    }
+}
+
+#[cfg(not(windows))]
+mod imp {
+    use std::{
+        io,
+        path::{Path, PathBuf},
+    };
+
+    pub fn extended(path: &Path) -> io::Result<PathBuf> {
+        Ok(path.to_path_buf())
+    }
+
+    pub fn display_form(path: &Path) -> PathBuf {
+        path.to_path_buf()
+    }
+}
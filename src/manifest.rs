@@ -0,0 +1,155 @@
+use anyhow::{Context, Result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    normalize::{self, Form},
+    schema::{Manifest, ManifestCipher, ManifestFile},
+    walk::OUTPUT_DIR,
+};
+
+/// Name of the manifest in the output directory of the input root.
+pub const FILE_NAME: &str = "manifest.json";
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static ENTRIES: Mutex<Vec<Entry>> = Mutex::new(Vec::new());
+
+/// One processed file.
+#[derive(Clone)]
+pub struct Entry {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub size: u64,
+    /// SHA-256 of the input and of the output.
+    pub sha256: String,
+    pub output_sha256: String,
+}
+
+/// Starts collecting an entry for every processed file.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn record(entry: Entry) {
+    if enabled() {
+        ENTRIES.lock().unwrap().push(entry);
+    }
+}
+
+/// Records `duplicate` as having the same contents as `original`, whose
+/// output it was linked to.
+pub fn record_duplicate(original: &Path, duplicate: &Path, output: PathBuf) {
+    let mut entries = ENTRIES.lock().unwrap();
+    let Some(entry) = entries
+        .iter()
+        .find(|entry| entry.input == original)
+        .cloned()
+    else {
+        return;
+    };
+    entries.push(Entry {
+        input: duplicate.to_path_buf(),
+        output,
+        ..entry
+    });
+}
+
+/// Writes the manifest of the run over `root` into its output directory
+/// and returns its path. Paths in it are relative to `root`, with names
+/// in `form`.
+pub fn write(root: &Path, key: &[u8], sparse: bool, form: Form) -> Result<PathBuf> {
+    let mut entries = ENTRIES.lock().unwrap().clone();
+    entries.sort_by(|a, b| a.input.cmp(&b.input));
+
+    let created = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+
+    let manifest = Manifest {
+        created: rfc3339(created),
+        normalization: match form {
+            Form::Nfc => "NFC",
+            Form::Nfd => "NFD",
+        },
+        cipher: ManifestCipher {
+            algorithm: "xor",
+            key_length: key.len(),
+            sparse,
+        },
+        files: entries
+            .iter()
+            .map(|entry| ManifestFile {
+                path: relative(&entry.input, root, form),
+                output: relative(&entry.output, root, form),
+                size: entry.size,
+                output_size: entry.size,
+                sha256: entry.sha256.clone(),
+                output_sha256: entry.output_sha256.clone(),
+            })
+            .collect(),
+    };
+
+    let path = root.join(OUTPUT_DIR).join(FILE_NAME);
+    fs::create_dir_all(root.join(OUTPUT_DIR))?;
+    fs::write(&path, manifest.to_json())
+        .with_context(|| format!("Failed to write manifest: {}", path.display()))?;
+    Ok(path)
+}
+
+fn relative(path: &Path, root: &Path, form: Form) -> String {
+    normalize::path(path.strip_prefix(root).unwrap_or(path), form)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// `seconds` since the Unix epoch as a UTC timestamp such as
+/// `2024-03-01T12:00:00Z`.
+pub fn rfc3339(seconds: u64) -> String {
+    let (days, rest) = ((seconds / 86400) as i64, seconds % 86400);
+
+    // Civil date from days since the epoch, after Howard Hinnant's
+    // days_from_civil inverse.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rest / 3600,
+        rest / 60 % 60,
+        rest % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rfc3339() {
+        assert_eq!(rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(rfc3339(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(rfc3339(1_709_294_461), "2024-03-01T12:01:01Z");
+    }
+}
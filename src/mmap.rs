@@ -0,0 +1,176 @@
+use anyhow::{bail, Result};
+use std::{
+    fmt,
+    fs::File,
+    io::{Seek, SeekFrom, Write},
+};
+
+use crate::cipher::Cipher;
+
+/// Bytes transformed between progress callbacks when working over a mapping.
+const STRIDE: usize = 8 * 1024 * 1024;
+
+/// The error `transform` ends with when the input can't be mapped, before
+/// anything is written, so the caller can stream the file instead.
+#[derive(Debug)]
+pub struct Unmappable(String);
+
+impl fmt::Display for Unmappable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Unmappable {}
+
+/// XOR `input` into `output` through memory mappings instead of the
+/// read/copy/write loop. The output is mapped too when possible; if that
+/// fails the transformed strides are written through the file handle.
+///
+/// Returns `Unmappable` without touching `output` when the input cannot be
+/// mapped, so callers can fall back to the streaming path. Any other error,
+/// including one from `progress`, comes from partway through.
+pub fn transform(
+    input: &File,
+    output: &mut File,
+    len: u64,
+    cipher: &mut dyn Cipher,
+    mut progress: impl FnMut(u64) -> Result<()>,
+) -> Result<()> {
+    let Ok(len) = usize::try_from(len) else {
+        bail!(Unmappable("File too large to map on this platform".into()));
+    };
+    if len == 0 {
+        bail!(Unmappable("Cannot map an empty file".into()));
+    }
+
+    let source =
+        imp::Mapping::map(input, len, false).map_err(|e| Unmappable(format!("{:#}", e)))?;
+
+    output.set_len(len as u64)?;
+    match imp::Mapping::map(output, len, true) {
+        Ok(mut mapping) => {
+            let target = mapping.as_mut_slice();
+            for start in (0..len).step_by(STRIDE) {
+                let end = (start + STRIDE).min(len);
+                target[start..end].copy_from_slice(&source.as_slice()[start..end]);
+                cipher.apply(start as u64, &mut target[start..end]);
+                progress(end as u64)?;
+            }
+            mapping.flush()?;
+        }
+        Err(_) => {
+            output.set_len(0)?;
+            output.seek(SeekFrom::Start(0))?;
+            let mut buffer = vec![0u8; STRIDE.min(len)];
+            for start in (0..len).step_by(STRIDE) {
+                let end = (start + STRIDE).min(len);
+                let chunk = &mut buffer[..end - start];
+                chunk.copy_from_slice(&source.as_slice()[start..end]);
+                cipher.apply(start as u64, chunk);
+                output.write_all(chunk)?;
+                progress(end as u64)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+mod imp {
+    use anyhow::{bail, Result};
+    use std::{fs::File, io, os::unix::io::AsRawFd, ptr, slice};
+
+    pub struct Mapping {
+        ptr: *mut libc::c_void,
+        len: usize,
+        writable: bool,
+    }
+
+    impl Mapping {
+        pub fn map(file: &File, len: usize, writable: bool) -> Result<Self> {
+            let prot = if writable {
+                libc::PROT_READ | libc::PROT_WRITE
+            } else {
+                libc::PROT_READ
+            };
+            let flags = if writable {
+                libc::MAP_SHARED
+            } else {
+                libc::MAP_PRIVATE
+            };
+
+            // SAFETY: we pass a valid descriptor and let the kernel pick the
+            // address; the result is checked against MAP_FAILED below.
+            let ptr = unsafe { libc::mmap(ptr::null_mut(), len, prot, flags, file.as_raw_fd(), 0) };
+            if ptr == libc::MAP_FAILED {
+                bail!("mmap failed: {}", io::Error::last_os_error());
+            }
+
+            // SAFETY: the region was just mapped with `len` bytes.
+            unsafe {
+                libc::madvise(ptr, len, libc::MADV_SEQUENTIAL);
+            }
+
+            Ok(Self { ptr, len, writable })
+        }
+
+        pub fn as_slice(&self) -> &[u8] {
+            // SAFETY: the mapping is valid for `len` bytes until drop.
+            unsafe { slice::from_raw_parts(self.ptr as *const u8, self.len) }
+        }
+
+        pub fn as_mut_slice(&mut self) -> &mut [u8] {
+            debug_assert!(self.writable);
+            // SAFETY: the mapping is writable and valid for `len` bytes
+            // until drop, and `&mut self` keeps other views out.
+            unsafe { slice::from_raw_parts_mut(self.ptr as *mut u8, self.len) }
+        }
+
+        /// Writes the mapping's dirty pages back to the file.
+        pub fn flush(&self) -> Result<()> {
+            // SAFETY: `ptr`/`len` describe a live mapping owned by `self`.
+            let rc = unsafe { libc::msync(self.ptr, self.len, libc::MS_SYNC) };
+            if rc != 0 {
+                bail!("msync failed: {}", io::Error::last_os_error());
+            }
+            Ok(())
+        }
+    }
+
+    impl Drop for Mapping {
+        fn drop(&mut self) {
+            // SAFETY: `ptr`/`len` describe a live mapping owned by `self`.
+            unsafe {
+                libc::munmap(self.ptr, self.len);
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use anyhow::{bail, Result};
+    use std::fs::File;
+
+    pub struct Mapping;
+
+    impl Mapping {
+        pub fn map(_file: &File, _len: usize, _writable: bool) -> Result<Self> {
+            bail!("Memory-mapped I/O is not supported on this platform")
+        }
+
+        pub fn as_slice(&self) -> &[u8] {
+            &[]
+        }
+
+        pub fn as_mut_slice(&mut self) -> &mut [u8] {
+            &mut []
+        }
+
+        pub fn flush(&self) -> Result<()> {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use anyhow::Result;
+use std::{path::Path, sync::OnceLock};
+
+pub use crate::schema::Event;
+use crate::tui;
+
+static MONITOR: OnceLock<imp::Monitor> = OnceLock::new();
+
+/// Listens on `path` for monitors and starts sending them events.
+pub fn serve(path: &Path) -> Result<()> {
+    let monitor = imp::Monitor::bind(path)?;
+    let _ = MONITOR.set(monitor);
+    Ok(())
+}
+
+/// Sends `event` to the dashboard and every connected monitor. Never
+/// blocks on a slow monitor.
+pub fn emit(event: Event) {
+    tui::observe(&event);
+    if let Some(monitor) = MONITOR.get() {
+        monitor.broadcast(&(event.to_json() + "\n"));
+    }
+}
+
+/// Stops listening and removes the socket.
+pub fn close() {
+    if let Some(monitor) = MONITOR.get() {
+        monitor.close();
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use anyhow::{Context, Result};
+    use std::{
+        fs,
+        io::{self, Write},
+        os::unix::{
+            fs::FileTypeExt,
+            net::{UnixListener, UnixStream},
+        },
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    pub struct Monitor {
+        path: PathBuf,
+        clients: Arc<Mutex<Vec<UnixStream>>>,
+    }
+
+    impl Monitor {
+        pub fn bind(path: &Path) -> Result<Self> {
+            // A socket left behind by an earlier run would make bind fail.
+            if fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket()) {
+                let _ = fs::remove_file(path);
+            }
+            let listener = UnixListener::bind(path).with_context(|| {
+                format!("Failed to listen on progress socket: {}", path.display())
+            })?;
+
+            let clients = Arc::new(Mutex::new(Vec::new()));
+            let accepted = Arc::clone(&clients);
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    if stream.set_nonblocking(true).is_ok() {
+                        accepted.lock().unwrap().push(stream);
+                    }
+                }
+            });
+
+            Ok(Self {
+                path: path.to_path_buf(),
+                clients,
+            })
+        }
+
+        /// Writes `line` to each client, skipping clients that aren't
+        /// keeping up and dropping ones that went away.
+        pub fn broadcast(&self, line: &str) {
+            self.clients
+                .lock()
+                .unwrap()
+                .retain_mut(|client| match client.write(line.as_bytes()) {
+                    Ok(written) => written == line.len(),
+                    Err(e) => e.kind() == io::ErrorKind::WouldBlock,
+                });
+        }
+
+        pub fn close(&self) {
+            self.clients.lock().unwrap().clear();
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use anyhow::{bail, Result};
+    use std::path::Path;
+
+    pub struct Monitor;
+
+    impl Monitor {
+        pub fn bind(_path: &Path) -> Result<Self> {
+            bail!("--progress-socket is only supported on Unix")
+        }
+
+        pub fn broadcast(&self, _line: &str) {}
+
+        pub fn close(&self) {}
+    }
+}
@@ -0,0 +1,90 @@
+use std::path::{Path, PathBuf};
+
+use crate::checksum::Sha256;
+
+/// Longest file name most filesystems take, in bytes, for when the
+/// filesystem can't be asked.
+const DEFAULT_NAME_MAX: usize = 255;
+
+/// Hex digits of the original name's digest kept in a shortened name, so
+/// names that share a long prefix stay apart.
+const DIGEST_LEN: usize = 16;
+
+/// `path` with `.extension` appended to its file name. If that makes the
+/// name too long for the filesystem, the original name is cut short and
+/// followed by `~` and a digest of it, so the result still fits, is
+/// still unique, and is the same every time for the same `path`.
+pub fn with_extension_added(path: &Path, extension: &str) -> PathBuf {
+    let original = path.file_name().unwrap_or_default();
+    let mut name = original.to_owned();
+    name.push(".");
+    name.push(extension);
+    let limit = name_max(path.parent().unwrap_or(Path::new(".")));
+    if name.len() <= limit {
+        return path.with_file_name(name);
+    }
+
+    let mut hasher = Sha256::new();
+    hasher.update(original.as_encoded_bytes());
+    let digest = hex::encode(hasher.finalize());
+    let tail = format!("~{}.{}", &digest[..DIGEST_LEN], extension);
+    let lossy = original.to_string_lossy();
+    let mut end = limit.saturating_sub(tail.len()).min(lossy.len());
+    while !lossy.is_char_boundary(end) {
+        end -= 1;
+    }
+    path.with_file_name(format!("{}{}", &lossy[..end], tail))
+}
+
+/// Longest file name the filesystem holding `dir` takes, in bytes.
+fn name_max(dir: &Path) -> usize {
+    imp::name_max(dir).unwrap_or(DEFAULT_NAME_MAX)
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt, path::Path};
+
+    pub fn name_max(dir: &Path) -> Option<usize> {
+        let dir = CString::new(dir.as_os_str().as_bytes()).ok()?;
+        // SAFETY: `dir` is NUL-terminated; pathconf only reads it.
+        let max = unsafe { libc::pathconf(dir.as_ptr(), libc::_PC_NAME_MAX) };
+        usize::try_from(max).ok().filter(|&max| max > 0)
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use std::path::Path;
+
+    // Windows limits names to 255 UTF-16 units, which is never more than
+    // the same name's UTF-8 bytes, so the default is safe there.
+    pub fn name_max(_dir: &Path) -> Option<usize> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_extension_added() {
+        let dir = std::env::temp_dir();
+        assert_eq!(
+            with_extension_added(&dir.join("a.txt"), "sha256"),
+            dir.join("a.txt.sha256")
+        );
+
+        let limit = name_max(&dir);
+        let long_a = dir.join(format!("{}a", "é".repeat(limit / 2)));
+        let long_b = dir.join(format!("{}b", "é".repeat(limit / 2)));
+        let short_a = with_extension_added(&long_a, "partial");
+        let short_b = with_extension_added(&long_b, "partial");
+        assert!(short_a.file_name().unwrap().len() <= limit);
+        assert_eq!(short_a.extension().unwrap(), "partial");
+        assert_eq!(short_a.parent(), Some(dir.as_path()));
+        assert_ne!(short_a, short_b);
+        assert_eq!(with_extension_added(&long_a, "partial"), short_a);
+    }
+}
@@ -0,0 +1,218 @@
+use std::{
+    borrow::Cow,
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
+
+mod tables;
+
+use tables::{COMBINING_CLASSES, COMPOSITIONS, DECOMPOSITIONS};
+
+/// Unicode normalization form for names written to manifests and
+/// checksum files, from `--normalize`. macOS tends to hand out names in
+/// NFD and most other systems in NFC, so the same name can arrive as
+/// different bytes.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Form {
+    /// Composed, as on Linux and Windows
+    #[default]
+    Nfc,
+    /// Decomposed, as on macOS
+    Nfd,
+}
+
+// Hangul syllables compose and decompose arithmetically.
+const S_BASE: u32 = 0xAC00;
+const L_BASE: u32 = 0x1100;
+const V_BASE: u32 = 0x1161;
+const T_BASE: u32 = 0x11A7;
+const L_COUNT: u32 = 19;
+const V_COUNT: u32 = 21;
+const T_COUNT: u32 = 28;
+const N_COUNT: u32 = V_COUNT * T_COUNT;
+const S_COUNT: u32 = L_COUNT * N_COUNT;
+
+/// `text` in `form`, borrowed when it already is.
+pub fn normalize(text: &str, form: Form) -> Cow<'_, str> {
+    // Nothing below U+00C0 decomposes or combines.
+    if text.chars().all(|c| c < '\u{C0}') {
+        return Cow::Borrowed(text);
+    }
+    let decomposed = decompose(text);
+    let chars = match form {
+        Form::Nfd => decomposed,
+        Form::Nfc => compose(decomposed),
+    };
+    let normalized: String = chars.into_iter().collect();
+    match normalized == text {
+        true => Cow::Borrowed(text),
+        false => Cow::Owned(normalized),
+    }
+}
+
+/// The path with each of its names in `form`. Names that aren't valid
+/// UTF-8 are left alone.
+pub fn path(path: &Path, form: Form) -> PathBuf {
+    path.iter()
+        .map(|name| match name.to_str() {
+            Some(name) => normalize(name, form).into_owned().into(),
+            None => name.to_os_string(),
+        })
+        .collect()
+}
+
+/// Whether two names are the same text, whatever form each is in.
+pub fn same_name(a: &OsStr, b: &OsStr) -> bool {
+    match (a.to_str(), b.to_str()) {
+        (Some(a), Some(b)) => a == b || normalize(a, Form::Nfc) == normalize(b, Form::Nfc),
+        _ => a == b,
+    }
+}
+
+/// Full canonical decomposition, with combining marks in canonical order.
+fn decompose(text: &str) -> Vec<char> {
+    let mut chars = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        decompose_char(c as u32, &mut chars);
+    }
+
+    // Stable sort of each run of combining marks by class.
+    let mut i = 1;
+    while i < chars.len() {
+        let class = combining_class(chars[i]);
+        let mut j = i;
+        while class != 0 && j > 0 && combining_class(chars[j - 1]) > class {
+            chars.swap(j - 1, j);
+            j -= 1;
+        }
+        i += 1;
+    }
+    chars
+}
+
+fn decompose_char(c: u32, out: &mut Vec<char>) {
+    if (S_BASE..S_BASE + S_COUNT).contains(&c) {
+        let index = c - S_BASE;
+        out.push(char_at(L_BASE + index / N_COUNT));
+        out.push(char_at(V_BASE + index % N_COUNT / T_COUNT));
+        if !index.is_multiple_of(T_COUNT) {
+            out.push(char_at(T_BASE + index % T_COUNT));
+        }
+        return;
+    }
+    match DECOMPOSITIONS.binary_search_by_key(&c, |&(from, _, _)| from) {
+        Ok(found) => {
+            let (_, first, second) = DECOMPOSITIONS[found];
+            decompose_char(first, out);
+            if second != 0 {
+                decompose_char(second, out);
+            }
+        }
+        Err(_) => out.push(char_at(c)),
+    }
+}
+
+/// Canonical composition of decomposed text.
+fn compose(chars: Vec<char>) -> Vec<char> {
+    let mut out: Vec<char> = Vec::with_capacity(chars.len());
+    let mut starter = None;
+    // Class of the last character since the starter, if there is one.
+    let mut last_class = None;
+    for c in chars {
+        let class = combining_class(c);
+        if let Some(at) = starter {
+            let blocked = last_class.is_some_and(|last| last == 0 || last >= class);
+            if !blocked {
+                if let Some(composite) = compose_pair(out[at], c) {
+                    out[at] = composite;
+                    continue;
+                }
+            }
+        }
+        if class == 0 {
+            starter = Some(out.len());
+            last_class = None;
+        } else {
+            last_class = Some(class);
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn compose_pair(first: char, second: char) -> Option<char> {
+    let (first, second) = (first as u32, second as u32);
+    if (L_BASE..L_BASE + L_COUNT).contains(&first) && (V_BASE..V_BASE + V_COUNT).contains(&second) {
+        let index = (first - L_BASE) * N_COUNT + (second - V_BASE) * T_COUNT;
+        return Some(char_at(S_BASE + index));
+    }
+    if (S_BASE..S_BASE + S_COUNT).contains(&first)
+        && (first - S_BASE).is_multiple_of(T_COUNT)
+        && (T_BASE + 1..T_BASE + T_COUNT).contains(&second)
+    {
+        return Some(char_at(first + second - T_BASE));
+    }
+    COMPOSITIONS
+        .binary_search_by_key(&(first, second), |&(a, b, _)| (a, b))
+        .ok()
+        .map(|found| char_at(COMPOSITIONS[found].2))
+}
+
+fn combining_class(c: char) -> u8 {
+    let c = c as u32;
+    COMBINING_CLASSES
+        .binary_search_by(|&(start, end, _)| {
+            if end < c {
+                std::cmp::Ordering::Less
+            } else if start > c {
+                std::cmp::Ordering::Greater
+            } else {
+                std::cmp::Ordering::Equal
+            }
+        })
+        .map_or(0, |found| COMBINING_CLASSES[found].2)
+}
+
+/// The tables only hold scalar values.
+fn char_at(c: u32) -> char {
+    char::from_u32(c).unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let cases = [
+            ("Ångström", "\u{C5}ngstr\u{F6}m", "A\u{30A}ngstro\u{308}m"),
+            ("\u{212B}", "\u{C5}", "A\u{30A}"),
+            ("d\u{307}\u{323}", "\u{1E0D}\u{307}", "d\u{323}\u{307}"),
+            ("q\u{307}\u{323}", "q\u{323}\u{307}", "q\u{323}\u{307}"),
+            ("각", "\u{AC01}", "\u{1100}\u{1161}\u{11A8}"),
+            ("が", "\u{304C}", "\u{304B}\u{3099}"),
+        ];
+        for (text, nfc, nfd) in cases {
+            assert_eq!(normalize(text, Form::Nfc), nfc, "NFC of {:?}", text);
+            assert_eq!(normalize(text, Form::Nfd), nfd, "NFD of {:?}", text);
+            assert_eq!(normalize(nfd, Form::Nfc), nfc, "NFC of {:?}", nfd);
+        }
+        assert!(matches!(
+            normalize("plain.txt", Form::Nfd),
+            Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn test_same_name() {
+        assert!(same_name(
+            OsStr::new("caf\u{E9}"),
+            OsStr::new("cafe\u{301}")
+        ));
+        assert!(!same_name(OsStr::new("cafe"), OsStr::new("caf\u{E9}")));
+        assert_eq!(
+            path(Path::new("re\u{301}sume\u{301}/a"), Form::Nfc),
+            Path::new("r\u{E9}sum\u{E9}/a")
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use anyhow::{bail, Context, Result};
+use std::process::{Command, Stdio};
+
+/// Shows a desktop notification through the platform's own tool:
+/// `notify-send` on Linux and the BSDs, `osascript` on macOS and a
+/// PowerShell toast on Windows.
+pub fn send(title: &str, body: &str) -> Result<()> {
+    let mut command = command(title, body);
+    let status = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .with_context(|| format!("Failed to run {:?}", command.get_program()))?;
+    if !status.success() {
+        bail!("{:?} exited with {}", command.get_program(), status);
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn command(title: &str, body: &str) -> Command {
+    let mut command = Command::new("osascript");
+    command.arg("-e").arg(format!(
+        "display notification {} with title {}",
+        applescript_string(body),
+        applescript_string(title)
+    ));
+    command
+}
+
+#[cfg(windows)]
+fn command(title: &str, body: &str) -> Command {
+    // Toasts need a registered app ID; borrow PowerShell's.
+    let script = format!(
+        "[Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime] > $null;\
+         $xml = [Windows.UI.Notifications.ToastNotificationManager]::GetTemplateContent([Windows.UI.Notifications.ToastTemplateType]::ToastText02);\
+         $text = $xml.GetElementsByTagName('text');\
+         $text.Item(0).AppendChild($xml.CreateTextNode({})) > $null;\
+         $text.Item(1).AppendChild($xml.CreateTextNode({})) > $null;\
+         $toast = [Windows.UI.Notifications.ToastNotification]::new($xml);\
+         [Windows.UI.Notifications.ToastNotificationManager]::CreateToastNotifier('{{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}}\\WindowsPowerShell\\v1.0\\powershell.exe').Show($toast)",
+        powershell_string(title),
+        powershell_string(body)
+    );
+    let mut command = Command::new("powershell");
+    command.args(["-NoProfile", "-NonInteractive", "-Command", &script]);
+    command
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn command(title: &str, body: &str) -> Command {
+    let mut command = Command::new("notify-send");
+    command.args(["--app-name", "xortool", title, body]);
+    command
+}
+
+#[cfg(any(target_os = "macos", test))]
+fn applescript_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(any(windows, test))]
+fn powershell_string(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quoting() {
+        assert_eq!(applescript_string(r#"a "b" \c"#), r#""a \"b\" \\c""#);
+        assert_eq!(powershell_string("it's"), "'it''s'");
+    }
+}
@@ -0,0 +1,87 @@
+use std::path::PathBuf;
+
+use crate::{config::Config, error::JustError, transform::DEFAULT_BUFFER_SIZE};
+
+/// How a run walks and transforms its input, apart from the input and
+/// key themselves. The CLI builds one from its flags, job files keep one
+/// at the top level, and library callers can fill one in and hand it to
+/// `JobBuilder::options`:
+///
+/// ```
+/// use xortool::options::ProcessOptions;
+///
+/// let options = ProcessOptions {
+///     recursive: true,
+///     exclude: vec!["*.tmp".into()],
+///     ..ProcessOptions::default()
+/// };
+/// assert_eq!(options.buffer_size, 64 * 1024);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProcessOptions {
+    /// Whether to enter subdirectories of a directory input.
+    pub recursive: bool,
+    /// Where to put the outputs instead of `xor/` next to each input.
+    pub output_dir: Option<PathBuf>,
+    /// Shell-style patterns of files and directories to leave out.
+    pub exclude: Vec<String>,
+    pub buffer_size: usize,
+    /// Whether a key that leaves data unchanged is allowed.
+    pub force: bool,
+}
+
+impl Default for ProcessOptions {
+    fn default() -> Self {
+        Self {
+            recursive: false,
+            output_dir: None,
+            exclude: Vec::new(),
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            force: false,
+        }
+    }
+}
+
+impl ProcessOptions {
+    /// Names of the settings `from_config` reads.
+    pub(crate) const KEYS: [&'static str; 5] =
+        ["recursive", "output_dir", "exclude", "buffer_size", "force"];
+
+    /// Reads the options from the top level of `config`, leaving the
+    /// ones it doesn't set at their defaults.
+    pub(crate) fn from_config(config: &Config) -> Result<Self, JustError> {
+        let invalid = |e: anyhow::Error| JustError::InvalidOptions(format!("{:#}", e));
+        let defaults = Self::default();
+        let buffer_size = match config.get_int("buffer_size").map_err(invalid)? {
+            Some(size) => usize::try_from(size)
+                .ok()
+                .filter(|&size| size > 0)
+                .ok_or_else(|| {
+                    JustError::InvalidOptions(format!("`buffer_size` can't be {}", size))
+                })?,
+            None => defaults.buffer_size,
+        };
+        Ok(Self {
+            recursive: config
+                .get_bool("recursive")
+                .map_err(invalid)?
+                .unwrap_or(defaults.recursive),
+            output_dir: config
+                .get_str("output_dir")
+                .map_err(invalid)?
+                .map(PathBuf::from),
+            exclude: config
+                .get_str_list("exclude")
+                .map_err(invalid)?
+                .unwrap_or_default()
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            buffer_size,
+            force: config
+                .get_bool("force")
+                .map_err(invalid)?
+                .unwrap_or(defaults.force),
+        })
+    }
+}
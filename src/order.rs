@@ -0,0 +1,64 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use walkdir::WalkDir;
+
+/// Order files are walked and processed in, from `--sort`.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum SortOrder {
+    /// By name within each directory, byte by byte
+    #[default]
+    Name,
+    /// Smallest first
+    Size,
+    /// Least recently modified first
+    Mtime,
+    /// As the filesystem lists them, which differs between filesystems
+    None,
+}
+
+/// A walk of `root` that lists each directory in name order, which every
+/// order but `None` starts from so that ties come out the same everywhere.
+pub fn walker(root: &Path, order: SortOrder) -> WalkDir {
+    let walker = WalkDir::new(root);
+    match order {
+        SortOrder::None => walker,
+        _ => walker.sort_by_file_name(),
+    }
+}
+
+/// Puts walked `files`, with their sizes, in `order`.
+pub fn sort(files: &mut [(u64, PathBuf)], order: SortOrder) {
+    match order {
+        SortOrder::Name | SortOrder::None => {}
+        SortOrder::Size => files.sort_by_key(|(size, _)| *size),
+        SortOrder::Mtime => files.sort_by_cached_key(|(_, path)| {
+            fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH)
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sort_by_size_is_stable() {
+        let mut files = vec![
+            (3, PathBuf::from("a")),
+            (1, PathBuf::from("b")),
+            (3, PathBuf::from("c")),
+            (2, PathBuf::from("d")),
+        ];
+        sort(&mut files, SortOrder::Size);
+        let names: Vec<_> = files
+            .iter()
+            .map(|(_, path)| path.to_str().unwrap())
+            .collect();
+        assert_eq!(names, ["b", "d", "a", "c"]);
+    }
+}
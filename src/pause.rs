@@ -0,0 +1,254 @@
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{resize, tui};
+
+static PAUSED: AtomicBool = AtomicBool::new(false);
+static SKIPS: AtomicUsize = AtomicUsize::new(0);
+static QUIT: AtomicBool = AtomicBool::new(false);
+static INTERRUPTS: AtomicUsize = AtomicUsize::new(0);
+
+const KEY_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+pub fn is_paused() -> bool {
+    PAUSED.load(Ordering::Relaxed)
+}
+
+pub fn toggle() {
+    PAUSED.fetch_xor(true, Ordering::Relaxed);
+}
+
+/// Asks for the files in flight to be abandoned. Also resumes a paused
+/// run so that they notice.
+pub fn skip() {
+    SKIPS.fetch_add(1, Ordering::Relaxed);
+    PAUSED.store(false, Ordering::Relaxed);
+}
+
+/// Counts `skip` requests; a file started under an older count is skipped.
+pub fn skips() -> usize {
+    SKIPS.load(Ordering::Relaxed)
+}
+
+/// Asks for the run to stop after the current chunk.
+pub fn quit() {
+    QUIT.store(true, Ordering::Relaxed);
+    PAUSED.store(false, Ordering::Relaxed);
+}
+
+pub fn quit_requested() -> bool {
+    QUIT.load(Ordering::Relaxed)
+}
+
+/// Handles Ctrl-C or SIGTERM: the first stops the run after the current
+/// chunk, so that it can clean up; a second one ends it on the spot.
+/// Returns whether this one should end the process.
+pub fn interrupt() -> bool {
+    if INTERRUPTS.fetch_add(1, Ordering::SeqCst) > 0 {
+        return true;
+    }
+    quit();
+    false
+}
+
+/// Interrupts received so far.
+pub fn interrupts() -> usize {
+    INTERRUPTS.load(Ordering::SeqCst)
+}
+
+/// The error a run ends with when it is stopped by the user.
+#[derive(Debug)]
+pub struct Stopped;
+
+impl fmt::Display for Stopped {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Stopped by user")
+    }
+}
+
+impl std::error::Error for Stopped {}
+
+/// The error a file's transform ends with when it is skipped.
+#[derive(Debug)]
+pub struct Skipped;
+
+impl fmt::Display for Skipped {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Skipped by user")
+    }
+}
+
+impl std::error::Error for Skipped {}
+
+/// Toggles pause on SIGUSR1 and, when attached to a terminal, on the `p`
+/// key. On the dashboard, `s` also skips the files in flight and `q` quits. Dropping it stops the key listener and restores the terminal.
+/// Ctrl-C and SIGTERM go through `interrupt`.
+pub struct PauseListener {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+    _input_mode: Option<term::InputMode>,
+}
+
+pub fn listen() -> PauseListener {
+    #[cfg(unix)]
+    // SAFETY: the action only flips an atomic, which is async-signal-safe.
+    let _ = unsafe { signal_hook::low_level::register(signal_hook::consts::SIGUSR1, toggle) };
+    #[cfg(unix)]
+    for signal in [signal_hook::consts::SIGINT, signal_hook::consts::SIGTERM] {
+        // SAFETY: the action updates atomics and, on a second interrupt,
+        // restores the terminal with tcsetattr(3) before dying of the
+        // signal, all of which is async-signal-safe.
+        let _ = unsafe {
+            signal_hook::low_level::register(signal, move || {
+                if interrupt() {
+                    term::restore();
+                    let _ = signal_hook::low_level::emulate_default_handler(signal);
+                }
+            })
+        };
+    }
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let interactive = atty::is(atty::Stream::Stdin) && atty::is(atty::Stream::Stdout);
+    let input_mode = if interactive {
+        term::InputMode::enable().ok()
+    } else {
+        None
+    };
+
+    let thread = input_mode.as_ref().map(|_| {
+        let stop = Arc::clone(&stop);
+        thread::spawn(move || read_keys(&stop))
+    });
+
+    PauseListener {
+        stop,
+        thread,
+        _input_mode: input_mode,
+    }
+}
+
+fn read_keys(stop: &AtomicBool) {
+    while !stop.load(Ordering::Relaxed) {
+        if !event::poll(KEY_POLL_INTERVAL).unwrap_or(false) {
+            continue;
+        }
+        let key = match event::read() {
+            Ok(Event::Key(key)) => key,
+            Ok(Event::Resize(..)) => {
+                resize::notify();
+                continue;
+            }
+            _ => continue,
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        match key.code {
+            KeyCode::Char('p') | KeyCode::Char('P') => toggle(),
+            KeyCode::Char('s') | KeyCode::Char('S') if tui::active() => skip(),
+            KeyCode::Char('q') | KeyCode::Char('Q') if tui::active() => quit(),
+            #[cfg(windows)]
+            KeyCode::Char('c')
+                if key
+                    .modifiers
+                    .contains(crossterm::event::KeyModifiers::CONTROL) =>
+            {
+                // Raw mode swallows Ctrl-C on Windows, so honor it here.
+                if interrupt() {
+                    let _ = crossterm::terminal::disable_raw_mode();
+                    tui::restore();
+                    std::process::exit(130);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Drop for PauseListener {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(unix)]
+mod term {
+    use std::{io, mem, sync::OnceLock};
+
+    static ORIGINAL: OnceLock<libc::termios> = OnceLock::new();
+
+    /// Turns off line buffering and echo on stdin so single key presses
+    /// arrive immediately, while leaving output processing and Ctrl-C
+    /// signals alone (unlike full raw mode).
+    pub struct InputMode;
+
+    impl InputMode {
+        pub fn enable() -> io::Result<Self> {
+            // SAFETY: termios is plain data and stdin is a terminal.
+            unsafe {
+                let mut ios: libc::termios = mem::zeroed();
+                if libc::tcgetattr(libc::STDIN_FILENO, &mut ios) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                ORIGINAL.get_or_init(|| ios);
+
+                ios.c_lflag &= !(libc::ICANON | libc::ECHO);
+                if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &ios) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            Ok(Self)
+        }
+    }
+
+    /// Puts back the terminal attributes from before `enable`. Only does
+    /// async-signal-safe things, as being killed by a signal skips Drop and
+    /// would leave the shell without echo.
+    pub fn restore() {
+        if let Some(original) = ORIGINAL.get() {
+            // SAFETY: restores attributes read from the same terminal.
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, original);
+            }
+        }
+    }
+
+    impl Drop for InputMode {
+        fn drop(&mut self) {
+            restore();
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod term {
+    use std::io;
+
+    pub struct InputMode;
+
+    impl InputMode {
+        pub fn enable() -> io::Result<Self> {
+            crossterm::terminal::enable_raw_mode()?;
+            Ok(Self)
+        }
+    }
+
+    impl Drop for InputMode {
+        fn drop(&mut self) {
+            let _ = crossterm::terminal::disable_raw_mode();
+        }
+    }
+}
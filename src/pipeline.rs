@@ -0,0 +1,93 @@
+use anyhow::{anyhow, Result};
+use std::{
+    io::{Read, Write},
+    sync::mpsc::sync_channel,
+    thread,
+};
+
+use crate::{
+    checksum::Checksums,
+    cipher::Cipher,
+    pool::{BufferPool, PooledBuffer},
+};
+
+/// Chunks allowed in flight between two stages.
+const DEPTH: usize = 4;
+
+/// Most pool buffers a single transform holds at once: a full channel
+/// between each pair of stages plus one in each stage.
+pub const BUFFERS: usize = 2 * DEPTH + 3;
+
+/// Runs reading, transforming and writing on separate threads connected by
+/// bounded channels, so I/O on both ends overlaps with the transform.
+/// Buffers come from `pool` and go back to it once written. `progress` is
+/// called on the calling thread, which does the writing.
+///
+/// With `checksums`, the plaintext is hashed on the reader thread and the
+/// ciphertext on the writer, so hashing overlaps with the transform.
+pub fn transform(
+    mut reader: impl Read + Send,
+    writer: &mut impl Write,
+    cipher: &mut dyn Cipher,
+    pool: &BufferPool,
+    checksums: Option<&mut Checksums>,
+    mut progress: impl FnMut(u64) -> Result<()>,
+) -> Result<()> {
+    let (mut plain, mut ciphertext) = match checksums {
+        Some(sums) => (Some(&mut sums.plain), Some(&mut sums.cipher)),
+        None => (None, None),
+    };
+    let (read_tx, read_rx) = sync_channel::<(PooledBuffer, usize, u64)>(DEPTH);
+    let (xor_tx, xor_rx) = sync_channel::<(PooledBuffer, usize)>(DEPTH);
+
+    thread::scope(|scope| {
+        let read_stage = scope.spawn(move || -> std::io::Result<()> {
+            let mut offset = 0u64;
+            loop {
+                let mut buffer = pool.get();
+                let read_count = reader.read(&mut buffer)?;
+                if read_count == 0 {
+                    break;
+                }
+                if let Some(plain) = plain.as_deref_mut() {
+                    plain.update(&buffer[..read_count]);
+                }
+                if read_tx.send((buffer, read_count, offset)).is_err() {
+                    break;
+                }
+                offset += read_count as u64;
+            }
+            Ok(())
+        });
+
+        scope.spawn(move || {
+            for (mut buffer, len, offset) in read_rx {
+                cipher.apply(offset, &mut buffer[..len]);
+                if xor_tx.send((buffer, len)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let written = (|| {
+            let mut processed = 0u64;
+            for (buffer, len) in xor_rx.iter() {
+                if let Some(ciphertext) = ciphertext.as_deref_mut() {
+                    ciphertext.update(&buffer[..len]);
+                }
+                writer.write_all(&buffer[..len])?;
+                processed += len as u64;
+                progress(processed)?;
+            }
+            Ok(())
+        })();
+
+        // Dropping our end unblocks the other stages if we bailed early.
+        drop(xor_rx);
+
+        let read = read_stage
+            .join()
+            .map_err(|_| anyhow!("Reader thread panicked"))?;
+        written.and(read.map_err(Into::into))
+    })
+}
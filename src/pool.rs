@@ -0,0 +1,111 @@
+use std::{
+    alloc::{self, Layout},
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+    sync::Mutex,
+};
+
+/// Alignment required for cache-bypassing I/O; covers 4K-sector devices.
+pub const ALIGN: usize = 4096;
+
+/// A zeroed heap buffer whose address and length are multiples of [`ALIGN`].
+pub struct AlignedBuffer {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+impl AlignedBuffer {
+    pub fn new(len: usize) -> Self {
+        let len = len.max(1).div_ceil(ALIGN) * ALIGN;
+        let layout = Layout::from_size_align(len, ALIGN).expect("valid buffer layout");
+        // SAFETY: `layout` has a non-zero size.
+        let ptr = unsafe { alloc::alloc_zeroed(layout) };
+        let ptr = NonNull::new(ptr).unwrap_or_else(|| alloc::handle_alloc_error(layout));
+        Self { ptr, len }
+    }
+}
+
+impl Deref for AlignedBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: `ptr` owns `len` initialized bytes.
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for AlignedBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // SAFETY: `ptr` owns `len` initialized bytes.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for AlignedBuffer {
+    fn drop(&mut self) {
+        // SAFETY: allocated in `new` with exactly this layout.
+        unsafe {
+            alloc::dealloc(
+                self.ptr.as_ptr(),
+                Layout::from_size_align_unchecked(self.len, ALIGN),
+            );
+        }
+    }
+}
+
+// SAFETY: the buffer is uniquely owned heap memory.
+unsafe impl Send for AlignedBuffer {}
+
+/// Hands out page-aligned buffers of one size and takes them back when
+/// they are dropped, so files and worker threads reuse a small set of
+/// allocations instead of allocating per file.
+pub struct BufferPool {
+    buffer_size: usize,
+    free: Mutex<Vec<AlignedBuffer>>,
+}
+
+impl BufferPool {
+    /// `buffer_size` is rounded up to a multiple of [`ALIGN`].
+    pub fn new(buffer_size: usize) -> Self {
+        Self {
+            buffer_size,
+            free: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn get(&self) -> PooledBuffer<'_> {
+        let buffer = self.free.lock().unwrap().pop();
+        PooledBuffer {
+            buffer: Some(buffer.unwrap_or_else(|| AlignedBuffer::new(self.buffer_size))),
+            pool: self,
+        }
+    }
+}
+
+/// A buffer borrowed from a [`BufferPool`], returned to it on drop.
+pub struct PooledBuffer<'a> {
+    buffer: Option<AlignedBuffer>,
+    pool: &'a BufferPool,
+}
+
+impl Deref for PooledBuffer<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.buffer.as_deref().unwrap()
+    }
+}
+
+impl DerefMut for PooledBuffer<'_> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        self.buffer.as_deref_mut().unwrap()
+    }
+}
+
+impl Drop for PooledBuffer<'_> {
+    fn drop(&mut self) {
+        if let Some(buffer) = self.buffer.take() {
+            self.pool.free.lock().unwrap().push(buffer);
+        }
+    }
+}
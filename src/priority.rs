@@ -0,0 +1,113 @@
+use std::io;
+
+/// Lowers the scheduling and I/O priority of the whole process.
+///
+/// `idle` asks for the lowest class the platform offers (idle I/O class on
+/// Linux, background mode on macOS and Windows); otherwise priority is
+/// just reduced so foreground work wins contention.
+pub fn lower(idle: bool) -> io::Result<()> {
+    imp::lower(idle)
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use std::io;
+
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+    const IOPRIO_CLASS_BE: libc::c_int = 2;
+    const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+
+    pub fn lower(idle: bool) -> io::Result<()> {
+        let nice = if idle { 19 } else { 10 };
+        // SAFETY: plain syscalls affecting only this process.
+        unsafe {
+            if libc::setpriority(libc::PRIO_PROCESS, 0, nice) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let ioprio = if idle {
+                IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT
+            } else {
+                (IOPRIO_CLASS_BE << IOPRIO_CLASS_SHIFT) | 7
+            };
+            if libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, ioprio) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use std::io;
+
+    pub fn lower(idle: bool) -> io::Result<()> {
+        // SAFETY: plain syscalls affecting only this process.
+        let rc = unsafe {
+            if idle {
+                libc::setpriority(libc::PRIO_DARWIN_PROCESS, 0, libc::PRIO_DARWIN_BG)
+            } else {
+                libc::setpriority(libc::PRIO_PROCESS, 0, 10)
+            }
+        };
+        if rc != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "macos"))))]
+mod imp {
+    use std::io;
+
+    pub fn lower(idle: bool) -> io::Result<()> {
+        let nice = if idle { 19 } else { 10 };
+        // SAFETY: plain syscall affecting only this process.
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::{ffi::c_void, io};
+
+    const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x0000_4000;
+    const PROCESS_MODE_BACKGROUND_BEGIN: u32 = 0x0010_0000;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentProcess() -> *mut c_void;
+        fn SetPriorityClass(process: *mut c_void, class: u32) -> i32;
+    }
+
+    pub fn lower(idle: bool) -> io::Result<()> {
+        let class = if idle {
+            PROCESS_MODE_BACKGROUND_BEGIN
+        } else {
+            BELOW_NORMAL_PRIORITY_CLASS
+        };
+        // SAFETY: the pseudo-handle from GetCurrentProcess is always valid.
+        if unsafe { SetPriorityClass(GetCurrentProcess(), class) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod imp {
+    use std::io;
+
+    pub fn lower(_idle: bool) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "priority controls are not supported on this platform",
+        ))
+    }
+}
@@ -0,0 +1,225 @@
+use std::{
+    error::Error,
+    fmt,
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// A file about to be transformed, passed to `on_file_start`.
+#[derive(Clone, Copy, Debug)]
+pub struct FileStart<'a> {
+    pub input: &'a Path,
+    pub output: &'a Path,
+}
+
+/// How far the transform of one file has got, passed to `on_progress`
+/// after each chunk.
+#[derive(Clone, Copy, Debug)]
+pub struct FileProgress<'a> {
+    pub path: &'a Path,
+    pub done: u64,
+    pub total: u64,
+}
+
+/// How the transform of one file ended, passed to `on_file_complete`.
+#[derive(Clone, Copy, Debug)]
+pub struct FileResult<'a> {
+    pub input: &'a Path,
+    pub output: &'a Path,
+    /// Bytes written to the output.
+    pub bytes: u64,
+    pub duration: Duration,
+    /// Why the file failed, or `None` if its output is complete. Errors
+    /// from `Job::run` are `JustError`s.
+    pub error: Option<&'a (dyn Error + Send + Sync)>,
+}
+
+/// How a whole run ended, passed to `on_run_complete`.
+#[derive(Clone, Copy, Debug)]
+pub struct RunResult<'a> {
+    pub input: &'a Path,
+    /// Files whose output is complete.
+    pub files: u64,
+    /// Files that failed.
+    pub failed: u64,
+    /// Bytes written to the complete outputs.
+    pub bytes: u64,
+    pub duration: Duration,
+    /// Why the run stopped early, or `None` if it went through.
+    pub error: Option<&'a (dyn Error + Send + Sync)>,
+}
+
+type RunStartHook = dyn Fn(&Path) + Send + Sync;
+type FileStartHook = dyn Fn(&FileStart) + Send + Sync;
+type ProgressHook = dyn Fn(&FileProgress) + Send + Sync;
+type CompleteHook = dyn Fn(&FileResult) + Send + Sync;
+type RunCompleteHook = dyn Fn(&RunResult) + Send + Sync;
+
+/// Totals of a job's current or last run, counted as files complete.
+/// `Job::stats` shares them, so another thread can read them while the
+/// run goes on; each count is exact, though two read one after the
+/// other may straddle a file completing.
+#[derive(Debug)]
+pub struct RunStats {
+    files_done: AtomicU64,
+    files_failed: AtomicU64,
+    bytes_done: AtomicU64,
+    started: Mutex<Instant>,
+}
+
+impl RunStats {
+    /// Files whose output is complete.
+    pub fn files_done(&self) -> u64 {
+        self.files_done.load(Ordering::Relaxed)
+    }
+
+    pub fn files_failed(&self) -> u64 {
+        self.files_failed.load(Ordering::Relaxed)
+    }
+
+    /// Bytes written to the complete outputs.
+    pub fn bytes_done(&self) -> u64 {
+        self.bytes_done.load(Ordering::Relaxed)
+    }
+
+    /// Time since the run started.
+    pub fn elapsed(&self) -> Duration {
+        self.started.lock().unwrap().elapsed()
+    }
+
+    /// `bytes_done` per second of `elapsed`.
+    pub fn throughput(&self) -> f64 {
+        match self.elapsed().as_secs_f64() {
+            0.0 => 0.0,
+            seconds => self.bytes_done() as f64 / seconds,
+        }
+    }
+
+    fn reset(&self) {
+        self.files_done.store(0, Ordering::Relaxed);
+        self.files_failed.store(0, Ordering::Relaxed);
+        self.bytes_done.store(0, Ordering::Relaxed);
+        *self.started.lock().unwrap() = Instant::now();
+    }
+
+    fn record(&self, result: &FileResult) {
+        match result.error {
+            Some(_) => self.files_failed.fetch_add(1, Ordering::Relaxed),
+            None => {
+                self.bytes_done.fetch_add(result.bytes, Ordering::Relaxed);
+                self.files_done.fetch_add(1, Ordering::Relaxed)
+            }
+        };
+    }
+}
+
+impl Default for RunStats {
+    fn default() -> Self {
+        Self {
+            files_done: AtomicU64::new(0),
+            files_failed: AtomicU64::new(0),
+            bytes_done: AtomicU64::new(0),
+            started: Mutex::new(Instant::now()),
+        }
+    }
+}
+
+/// The closures a `Job` reports to. Workers of a parallel run call them
+/// from their own threads. The run's `RunStats` are counted as
+/// `file_complete` is called.
+#[derive(Clone, Default)]
+pub(crate) struct Hooks {
+    on_run_start: Option<Arc<RunStartHook>>,
+    on_file_start: Option<Arc<FileStartHook>>,
+    on_progress: Option<Arc<ProgressHook>>,
+    on_file_complete: Option<Arc<CompleteHook>>,
+    on_run_complete: Option<Arc<RunCompleteHook>>,
+    stats: Arc<RunStats>,
+}
+
+impl Hooks {
+    pub fn set_run_start(&mut self, hook: impl Fn(&Path) + Send + Sync + 'static) {
+        self.on_run_start = Some(Arc::new(hook));
+    }
+
+    pub fn set_file_start(&mut self, hook: impl Fn(&FileStart) + Send + Sync + 'static) {
+        self.on_file_start = Some(Arc::new(hook));
+    }
+
+    pub fn set_progress(&mut self, hook: impl Fn(&FileProgress) + Send + Sync + 'static) {
+        self.on_progress = Some(Arc::new(hook));
+    }
+
+    pub fn set_file_complete(&mut self, hook: impl Fn(&FileResult) + Send + Sync + 'static) {
+        self.on_file_complete = Some(Arc::new(hook));
+    }
+
+    pub fn set_run_complete(&mut self, hook: impl Fn(&RunResult) + Send + Sync + 'static) {
+        self.on_run_complete = Some(Arc::new(hook));
+    }
+
+    pub fn stats(&self) -> &Arc<RunStats> {
+        &self.stats
+    }
+
+    /// Starts the run's stats again from zero.
+    pub fn run_start(&self, input: &Path) {
+        self.stats.reset();
+        if let Some(hook) = &self.on_run_start {
+            hook(input);
+        }
+    }
+
+    pub fn file_start(&self, start: &FileStart) {
+        if let Some(hook) = &self.on_file_start {
+            hook(start);
+        }
+    }
+
+    pub fn progress(&self, progress: &FileProgress) {
+        if let Some(hook) = &self.on_progress {
+            hook(progress);
+        }
+    }
+
+    pub fn file_complete(&self, result: &FileResult) {
+        self.stats.record(result);
+        if let Some(hook) = &self.on_file_complete {
+            hook(result);
+        }
+    }
+
+    pub fn run_complete(
+        &self,
+        input: &Path,
+        duration: Duration,
+        error: Option<&(dyn Error + Send + Sync)>,
+    ) {
+        if let Some(hook) = &self.on_run_complete {
+            hook(&RunResult {
+                input,
+                files: self.stats.files_done(),
+                failed: self.stats.files_failed(),
+                bytes: self.stats.bytes_done(),
+                duration,
+                error,
+            });
+        }
+    }
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Hooks")
+            .field("on_run_start", &self.on_run_start.is_some())
+            .field("on_file_start", &self.on_file_start.is_some())
+            .field("on_progress", &self.on_progress.is_some())
+            .field("on_file_complete", &self.on_file_complete.is_some())
+            .field("on_run_complete", &self.on_run_complete.is_some())
+            .finish()
+    }
+}
@@ -0,0 +1,115 @@
+use std::sync::atomic::{AtomicU16, AtomicUsize, Ordering};
+
+use crate::width;
+
+/// Columns assumed when the terminal size can't be read.
+const DEFAULT_WIDTH: u16 = 80;
+
+static GENERATION: AtomicUsize = AtomicUsize::new(0);
+static WIDTH: AtomicU16 = AtomicU16::new(0);
+
+/// Starts tracking terminal resizes. On unix this catches SIGWINCH; on
+/// other platforms resizes arrive as events through the key listener,
+/// which calls `notify`.
+pub fn watch() {
+    #[cfg(unix)]
+    // SAFETY: the action only updates atomics, which is async-signal-safe.
+    let _ = unsafe { signal_hook::low_level::register(signal_hook::consts::SIGWINCH, notify) };
+}
+
+/// Records that the terminal was resized.
+pub fn notify() {
+    WIDTH.store(0, Ordering::Relaxed);
+    GENERATION.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Counts resizes so far. Anything drawn under an older value may have
+/// been wrapped by the terminal and needs redrawing.
+pub fn generation() -> usize {
+    GENERATION.load(Ordering::Relaxed)
+}
+
+/// Current terminal width in columns, re-read after each resize.
+pub fn width() -> usize {
+    let mut width = WIDTH.load(Ordering::Relaxed);
+    if width == 0 {
+        width = crossterm::terminal::size()
+            .map(|(columns, _)| columns)
+            .ok()
+            .filter(|&columns| columns > 0)
+            .unwrap_or(DEFAULT_WIDTH);
+        WIDTH.store(width, Ordering::Relaxed);
+    }
+    width as usize
+}
+
+/// Columns `text` takes up on screen, skipping color escapes.
+pub fn visible_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+        } else {
+            width += width::char_width(c);
+        }
+    }
+    width
+}
+
+/// Cuts `text` to fit on one line of the terminal, so that it can be
+/// redrawn in place without the terminal wrapping it.
+pub fn fit(text: &str) -> String {
+    truncate(text, width().saturating_sub(1))
+}
+
+/// Cuts `text` after `columns` visible columns, keeping color escapes and
+/// resetting styles if anything was cut. Wide characters are cut whole
+/// and combining characters stay with the one before them.
+fn truncate(text: &str, columns: usize) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut width = 0;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            out.push(c);
+            for c in chars.by_ref() {
+                out.push(c);
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+            continue;
+        }
+        let joined = out.ends_with(width::ZWJ);
+        let char_width = if joined { 0 } else { width::char_width(c) };
+        if width + char_width > columns {
+            out.push_str("\x1b[0m");
+            return out;
+        }
+        out.push(c);
+        width += char_width;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_visible_width() {
+        assert_eq!(visible_width("plain"), 5);
+        assert_eq!(visible_width("\x1b[1mbold\x1b[0m ▶"), 6);
+        assert_eq!(visible_width("\x1b[38;5;10m✓\x1b[39m"), 1);
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("abcdef", 3), "abc\x1b[0m");
+        assert_eq!(truncate("\x1b[1mab\x1b[0mcd", 2), "\x1b[1mab\x1b[0m\x1b[0m");
+        assert_eq!(truncate("日本語", 5), "日本\x1b[0m");
+        assert_eq!(truncate("e\u{301}x", 1), "e\u{301}\x1b[0m");
+    }
+}
@@ -0,0 +1,223 @@
+use std::{fmt::Write as _, time::Duration};
+
+use crate::json::json_string;
+
+/// Version of the JSON xortool writes: progress events, manifests and
+/// job specs all carry it as `schema_version`. Fields may be added
+/// without a new version, so consumers should ignore ones they don't
+/// know. Removing or renaming a field or changing its type or meaning
+/// bumps it.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Something that happened during the run, sent to `--progress-socket`
+/// monitors as one line of JSON. Every line has `schema_version` and an
+/// `event` naming the variant; paths are relative to where the run was
+/// started.
+#[derive(Clone, Debug)]
+pub enum Event<'a> {
+    /// `"progress"`: `processed` of the `total` bytes of `file` are done.
+    Progress {
+        file: &'a str,
+        processed: u64,
+        total: u64,
+    },
+    /// `"done"`: `file`'s output is complete, `bytes` long, after
+    /// `seconds`.
+    Done {
+        file: &'a str,
+        bytes: u64,
+        duration: Duration,
+    },
+    /// `"failed"`: `file` failed with `error`.
+    Failed { file: &'a str, error: &'a str },
+    /// `"skipped"`: the user skipped `file`.
+    Skipped { file: &'a str },
+    /// `"finished"`: the run is over after `seconds`; `ok` is false and
+    /// `error` set if it failed, and `error` is `null` otherwise.
+    Finished {
+        error: Option<String>,
+        duration: Duration,
+    },
+}
+
+impl Event<'_> {
+    pub fn to_json(&self) -> String {
+        let fields = match self {
+            Event::Progress {
+                file,
+                processed,
+                total,
+            } => format!(
+                r#""event":"progress","file":{},"processed":{},"total":{}"#,
+                json_string(file),
+                processed,
+                total
+            ),
+            Event::Done {
+                file,
+                bytes,
+                duration,
+            } => format!(
+                r#""event":"done","file":{},"bytes":{},"seconds":{:.3}"#,
+                json_string(file),
+                bytes,
+                duration.as_secs_f64()
+            ),
+            Event::Failed { file, error } => format!(
+                r#""event":"failed","file":{},"error":{}"#,
+                json_string(file),
+                json_string(error)
+            ),
+            Event::Skipped { file } => {
+                format!(r#""event":"skipped","file":{}"#, json_string(file))
+            }
+            Event::Finished { error, duration } => format!(
+                r#""event":"finished","ok":{},"error":{},"seconds":{:.3}"#,
+                error.is_none(),
+                error.as_deref().map_or("null".to_string(), json_string),
+                duration.as_secs_f64()
+            ),
+        };
+        format!(r#"{{"schema_version":{},{}}}"#, SCHEMA_VERSION, fields)
+    }
+}
+
+/// `xor/manifest.json`, written by `--manifest`.
+#[derive(Clone, Debug)]
+pub struct Manifest {
+    /// When the run finished, as an RFC 3339 UTC timestamp.
+    pub created: String,
+    /// `"NFC"` or `"NFD"`, the Unicode form of the paths.
+    pub normalization: &'static str,
+    pub cipher: ManifestCipher,
+    /// Sorted by `path`.
+    pub files: Vec<ManifestFile>,
+}
+
+/// How the outputs in a manifest were made, without the key itself.
+#[derive(Clone, Debug)]
+pub struct ManifestCipher {
+    /// Always `"xor"` so far.
+    pub algorithm: &'static str,
+    pub key_length: usize,
+    /// Whether runs of zeros in the inputs were left as holes.
+    pub sparse: bool,
+}
+
+/// One input and its output, with paths relative to the input root.
+#[derive(Clone, Debug)]
+pub struct ManifestFile {
+    pub path: String,
+    pub output: String,
+    pub size: u64,
+    pub output_size: u64,
+    /// SHA-256 of the input and of the output, in hex.
+    pub sha256: String,
+    pub output_sha256: String,
+}
+
+impl Manifest {
+    /// Manifests also have `version`, the field the first ones were
+    /// versioned by. It stays 1 until the manifest layout changes.
+    const LEGACY_VERSION: u32 = 1;
+
+    /// The manifest as indented JSON, one file per line.
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        let _ = writeln!(json, "{{");
+        let _ = writeln!(json, "  \"schema_version\": {},", SCHEMA_VERSION);
+        let _ = writeln!(json, "  \"version\": {},", Self::LEGACY_VERSION);
+        let _ = writeln!(json, "  \"created\": {},", json_string(&self.created));
+        let _ = writeln!(
+            json,
+            "  \"normalization\": {},",
+            json_string(self.normalization)
+        );
+        let _ = writeln!(
+            json,
+            "  \"cipher\": {{\"algorithm\": {}, \"key_length\": {}, \"sparse\": {}}},",
+            json_string(self.cipher.algorithm),
+            self.cipher.key_length,
+            self.cipher.sparse
+        );
+        let _ = writeln!(json, "  \"files\": [");
+        for (i, file) in self.files.iter().enumerate() {
+            let _ = writeln!(
+                json,
+                "    {{\"path\": {}, \"output\": {}, \"size\": {}, \"output_size\": {}, \"sha256\": {}, \"output_sha256\": {}}}{}",
+                json_string(&file.path),
+                json_string(&file.output),
+                file.size,
+                file.output_size,
+                json_string(&file.sha256),
+                json_string(&file.output_sha256),
+                if i + 1 < self.files.len() { "," } else { "" }
+            );
+        }
+        let _ = writeln!(json, "  ]");
+        let _ = writeln!(json, "}}");
+        json
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_json() {
+        let event = Event::Progress {
+            file: "dir/\"odd\"\n.bin",
+            processed: 10,
+            total: 20,
+        };
+        assert_eq!(
+            event.to_json(),
+            r#"{"schema_version":1,"event":"progress","file":"dir/\"odd\"\n.bin","processed":10,"total":20}"#
+        );
+
+        let event = Event::Finished {
+            error: None,
+            duration: Duration::from_millis(1500),
+        };
+        assert_eq!(
+            event.to_json(),
+            r#"{"schema_version":1,"event":"finished","ok":true,"error":null,"seconds":1.500}"#
+        );
+    }
+
+    #[test]
+    fn test_manifest_json() {
+        let manifest = Manifest {
+            created: "2024-03-01T12:00:00Z".into(),
+            normalization: "NFC",
+            cipher: ManifestCipher {
+                algorithm: "xor",
+                key_length: 2,
+                sparse: false,
+            },
+            files: vec![ManifestFile {
+                path: "a".into(),
+                output: "xor/a".into(),
+                size: 3,
+                output_size: 3,
+                sha256: "01".into(),
+                output_sha256: "02".into(),
+            }],
+        };
+        assert_eq!(
+            manifest.to_json(),
+            r#"{
+  "schema_version": 1,
+  "version": 1,
+  "created": "2024-03-01T12:00:00Z",
+  "normalization": "NFC",
+  "cipher": {"algorithm": "xor", "key_length": 2, "sparse": false},
+  "files": [
+    {"path": "a", "output": "xor/a", "size": 3, "output_size": 3, "sha256": "01", "output_sha256": "02"}
+  ]
+}
+"#
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use anyhow::{bail, ensure, Context, Result};
+use std::{
+    env, fs,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::{checksum::Sha256, color::Stylize, transform::xor_encrypt, walk::OUTPUT_DIR};
+
+const KEY: &str = "a1b2c3d4e5";
+
+/// I/O paths the round trip is run through, as extra arguments. Those a
+/// platform lacks fall back to buffered I/O, which is still checked.
+const MODES: &[&[&str]] = &[
+    &[],
+    &["--pipeline"],
+    &["--mmap"],
+    &["--sparse"],
+    &["--direct-io"],
+    &["--io-uring"],
+    &["--checksum"],
+    &["--jobs", "4"],
+];
+
+/// Runs known-answer tests and a round trip of a scratch directory
+/// through every I/O path, printing a line per check.
+pub fn run() -> Result<()> {
+    let mut checks: Vec<(String, Result<()>)> = vec![
+        ("SHA-256 known answers".to_string(), sha256_known_answers()),
+        ("XOR known answers".to_string(), xor_known_answers()),
+    ];
+    let dir = env::temp_dir().join(format!("xortool-selftest-{}", std::process::id()));
+    for mode in MODES {
+        let name = match mode.is_empty() {
+            true => "round trip".to_string(),
+            false => format!("round trip {}", mode.join(" ")),
+        };
+        checks.push((name, round_trip(&dir, mode)));
+    }
+    let _ = fs::remove_dir_all(&dir);
+
+    let mut failed = 0;
+    for (name, result) in &checks {
+        match result {
+            Ok(()) => println!("{} {}", "ok".green(), name),
+            Err(e) => {
+                failed += 1;
+                println!("{} {}: {:#}", "FAILED".red(), name, e);
+            }
+        }
+    }
+    if failed > 0 {
+        bail!("{} of {} self-tests failed", failed, checks.len());
+    }
+    println!("All {} self-tests passed", checks.len());
+    Ok(())
+}
+
+fn sha256_known_answers() -> Result<()> {
+    let cases: [(&[u8], &str); 3] = [
+        (
+            b"",
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+        ),
+        (
+            b"abc",
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+        ),
+        (
+            b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+        ),
+    ];
+    for (input, expected) in cases {
+        let mut hasher = Sha256::new();
+        hasher.update(input);
+        let digest = hex::encode(hasher.finalize());
+        ensure!(digest == expected, "digest of {:?} is {}", input, digest);
+    }
+    Ok(())
+}
+
+/// Checks the vectorized transform against a byte-at-a-time one, at
+/// every key phase and across lengths around the vector widths.
+fn xor_known_answers() -> Result<()> {
+    let mut fixed = *b"xortool";
+    xor_encrypt(&mut fixed, &[0x01, 0x02], 1);
+    ensure!(&fixed == b"znpumnn", "fixed vector gave {:?}", fixed);
+
+    let key: Vec<u8> = (1..=7).collect();
+    for len in [0, 1, 15, 16, 17, 31, 32, 33, 63, 64, 65, 1000] {
+        for offset in 0..key.len() as u64 {
+            let plain: Vec<u8> = (0..len).map(|i| (i * 37 + 11) as u8).collect();
+            let expected: Vec<u8> = plain
+                .iter()
+                .enumerate()
+                .map(|(i, b)| b ^ key[(i + offset as usize) % key.len()])
+                .collect();
+            let mut data = plain.clone();
+            xor_encrypt(&mut data, &key, offset);
+            ensure!(data == expected, "length {} at offset {}", len, offset);
+        }
+    }
+    Ok(())
+}
+
+/// Encrypts and decrypts a scratch directory by running this binary, and
+/// compares the result with the originals.
+fn round_trip(dir: &Path, mode: &[&str]) -> Result<()> {
+    let _ = fs::remove_dir_all(dir);
+    let files: [(&str, Vec<u8>); 4] = [
+        ("empty", Vec::new()),
+        (
+            "ünïcødé 日本語.txt",
+            "héllo wörld\n".repeat(100).into_bytes(),
+        ),
+        (
+            "large.bin",
+            (0..3_000_000u32).map(|i| (i * 7 + i / 251) as u8).collect(),
+        ),
+        (
+            "nested/inner.bin",
+            (0..70_000u32).map(|i| i as u8).collect(),
+        ),
+    ];
+    for (name, contents) in &files {
+        let path = dir.join(name);
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(&path, contents)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+
+    run_tool(dir, mode)?;
+    run_tool(&dir.join(OUTPUT_DIR), mode)?;
+    run_tool(&dir.join("nested").join(OUTPUT_DIR), mode)?;
+
+    for (name, contents) in &files {
+        let name = Path::new(name);
+        let parent = name.parent().unwrap_or(Path::new(""));
+        let file_name = name.file_name().unwrap();
+        let encrypted = fs::read(dir.join(parent).join(OUTPUT_DIR).join(file_name))
+            .with_context(|| format!("No output for {}", name.display()))?;
+        ensure!(
+            contents.is_empty() || encrypted != *contents,
+            "{} was not encrypted",
+            name.display()
+        );
+        let decrypted = fs::read(
+            dir.join(parent)
+                .join(OUTPUT_DIR)
+                .join(OUTPUT_DIR)
+                .join(file_name),
+        )
+        .with_context(|| format!("No decrypted output for {}", name.display()))?;
+        ensure!(
+            decrypted == *contents,
+            "{} did not decrypt to the original",
+            name.display()
+        );
+    }
+    Ok(())
+}
+
+fn run_tool(input: &Path, mode: &[&str]) -> Result<()> {
+    let exe = env::current_exe()?;
+    let status = Command::new(exe)
+        .args(["--key", KEY, "--recursive", "--plain"])
+        .args(mode)
+        .arg(input)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    ensure!(
+        status.success(),
+        "xortool {} exited with {}",
+        mode.join(" "),
+        status
+    );
+    Ok(())
+}
@@ -0,0 +1,139 @@
+use crate::core::xor_from_phase;
+
+/// Bytes handled per vector iteration.
+const BLOCK: usize = 64;
+
+/// XOR `data` with `key` repeated from `phase`, 64 bytes at a time.
+///
+/// The key is pre-expanded so that every 64-byte window starting inside the
+/// first repetition is contiguous, which lets each block use a plain
+/// unaligned load regardless of key length. AVX2 is picked at runtime on
+/// x86_64 and NEON is used on aarch64; other targets use a word-wise loop.
+pub fn xor_keystream(data: &mut [u8], key: &[u8], phase: usize) {
+    if data.len() < BLOCK {
+        xor_from_phase(data, key, phase);
+        return;
+    }
+
+    let mut expanded = Vec::with_capacity(key.len() + BLOCK);
+    while expanded.len() < key.len() + BLOCK {
+        expanded.extend_from_slice(key);
+    }
+
+    let split = data.len() - data.len() % BLOCK;
+    let (blocks, tail) = data.split_at_mut(split);
+    let phase = xor_blocks(blocks, &expanded, key.len(), phase);
+    xor_from_phase(tail, key, phase);
+}
+
+/// Processes whole blocks and returns the key phase for the next byte.
+fn xor_blocks(blocks: &mut [u8], expanded: &[u8], key_len: usize, phase: usize) -> usize {
+    #[cfg(target_arch = "x86_64")]
+    if std::arch::is_x86_feature_detected!("avx2") {
+        // SAFETY: AVX2 support was just verified.
+        return unsafe { xor_blocks_avx2(blocks, expanded, key_len, phase) };
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    {
+        // SAFETY: NEON is mandatory on aarch64.
+        return unsafe { xor_blocks_neon(blocks, expanded, key_len, phase) };
+    }
+
+    #[allow(unreachable_code)]
+    xor_blocks_portable(blocks, expanded, key_len, phase)
+}
+
+fn xor_blocks_portable(
+    blocks: &mut [u8],
+    expanded: &[u8],
+    key_len: usize,
+    mut phase: usize,
+) -> usize {
+    for block in blocks.chunks_exact_mut(BLOCK) {
+        let stream = &expanded[phase..phase + BLOCK];
+        for (word, key) in block.chunks_exact_mut(8).zip(stream.chunks_exact(8)) {
+            let value = u64::from_ne_bytes(word.try_into().unwrap())
+                ^ u64::from_ne_bytes(key.try_into().unwrap());
+            word.copy_from_slice(&value.to_ne_bytes());
+        }
+        phase = (phase + BLOCK) % key_len;
+    }
+    phase
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn xor_blocks_avx2(
+    blocks: &mut [u8],
+    expanded: &[u8],
+    key_len: usize,
+    mut phase: usize,
+) -> usize {
+    use std::arch::x86_64::*;
+
+    for block in blocks.chunks_exact_mut(BLOCK) {
+        let stream = expanded[phase..phase + BLOCK].as_ptr();
+        let ptr = block.as_mut_ptr();
+        for lane in [0, 32] {
+            let data = _mm256_loadu_si256(ptr.add(lane) as *const __m256i);
+            let key = _mm256_loadu_si256(stream.add(lane) as *const __m256i);
+            _mm256_storeu_si256(ptr.add(lane) as *mut __m256i, _mm256_xor_si256(data, key));
+        }
+        phase = (phase + BLOCK) % key_len;
+    }
+    phase
+}
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn xor_blocks_neon(
+    blocks: &mut [u8],
+    expanded: &[u8],
+    key_len: usize,
+    mut phase: usize,
+) -> usize {
+    use std::arch::aarch64::*;
+
+    for block in blocks.chunks_exact_mut(BLOCK) {
+        let stream = expanded[phase..phase + BLOCK].as_ptr();
+        let ptr = block.as_mut_ptr();
+        for lane in [0, 16, 32, 48] {
+            let data = vld1q_u8(ptr.add(lane));
+            let key = vld1q_u8(stream.add(lane));
+            vst1q_u8(ptr.add(lane), veorq_u8(data, key));
+        }
+        phase = (phase + BLOCK) % key_len;
+    }
+    phase
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_scalar() {
+        let data: Vec<u8> = (0..1000u32).map(|i| (i * 7) as u8).collect();
+
+        for key_len in [1, 3, 31, 64, 65, 200] {
+            let key: Vec<u8> = (0..key_len as u32).map(|i| (i * 13 + 1) as u8).collect();
+            for phase in [0, key_len / 2, key_len - 1] {
+                let mut expected = data.clone();
+                xor_from_phase(&mut expected, &key, phase);
+
+                let mut actual = data.clone();
+                xor_keystream(&mut actual, &key, phase);
+                assert_eq!(expected, actual, "key_len={} phase={}", key_len, phase);
+
+                let mut portable = data[..960].to_vec();
+                let mut expanded = Vec::new();
+                while expanded.len() < key.len() + BLOCK {
+                    expanded.extend_from_slice(&key);
+                }
+                xor_blocks_portable(&mut portable, &expanded, key.len(), phase);
+                assert_eq!(expected[..960], portable[..]);
+            }
+        }
+    }
+}
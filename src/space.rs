@@ -0,0 +1,112 @@
+use anyhow::{bail, Result};
+use std::{io, path::Path};
+
+use crate::humanize;
+
+/// Bytes an unprivileged user can still write to the filesystem holding
+/// `path`.
+pub fn available(path: &Path) -> io::Result<u64> {
+    imp::available(path)
+}
+
+/// Checks up front that `needed` bytes of output fit on the filesystem
+/// holding `dir`, so a long run doesn't stop halfway with a full disk.
+/// With `warn_only` a shortfall is only reported. Platforms that can't
+/// tell are not checked.
+pub fn preflight(dir: &Path, needed: u64, warn_only: bool) -> Result<()> {
+    let Ok(free) = available(dir) else {
+        return Ok(());
+    };
+    if needed <= free {
+        return Ok(());
+    }
+    let message = format!(
+        "Outputs need {} but only {} is free at {}",
+        humanize::size(needed),
+        humanize::size(free),
+        dir.display()
+    );
+    if !warn_only {
+        bail!("{} (use --allow-low-space to run anyway)", message);
+    }
+    eprintln!("Warning: {}", message);
+    Ok(())
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::{ffi::CString, io, mem, os::unix::ffi::OsStrExt, path::Path};
+
+    pub fn available(path: &Path) -> io::Result<u64> {
+        let path = CString::new(path.as_os_str().as_bytes())?;
+        // SAFETY: `path` is NUL-terminated and `stats` is written by the
+        // call before it is read.
+        unsafe {
+            let mut stats: libc::statvfs = mem::zeroed();
+            if libc::statvfs(path.as_ptr(), &mut stats) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            #[allow(clippy::unnecessary_cast)]
+            Ok(stats.f_bavail as u64 * stats.f_frsize as u64)
+        }
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::{io, os::windows::ffi::OsStrExt, path::Path, ptr};
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetDiskFreeSpaceExW(
+            directory: *const u16,
+            available_to_caller: *mut u64,
+            total: *mut u64,
+            free: *mut u64,
+        ) -> i32;
+    }
+
+    pub fn available(path: &Path) -> io::Result<u64> {
+        let wide: Vec<u16> = path.as_os_str().encode_wide().chain([0]).collect();
+        let mut available = 0;
+        // SAFETY: `wide` is NUL-terminated and the totals we don't need
+        // may be null.
+        if unsafe {
+            GetDiskFreeSpaceExW(
+                wide.as_ptr(),
+                &mut available,
+                ptr::null_mut(),
+                ptr::null_mut(),
+            )
+        } == 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(available)
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod imp {
+    use std::{io, path::Path};
+
+    pub fn available(_path: &Path) -> io::Result<u64> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "free space is not known on this platform",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preflight() {
+        let dir = std::env::temp_dir();
+        assert!(preflight(&dir, 0, false).is_ok());
+        assert!(preflight(&dir, u64::MAX, false).is_err());
+        assert!(preflight(&dir, u64::MAX, true).is_ok());
+    }
+}
@@ -0,0 +1,116 @@
+use anyhow::Result;
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Write},
+    ops::Range,
+};
+
+use crate::{cipher::Cipher, pool::BufferPool};
+
+/// Returns the byte ranges of `file` that hold data, or `None` when the
+/// file has no holes or the platform can't tell us where they are.
+pub fn data_regions(file: &File, len: u64) -> io::Result<Option<Vec<Range<u64>>>> {
+    let regions = imp::data_regions(file, len)?;
+    Ok(regions.filter(|regions| !(regions.len() == 1 && regions[0] == (0..len))))
+}
+
+/// Transforms only the data regions of `input`, leaving holes in place in
+/// `output`. Holes are not XORed, so outputs written this way must also be
+/// decrypted in sparse mode to get zeros back for them.
+pub fn transform(
+    input: &mut File,
+    output: &mut File,
+    regions: &[Range<u64>],
+    len: u64,
+    cipher: &mut dyn Cipher,
+    pool: &BufferPool,
+    mut progress: impl FnMut(u64) -> Result<()>,
+) -> Result<()> {
+    let mut buffer = pool.get();
+    let mut processed = 0u64;
+
+    for region in regions {
+        input.seek(SeekFrom::Start(region.start))?;
+        output.seek(SeekFrom::Start(region.start))?;
+
+        let mut offset = region.start;
+        while offset < region.end {
+            let want = (region.end - offset).min(buffer.len() as u64) as usize;
+            let read_count = input.read(&mut buffer[..want])?;
+            if read_count == 0 {
+                break;
+            }
+
+            cipher.apply(offset, &mut buffer[..read_count]);
+            output.write_all(&buffer[..read_count])?;
+
+            offset += read_count as u64;
+            processed += read_count as u64;
+            progress(processed)?;
+        }
+    }
+
+    output.set_len(len)?;
+    Ok(())
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "macos"
+))]
+mod imp {
+    use std::{fs::File, io, ops::Range, os::unix::io::AsRawFd};
+
+    pub fn data_regions(file: &File, len: u64) -> io::Result<Option<Vec<Range<u64>>>> {
+        let fd = file.as_raw_fd();
+        let mut regions = Vec::new();
+        let mut offset = 0u64;
+
+        while offset < len {
+            // SAFETY: lseek on a descriptor we own.
+            let start = unsafe { libc::lseek(fd, offset as libc::off_t, libc::SEEK_DATA) };
+            if start < 0 {
+                let err = io::Error::last_os_error();
+                match err.raw_os_error() {
+                    // No data past `offset`: the rest of the file is a hole.
+                    Some(libc::ENXIO) => break,
+                    // Filesystem doesn't support hole queries.
+                    Some(libc::EINVAL) | Some(libc::ENOTSUP) => return Ok(None),
+                    _ => return Err(err),
+                }
+            }
+
+            // SAFETY: as above.
+            let end = unsafe { libc::lseek(fd, start, libc::SEEK_HOLE) };
+            if end < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let (start, end) = (start as u64, (end as u64).min(len));
+            regions.push(start..end);
+            offset = end;
+        }
+
+        // SAFETY: as above; put the cursor back for the caller.
+        if unsafe { libc::lseek(fd, 0, libc::SEEK_SET) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Some(regions))
+    }
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "macos"
+)))]
+mod imp {
+    use std::{fs::File, io, ops::Range};
+
+    pub fn data_regions(_file: &File, _len: u64) -> io::Result<Option<Vec<Range<u64>>>> {
+        Ok(None)
+    }
+}
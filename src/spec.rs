@@ -0,0 +1,247 @@
+use std::{
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config::Config,
+    error::JustError,
+    job::JobBuilder,
+    json::json_string,
+    key::{HexKey, KeyEnv, KeyFile, KeyPrompt, KeySource},
+    options::ProcessOptions,
+    schema::SCHEMA_VERSION,
+};
+
+/// A run written down, so one that recurs can live in a file under
+/// version control instead of a long command line:
+///
+/// ```toml
+/// input = "photos"
+/// recursive = true
+/// output_dir = "encrypted"
+/// exclude = ["*.tmp", "cache"]
+///
+/// [key]
+/// env = "PHOTOS_KEY"
+/// ```
+///
+/// The key is one of `hex`, `file`, `env` or `prompt = true`. Relative
+/// paths are taken from the directory the file is in.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct JobSpec {
+    pub input: PathBuf,
+    pub key: Option<KeyRef>,
+    /// The settings at the top level of the file besides `input`.
+    pub options: ProcessOptions,
+}
+
+/// Where a spec's key comes from. All but `Hex` keep the key itself out
+/// of the file.
+#[derive(Clone, Debug, PartialEq)]
+pub enum KeyRef {
+    Hex(String),
+    File(PathBuf),
+    Env(String),
+    Prompt,
+}
+
+impl KeyRef {
+    pub fn source(&self) -> Box<dyn KeySource> {
+        match self {
+            KeyRef::Hex(hex) => Box::new(HexKey(hex.clone())),
+            KeyRef::File(path) => Box::new(KeyFile(path.clone())),
+            KeyRef::Env(var) => Box::new(KeyEnv(var.clone())),
+            KeyRef::Prompt => Box::new(KeyPrompt),
+        }
+    }
+}
+
+const KEY_KEYS: [&str; 4] = ["key.hex", "key.file", "key.env", "key.prompt"];
+
+impl JobSpec {
+    /// Reads the TOML spec at `path`.
+    pub fn load(path: &Path) -> Result<Self, JustError> {
+        let text = fs::read_to_string(path).map_err(|source| JustError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let mut spec = Self::from_toml(&text).map_err(|e| match e {
+            JustError::InvalidOptions(message) => {
+                JustError::InvalidOptions(format!("{}: {}", path.display(), message))
+            }
+            e => e,
+        })?;
+        spec.resolve_paths(path.parent().unwrap_or(Path::new("")));
+        Ok(spec)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, JustError> {
+        let invalid = |e: anyhow::Error| JustError::InvalidOptions(format!("{:#}", e));
+        let config = Config::parse(text).map_err(invalid)?;
+        if let Some(unknown) = config.keys().into_iter().find(|key| {
+            *key != "input" && !ProcessOptions::KEYS.contains(key) && !KEY_KEYS.contains(key)
+        }) {
+            return Err(JustError::InvalidOptions(format!(
+                "unknown setting `{}`",
+                unknown
+            )));
+        }
+
+        let Some(input) = config.get_str("input").map_err(invalid)? else {
+            return Err(JustError::InvalidOptions("no `input` given".into()));
+        };
+        let key = match config.keys_in("key").as_slice() {
+            [] => None,
+            ["key.hex"] => config
+                .get_str("key.hex")
+                .map_err(invalid)?
+                .map(|hex| KeyRef::Hex(hex.into())),
+            ["key.file"] => config
+                .get_str("key.file")
+                .map_err(invalid)?
+                .map(|path| KeyRef::File(path.into())),
+            ["key.env"] => config
+                .get_str("key.env")
+                .map_err(invalid)?
+                .map(|var| KeyRef::Env(var.into())),
+            ["key.prompt"] => match config.get_bool("key.prompt").map_err(invalid)? {
+                Some(true) => Some(KeyRef::Prompt),
+                _ => None,
+            },
+            _ => {
+                return Err(JustError::InvalidOptions(
+                    "`key` takes one of `hex`, `file`, `env` or `prompt`".into(),
+                ))
+            }
+        };
+        Ok(Self {
+            input: input.into(),
+            key,
+            options: ProcessOptions::from_config(&config)?,
+        })
+    }
+
+    /// The spec as TOML that `from_toml` reads back. Settings left at
+    /// their defaults are left out.
+    pub fn to_toml(&self) -> String {
+        let mut out = String::new();
+        let path = |path: &Path| json_string(&path.to_string_lossy());
+        let (options, defaults) = (&self.options, ProcessOptions::default());
+        let _ = writeln!(out, "input = {}", path(&self.input));
+        if options.recursive {
+            out.push_str("recursive = true\n");
+        }
+        if let Some(dir) = &options.output_dir {
+            let _ = writeln!(out, "output_dir = {}", path(dir));
+        }
+        if !options.exclude.is_empty() {
+            let patterns: Vec<_> = options.exclude.iter().map(|p| json_string(p)).collect();
+            let _ = writeln!(out, "exclude = [{}]", patterns.join(", "));
+        }
+        if options.buffer_size != defaults.buffer_size {
+            let _ = writeln!(out, "buffer_size = {}", options.buffer_size);
+        }
+        if options.force {
+            out.push_str("force = true\n");
+        }
+        if let Some(key) = &self.key {
+            out.push_str("\n[key]\n");
+            let _ = match key {
+                KeyRef::Hex(hex) => writeln!(out, "hex = {}", json_string(hex)),
+                KeyRef::File(file) => writeln!(out, "file = {}", path(file)),
+                KeyRef::Env(var) => writeln!(out, "env = {}", json_string(var)),
+                KeyRef::Prompt => writeln!(out, "prompt = true"),
+            };
+        }
+        out
+    }
+
+    /// The spec as a JSON object with the same fields as the TOML, and
+    /// `schema_version`.
+    pub fn to_json(&self) -> String {
+        let path = |path: &Path| json_string(&path.to_string_lossy());
+        let options = &self.options;
+        let mut fields = vec![
+            format!("\"schema_version\":{}", SCHEMA_VERSION),
+            format!("\"input\":{}", path(&self.input)),
+            format!("\"recursive\":{}", options.recursive),
+        ];
+        if let Some(dir) = &options.output_dir {
+            fields.push(format!("\"output_dir\":{}", path(dir)));
+        }
+        let patterns: Vec<_> = options.exclude.iter().map(|p| json_string(p)).collect();
+        fields.push(format!("\"exclude\":[{}]", patterns.join(",")));
+        fields.push(format!("\"buffer_size\":{}", options.buffer_size));
+        fields.push(format!("\"force\":{}", options.force));
+        if let Some(key) = &self.key {
+            let key = match key {
+                KeyRef::Hex(hex) => format!("{{\"hex\":{}}}", json_string(hex)),
+                KeyRef::File(file) => format!("{{\"file\":{}}}", path(file)),
+                KeyRef::Env(var) => format!("{{\"env\":{}}}", json_string(var)),
+                KeyRef::Prompt => "{\"prompt\":true}".to_string(),
+            };
+            fields.push(format!("\"key\":{}", key));
+        }
+        format!("{{{}}}", fields.join(","))
+    }
+
+    /// A builder set up as the spec says, with the key loaded from its
+    /// source.
+    pub fn builder(&self) -> Result<JobBuilder, JustError> {
+        let Some(key) = &self.key else {
+            return Err(JustError::InvalidKey("No key given".into()));
+        };
+        Ok(crate::job::Job::builder()
+            .input(&self.input)
+            .key(key.source().load()?)
+            .options(self.options.clone()))
+    }
+
+    fn resolve_paths(&mut self, base: &Path) {
+        self.input = base.join(&self.input);
+        if let Some(dir) = &mut self.options.output_dir {
+            *dir = base.join(&*dir);
+        }
+        if let Some(KeyRef::File(file)) = &mut self.key {
+            *file = base.join(&*file);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spec_round_trip() {
+        let spec = JobSpec::from_toml(
+            r#"
+            input = "photos"
+            recursive = true
+            exclude = ["*.tmp", "cache"]
+            buffer_size = 65536
+
+            [key]
+            env = "PHOTOS_KEY"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(spec.input, Path::new("photos"));
+        assert_eq!(spec.key, Some(KeyRef::Env("PHOTOS_KEY".into())));
+        assert_eq!(spec.options.exclude, ["*.tmp", "cache"]);
+        assert_eq!(spec.options.buffer_size, 65536);
+        assert!(spec.options.recursive && !spec.options.force);
+        assert_eq!(JobSpec::from_toml(&spec.to_toml()).unwrap(), spec);
+        assert_eq!(
+            spec.to_json(),
+            r#"{"schema_version":1,"input":"photos","recursive":true,"exclude":["*.tmp","cache"],"buffer_size":65536,"force":false,"key":{"env":"PHOTOS_KEY"}}"#
+        );
+
+        assert!(JobSpec::from_toml("recursive = true").is_err());
+        assert!(JobSpec::from_toml("input = \"a\"\nbuffer_size = 0").is_err());
+        assert!(JobSpec::from_toml("input = \"a\"\nrecursve = true").is_err());
+        assert!(JobSpec::from_toml("input = \"a\"\n[key]\nhex = \"ff\"\nenv = \"K\"").is_err());
+    }
+}
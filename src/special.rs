@@ -0,0 +1,109 @@
+use anyhow::{bail, Result};
+use std::{
+    fs::{self, FileType},
+    path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use walkdir::DirEntry;
+
+static SKIPPED: AtomicUsize = AtomicUsize::new(0);
+
+/// What a filesystem object that is neither a regular file nor a
+/// directory is, for messages.
+fn kind(file_type: FileType) -> &'static str {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        if file_type.is_fifo() {
+            return "FIFO";
+        }
+        if file_type.is_socket() {
+            return "socket";
+        }
+        if file_type.is_block_device() || file_type.is_char_device() {
+            return "device";
+        }
+    }
+    if file_type.is_symlink() {
+        return "symlink";
+    }
+    "special file"
+}
+
+/// Deals with a walk entry that isn't a regular file or directory: an
+/// error with `--strict`, otherwise a warning, as reading a FIFO or
+/// device could block or never end. Symlinks aren't followed; only those
+/// whose target is missing are reported.
+pub fn skip(entry: &DirEntry, strict: bool) -> Result<()> {
+    let file_type = entry.file_type();
+    let label = if file_type.is_symlink() {
+        if fs::metadata(entry.path()).is_ok() {
+            return Ok(());
+        }
+        "dangling symlink"
+    } else {
+        kind(file_type)
+    };
+
+    refuse_or_warn(entry.path(), label, strict)
+}
+
+/// Checks the input named on the command line before it is opened, as
+/// opening a FIFO blocks until something writes to it. Returns whether
+/// it should be processed: false for anything but a regular file, unless
+/// `--strict` makes that an error.
+pub fn check_input(path: &Path, strict: bool) -> Result<bool> {
+    // A missing input is left for the open to report.
+    let Ok(metadata) = fs::metadata(path) else {
+        return Ok(true);
+    };
+    if metadata.is_file() {
+        return Ok(true);
+    }
+    refuse_or_warn(path, kind(metadata.file_type()), strict)?;
+    Ok(false)
+}
+
+/// Special files skipped so far.
+pub fn skipped() -> usize {
+    SKIPPED.load(Ordering::Relaxed)
+}
+
+fn refuse_or_warn(path: &Path, label: &str, strict: bool) -> Result<()> {
+    if strict {
+        bail!("Refusing to read {}: {}", label, path.display());
+    }
+    SKIPPED.fetch_add(1, Ordering::Relaxed);
+    eprintln!("Skipping {}: {}", label, path.display());
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use walkdir::WalkDir;
+
+    #[test]
+    fn test_dangling_symlink() {
+        let dir = std::env::temp_dir().join(format!("xortool-special-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        std::os::unix::fs::symlink(dir.join("missing"), dir.join("dangling")).unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("live")).unwrap();
+
+        for entry in WalkDir::new(&dir).min_depth(1) {
+            let entry = entry.unwrap();
+            let dangling = entry.file_name() == "dangling";
+            assert_eq!(skip(&entry, true).is_err(), dangling);
+            assert!(skip(&entry, false).is_ok());
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_check_input() {
+        assert!(!check_input(Path::new("/dev/null"), false).unwrap());
+        assert!(check_input(Path::new("/dev/null"), true).is_err());
+        assert!(check_input(Path::new(file!()), true).unwrap());
+    }
+}
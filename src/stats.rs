@@ -0,0 +1,187 @@
+use anyhow::{Context, Result};
+use std::{
+    cmp::Reverse,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use crate::{cli::shorten_path, color::Stylize, humanize, i18n::Msg};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static FILES: Mutex<Vec<FileStats>> = Mutex::new(Vec::new());
+
+/// Widest the file column of the summary table gets.
+const PATH_WIDTH: usize = 50;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+    Ok,
+    Failed,
+    Skipped,
+    /// A duplicate whose output was linked to another file's.
+    Linked,
+    /// Completed, but the input changed size while it was read.
+    Unstable,
+}
+
+impl Status {
+    fn as_str(self) -> &'static str {
+        match self {
+            Status::Ok => "ok",
+            Status::Failed => "failed",
+            Status::Skipped => "skipped",
+            Status::Linked => "linked",
+            Status::Unstable => "unstable",
+        }
+    }
+}
+
+/// How one file of the run went.
+pub struct FileStats {
+    pub path: String,
+    pub bytes: u64,
+    pub duration: Duration,
+    pub status: Status,
+    /// SHA-256 of the input, when computed.
+    pub checksum: Option<String>,
+}
+
+/// Starts keeping statistics for every file that completes.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn record(
+    path: &str,
+    bytes: u64,
+    duration: Duration,
+    status: Status,
+    checksum: Option<String>,
+) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    FILES.lock().unwrap().push(FileStats {
+        path: path.to_string(),
+        bytes,
+        duration,
+        status,
+        checksum,
+    });
+}
+
+/// Prints the `limit` slowest files (all of them without a limit) with
+/// their size, time, throughput and share of the total processing time.
+pub fn print_table(limit: Option<usize>) {
+    let files = FILES.lock().unwrap();
+    if files.is_empty() {
+        return;
+    }
+    let mut files: Vec<&FileStats> = files.iter().collect();
+    files.sort_by_key(|file| Reverse(file.duration));
+    let total: Duration = files.iter().map(|file| file.duration).sum();
+    let shown = &files[..limit.unwrap_or(files.len()).min(files.len())];
+
+    let title = match limit {
+        Some(_) if shown.len() < files.len() => {
+            Msg::SlowestFiles.fill(&[&shown.len(), &files.len()])
+        }
+        _ => Msg::FilesByTime.to_string(),
+    };
+    println!("\n{}", title.bold());
+    println!(
+        "{}",
+        format!(
+            "{:>8}  {:>6}  {:>9}  {:>11}  {}",
+            Msg::Time,
+            Msg::Share,
+            Msg::Size,
+            Msg::Speed,
+            Msg::File
+        )
+        .dim()
+    );
+    for file in shown {
+        let seconds = file.duration.as_secs_f64();
+        println!(
+            "{:>7.2}s  {:>5.1}%  {:>9}  {:>11}  {}",
+            seconds,
+            share(file.duration, total),
+            humanize::size(file.bytes),
+            humanize::rate(file.bytes as f64 / seconds),
+            shorten_path(&file.path, PATH_WIDTH)
+        );
+    }
+}
+
+/// Writes one row per file, in the order they finished, for loading into
+/// a spreadsheet.
+pub fn write_csv(path: &Path) -> Result<()> {
+    let file = File::create(path)
+        .with_context(|| format!("Failed to create stats file: {}", path.display()))?;
+    let mut out = BufWriter::new(file);
+
+    writeln!(out, "path,bytes,seconds,mb_per_s,status,checksum")?;
+    for file in FILES.lock().unwrap().iter() {
+        let seconds = file.duration.as_secs_f64();
+        let speed = if seconds > 0.0 {
+            file.bytes as f64 / seconds / (1024.0 * 1024.0)
+        } else {
+            0.0
+        };
+        writeln!(
+            out,
+            "{},{},{:.6},{:.3},{},{}",
+            csv_field(&file.path),
+            file.bytes,
+            seconds,
+            speed,
+            file.status.as_str(),
+            file.checksum.as_deref().unwrap_or("")
+        )?;
+    }
+    out.flush()
+        .with_context(|| format!("Failed to write stats file: {}", path.display()))
+}
+
+/// Quotes `text` if it contains a delimiter, quote or line break.
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// `part` as a percentage of `total`.
+fn share(part: Duration, total: Duration) -> f64 {
+    if total.is_zero() {
+        return 0.0;
+    }
+    part.as_secs_f64() / total.as_secs_f64() * 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_share() {
+        let ms = Duration::from_millis;
+        assert_eq!(share(ms(800), ms(1000)), 80.0);
+        assert_eq!(share(ms(0), ms(0)), 0.0);
+    }
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("dir/file.bin"), "dir/file.bin");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
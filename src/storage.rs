@@ -0,0 +1,98 @@
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{longpath, walk::normalize_path};
+
+/// Where a `Job` reads its inputs and writes its outputs. Paths are the
+/// backend's own: a local path for `LocalStorage`, a key or remote path
+/// for others.
+pub trait Storage: fmt::Debug + Send + Sync {
+    /// `path` in the form the job compares and derives outputs from, as
+    /// `canonicalize` gives for local files. Fails if nothing is there.
+    fn resolve(&self, path: &Path) -> io::Result<PathBuf>;
+
+    fn is_dir(&self, path: &Path) -> bool;
+
+    /// Opens the file at `path` for reading, with its length.
+    fn open(&self, path: &Path) -> io::Result<(Box<dyn Read + Send>, u64)>;
+
+    /// Creates or truncates the file at `path` for writing, and any
+    /// directories it needs.
+    fn create(&self, path: &Path) -> io::Result<Box<dyn Write + Send>>;
+
+    fn remove(&self, path: &Path) -> io::Result<()>;
+
+    /// The files under the directory `root`, sorted by path. `include` is
+    /// asked about each file and directory below `root`, whether it is a
+    /// directory, and the ones it turns down are left out with everything
+    /// in them.
+    fn list(&self, root: &Path, include: &dyn Fn(&Path, bool) -> bool) -> io::Result<Vec<PathBuf>>;
+}
+
+/// The local filesystem, which jobs use unless given another backend.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LocalStorage;
+
+impl Storage for LocalStorage {
+    fn resolve(&self, path: &Path) -> io::Result<PathBuf> {
+        longpath::extended(&normalize_path(path))?.canonicalize()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn open(&self, path: &Path) -> io::Result<(Box<dyn Read + Send>, u64)> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        Ok((Box::new(file), len))
+    }
+
+    fn create(&self, path: &Path) -> io::Result<Box<dyn Write + Send>> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Ok(Box::new(File::create(path)?))
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn list(&self, root: &Path, include: &dyn Fn(&Path, bool) -> bool) -> io::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        list_dir(root, include, &mut files)?;
+        Ok(files)
+    }
+}
+
+/// Adds the files under `dir` to `files`, depth first in file name order
+/// as a sorted walk visits them. Symlinks are not followed.
+fn list_dir(
+    dir: &Path,
+    include: &dyn Fn(&Path, bool) -> bool,
+    files: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+    let with_path = |e: io::Error| io::Error::new(e.kind(), format!("{}: {}", dir.display(), e));
+    let mut entries = fs::read_dir(dir)
+        .and_then(|entries| entries.collect::<io::Result<Vec<_>>>())
+        .map_err(with_path)?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let file_type = entry.file_type().map_err(with_path)?;
+        let path = entry.path();
+        if !include(&path, file_type.is_dir()) {
+            continue;
+        }
+        if file_type.is_dir() {
+            list_dir(&path, include, files)?;
+        } else if file_type.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,426 @@
+use anyhow::{bail, Context, Result};
+use std::io::{self, Read, Write};
+
+use crate::{
+    cipher::Cipher,
+    transform::{self, read_full},
+};
+
+const BLOCK: usize = 512;
+
+/// What a member is. Only regular files carry data worth transforming.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    File,
+    Dir,
+    Other,
+}
+
+/// One entry of a tar archive.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Member {
+    /// Path inside the archive, with `/` between components.
+    pub path: String,
+    pub kind: Kind,
+    pub size: u64,
+    /// Permission bits.
+    pub mode: u32,
+    /// Seconds since the Unix epoch.
+    pub mtime: u64,
+}
+
+/// Reads the members of a ustar, GNU or pax archive as a stream. After
+/// `next_member` returns a member, the reader reads that member's data.
+pub struct Reader<R> {
+    inner: R,
+    /// Data of the current member not read yet.
+    data_left: u64,
+    /// Padding after the current member's data.
+    padding: u64,
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            data_left: 0,
+            padding: 0,
+        }
+    }
+
+    /// The next member, skipping what is left of the current one, or
+    /// `None` at the end of the archive. Long names from GNU `L` and pax
+    /// `path` records are applied to the member they describe.
+    pub fn next_member(&mut self) -> Result<Option<Member>> {
+        let mut long_path = None;
+        loop {
+            self.skip_rest()?;
+            let mut header = [0; BLOCK];
+            match read_full(&mut self.inner, &mut header)? {
+                0 => return Ok(None),
+                BLOCK => {}
+                _ => bail!("tar archive ends inside a header"),
+            }
+            if header.iter().all(|&b| b == 0) {
+                return Ok(None);
+            }
+            let expected = octal(&header[148..156]).context("tar header has no checksum")?;
+            let actual: u64 = header
+                .iter()
+                .enumerate()
+                .map(|(i, &b)| {
+                    if (148..156).contains(&i) {
+                        32
+                    } else {
+                        b as u64
+                    }
+                })
+                .sum();
+            if actual != expected {
+                bail!("tar header checksum doesn't match; the input may not be a tar archive");
+            }
+
+            let size = number(&header[124..136]).context("tar header has an invalid size")?;
+            self.data_left = size;
+            self.padding = padding(size);
+            match header[156] {
+                b'L' => {
+                    let name = self.read_data()?;
+                    long_path = Some(trim_nul(&name).to_string());
+                    continue;
+                }
+                b'x' => {
+                    let records = self.read_data()?;
+                    if let Some(path) = pax_path(&records)? {
+                        long_path = Some(path);
+                    }
+                    continue;
+                }
+                b'g' => continue,
+                _ => {}
+            }
+
+            let name = trim_nul(&header[..100]);
+            let path = match long_path.take() {
+                Some(path) => path,
+                None if &header[257..262] == b"ustar" && header[345] != 0 => {
+                    format!("{}/{}", trim_nul(&header[345..500]), name)
+                }
+                None => name.to_string(),
+            };
+            let kind = match header[156] {
+                b'0' | 0 | b'7' => Kind::File,
+                b'5' => Kind::Dir,
+                _ => Kind::Other,
+            };
+            return Ok(Some(Member {
+                path,
+                kind,
+                size,
+                mode: octal(&header[100..108]).unwrap_or(0o644) as u32,
+                mtime: number(&header[136..148]).unwrap_or(0),
+            }));
+        }
+    }
+
+    fn read_data(&mut self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        self.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    fn skip_rest(&mut self) -> io::Result<()> {
+        let rest = self.data_left + self.padding;
+        let skipped = io::copy(&mut (&mut self.inner).take(rest), &mut io::sink())?;
+        if skipped < rest {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.data_left = 0;
+        self.padding = 0;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf
+            .len()
+            .min(self.data_left.min(usize::MAX as u64) as usize);
+        if len == 0 {
+            return Ok(0);
+        }
+        let read = self.inner.read(&mut buf[..len])?;
+        if read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.data_left -= read as u64;
+        Ok(read)
+    }
+}
+
+/// Writes members into a ustar archive, with pax records for paths that
+/// don't fit its fields.
+pub struct Writer<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner }
+    }
+
+    /// Adds `member` with the first `member.size` bytes of `data` passed
+    /// through `cipher`, which starts from keystream position 0. Fails if
+    /// `data` ends early.
+    pub fn append(
+        &mut self,
+        member: &Member,
+        data: &mut impl Read,
+        cipher: &mut dyn Cipher,
+    ) -> Result<()> {
+        let (name, prefix) = match split_path(&member.path) {
+            Some(split) => split,
+            None => {
+                let record = pax_record("path", &member.path);
+                let pax = Member {
+                    path: "././@PaxHeader".into(),
+                    kind: Kind::Other,
+                    size: record.len() as u64,
+                    mode: 0o644,
+                    mtime: member.mtime,
+                };
+                self.write_header(&pax, b'x', "././@PaxHeader", "")?;
+                self.inner.write_all(&record)?;
+                self.pad(record.len() as u64)?;
+                (truncate(&member.path, 100), "")
+            }
+        };
+        let typeflag = match member.kind {
+            Kind::Dir => b'5',
+            _ => b'0',
+        };
+        self.write_header(member, typeflag, name, prefix)?;
+
+        let mut buffer = vec![0; transform::DEFAULT_BUFFER_SIZE];
+        let written = transform::stream_with(
+            &mut data.take(member.size),
+            &mut self.inner,
+            cipher,
+            &mut buffer,
+            0,
+            |_| Ok::<_, io::Error>(()),
+        )?;
+        if written != member.size {
+            bail!(
+                "{} ended after {} of {} bytes",
+                member.path,
+                written,
+                member.size
+            );
+        }
+        self.pad(member.size)
+    }
+
+    /// Ends the archive and returns the writer it went to.
+    pub fn finish(mut self) -> Result<W> {
+        self.inner.write_all(&[0; 2 * BLOCK])?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    fn write_header(
+        &mut self,
+        member: &Member,
+        typeflag: u8,
+        name: &str,
+        prefix: &str,
+    ) -> Result<()> {
+        let mut header = [0; BLOCK];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        put_octal(&mut header[100..108], u64::from(member.mode & 0o7777));
+        put_octal(&mut header[108..116], 0);
+        put_octal(&mut header[116..124], 0);
+        put_number(&mut header[124..136], member.size);
+        put_number(&mut header[136..148], member.mtime);
+        header[156] = typeflag;
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+
+        header[148..156].fill(b' ');
+        let checksum: u64 = header.iter().map(|&b| b as u64).sum();
+        put_octal(&mut header[148..155], checksum);
+        self.inner.write_all(&header)?;
+        Ok(())
+    }
+
+    fn pad(&mut self, size: u64) -> Result<()> {
+        self.inner
+            .write_all(&[0; BLOCK][..padding(size) as usize])?;
+        Ok(())
+    }
+}
+
+fn padding(size: u64) -> u64 {
+    (BLOCK as u64 - size % BLOCK as u64) % BLOCK as u64
+}
+
+fn trim_nul(field: &[u8]) -> &str {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    std::str::from_utf8(&field[..end]).unwrap_or("")
+}
+
+/// An octal field, which may be padded with spaces or NULs.
+fn octal(field: &[u8]) -> Option<u64> {
+    let text = std::str::from_utf8(field).ok()?;
+    let text = text.trim_matches(|c: char| c == ' ' || c == '\0');
+    u64::from_str_radix(text, 8).ok()
+}
+
+/// A numeric field, in octal or, with the high bit set, GNU base-256
+/// for values octal can't hold.
+fn number(field: &[u8]) -> Option<u64> {
+    if field[0] & 0x80 == 0 {
+        return octal(field);
+    }
+    let mut value = u64::from(field[0] & 0x7f);
+    for &b in &field[1..] {
+        value = value.checked_mul(256)? | u64::from(b);
+    }
+    Some(value)
+}
+
+/// Writes `value` as zero-padded octal filling all but the last byte of
+/// `field`, which is left NUL.
+fn put_octal(field: &mut [u8], value: u64) {
+    let digits = field.len() - 1;
+    let text = format!("{:0width$o}", value, width = digits);
+    field[..digits].copy_from_slice(text.as_bytes());
+}
+
+fn put_number(field: &mut [u8], value: u64) {
+    if value < 1 << (3 * (field.len() - 1)) {
+        put_octal(field, value);
+    } else {
+        field.fill(0);
+        field[0] = 0x80;
+        let bytes = value.to_be_bytes();
+        let start = field.len() - bytes.len();
+        field[start..].copy_from_slice(&bytes);
+    }
+}
+
+/// `path` as the ustar name and prefix fields, if it fits them.
+fn split_path(path: &str) -> Option<(&str, &str)> {
+    if path.len() <= 100 {
+        return Some((path, ""));
+    }
+    path.match_indices('/')
+        .map(|(i, _)| (&path[i + 1..], &path[..i]))
+        .find(|(name, prefix)| name.len() <= 100 && prefix.len() <= 155 && !name.is_empty())
+}
+
+fn truncate(text: &str, len: usize) -> &str {
+    let mut end = len.min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// A pax record, `"<length> <key>=<value>\n"`, where the length counts
+/// the whole record including its own digits.
+fn pax_record(key: &str, value: &str) -> Vec<u8> {
+    let body = format!(" {}={}\n", key, value);
+    let mut len = body.len() + 1;
+    while (len.to_string().len() + body.len()) != len {
+        len += 1;
+    }
+    format!("{}{}", len, body).into_bytes()
+}
+
+fn pax_path(records: &[u8]) -> Result<Option<String>> {
+    let mut rest = records;
+    let mut path = None;
+    while !rest.is_empty() {
+        let space = rest
+            .iter()
+            .position(|&b| b == b' ')
+            .context("malformed pax record")?;
+        let len: usize = std::str::from_utf8(&rest[..space])?
+            .parse()
+            .context("malformed pax record length")?;
+        if len <= space || len > rest.len() {
+            bail!("malformed pax record length");
+        }
+        let record = std::str::from_utf8(&rest[space + 1..len - 1])?;
+        if let Some(value) = record.strip_prefix("path=") {
+            path = Some(value.to_string());
+        }
+        rest = &rest[len..];
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cipher::Xor;
+
+    #[test]
+    fn test_tar_round_trip() {
+        let long = format!("{}/{}", "d".repeat(120), "f".repeat(20));
+        let longer = "x".repeat(300);
+        let files: [(&str, Vec<u8>); 4] = [
+            ("a.txt", b"hello".to_vec()),
+            ("dir/empty", Vec::new()),
+            (&long, vec![1; 700]),
+            (&longer, vec![2; 512]),
+        ];
+
+        let mut xor = Xor::new([0x5a]);
+        let mut writer = Writer::new(Vec::new());
+        for (path, data) in &files {
+            let member = Member {
+                path: path.to_string(),
+                kind: Kind::File,
+                size: data.len() as u64,
+                mode: 0o640,
+                mtime: 1_700_000_000,
+            };
+            writer.append(&member, &mut &data[..], &mut xor).unwrap();
+        }
+        let archive = writer.finish().unwrap();
+        assert_eq!(archive.len() % BLOCK, 0);
+
+        let mut reader = Reader::new(&archive[..]);
+        for (path, data) in &files {
+            let member = reader.next_member().unwrap().unwrap();
+            assert_eq!(member.path, *path);
+            assert_eq!(
+                (member.kind, member.mode, member.mtime),
+                (Kind::File, 0o640, 1_700_000_000)
+            );
+            let mut decrypted = Vec::new();
+            reader.read_to_end(&mut decrypted).unwrap();
+            xor.apply(0, &mut decrypted);
+            assert_eq!(decrypted, *data);
+        }
+        assert!(reader.next_member().unwrap().is_none());
+
+        let mut short = Writer::new(Vec::new());
+        let member = Member {
+            path: "short".into(),
+            kind: Kind::File,
+            size: 10,
+            mode: 0o644,
+            mtime: 0,
+        };
+        assert!(short.append(&member, &mut &b"abc"[..], &mut xor).is_err());
+
+        let mut field = [0; 12];
+        put_number(&mut field, 1 << 40);
+        assert_eq!(number(&field), Some(1 << 40));
+        assert_eq!(pax_record("path", "a"), b"9 path=a\n");
+    }
+}
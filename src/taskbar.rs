@@ -0,0 +1,58 @@
+use std::{
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicBool, AtomicU16, Ordering},
+        Mutex,
+    },
+};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static TITLE: Mutex<String> = Mutex::new(String::new());
+
+/// Last state sent, as percent plus `PAUSED_FLAG`, so that repeated
+/// updates with the same whole percentage don't write anything.
+static LAST: AtomicU16 = AtomicU16::new(u16::MAX);
+const PAUSED_FLAG: u16 = 0x100;
+
+/// Starts reporting progress in the terminal's tab title and, through
+/// OSC 9;4, its taskbar entry. The current title is saved to be put back
+/// by `finish`.
+pub fn enable(title: &str) {
+    *TITLE.lock().unwrap() = title.to_string();
+    ENABLED.store(true, Ordering::Relaxed);
+    emit("\x1b[22;0t");
+}
+
+pub fn update(percent: f64, paused: bool) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let percent = percent.clamp(0.0, 100.0) as u16;
+    let state = percent | if paused { PAUSED_FLAG } else { 0 };
+    if LAST.swap(state, Ordering::Relaxed) == state {
+        return;
+    }
+
+    // OSC 9;4 states: 1 is normal progress, 4 is paused (shown as a
+    // warning).
+    emit(&format!(
+        "\x1b]9;4;{};{}\x07\x1b]0;{}% {}\x07",
+        if paused { 4 } else { 1 },
+        percent,
+        percent,
+        TITLE.lock().unwrap()
+    ));
+}
+
+/// Clears the progress indicator and restores the saved title.
+pub fn finish() {
+    if ENABLED.swap(false, Ordering::Relaxed) {
+        emit("\x1b]9;4;0;0\x07\x1b[23;0t");
+    }
+}
+
+fn emit(sequence: &str) {
+    let mut stdout = io::stdout().lock();
+    let _ = stdout.write_all(sequence.as_bytes());
+    let _ = stdout.flush();
+}
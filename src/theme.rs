@@ -0,0 +1,259 @@
+use anyhow::{bail, Result};
+use crossterm::style::{Color, Stylize};
+use std::sync::OnceLock;
+
+use crate::{
+    color::{self, paint},
+    config::Config,
+    tui,
+};
+
+/// Partially filled braille cells, from one dot to seven.
+const BRAILLE_STEPS: [char; 7] = ['⡀', '⡄', '⡆', '⡇', '⣇', '⣧', '⣷'];
+
+const SPINNER_FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const ASCII_SPINNER_FRAMES: [char; 4] = ['|', '/', '-', '\\'];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BarStyle {
+    Ascii,
+    Blocks,
+    Braille,
+}
+
+/// How progress is drawn, from the `[progress]` config table.
+///
+/// In `ascii` mode only ASCII characters are printed and the cursor is
+/// never moved, for dumb terminals, serial consoles and log viewers.
+#[derive(Clone, Debug)]
+pub struct Theme {
+    pub style: BarStyle,
+    pub width: usize,
+    pub filled: Color,
+    pub empty: Color,
+    pub ascii: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            style: BarStyle::Blocks,
+            width: 20,
+            filled: Color::DarkCyan,
+            empty: Color::DarkGrey,
+            ascii: false,
+        }
+    }
+}
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+impl Theme {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let mut theme = Self::default();
+
+        if let Some(name) = config.get_str("progress.style")? {
+            theme.style = match name {
+                "ascii" => BarStyle::Ascii,
+                "blocks" => BarStyle::Blocks,
+                "braille" => BarStyle::Braille,
+                _ => bail!(
+                    "{}unknown progress.style `{}` (expected ascii, blocks or braille)",
+                    config.location(),
+                    name
+                ),
+            };
+        }
+        if let Some(width) = config.get_int("progress.width")? {
+            if !(1..=200).contains(&width) {
+                bail!("{}progress.width must be 1-200", config.location());
+            }
+            theme.width = width as usize;
+        }
+        for (key, color) in [
+            ("progress.filled_color", &mut theme.filled),
+            ("progress.empty_color", &mut theme.empty),
+        ] {
+            if let Some(name) = config.get_str(key)? {
+                *color = Color::try_from(name).or_else(|_| {
+                    bail!("{}unknown color `{}` for {}", config.location(), name, key)
+                })?;
+            }
+        }
+
+        Ok(theme)
+    }
+
+    /// Switches to ASCII output; the bar style follows.
+    pub fn set_ascii(&mut self) {
+        self.ascii = true;
+        self.style = BarStyle::Ascii;
+    }
+
+    /// Makes this the theme returned by `current` for the rest of the run.
+    pub fn install(self) {
+        let _ = THEME.set(self);
+    }
+
+    /// Whether progress can be redrawn in place: stdout is a terminal that
+    /// understands cursor movement and the dashboard isn't using it.
+    pub fn live(&self) -> bool {
+        !self.ascii && !tui::active() && atty::is(atty::Stream::Stdout)
+    }
+
+    pub fn running(&self) -> &'static str {
+        if self.ascii {
+            ">"
+        } else {
+            "▶"
+        }
+    }
+
+    pub fn paused(&self) -> &'static str {
+        if self.ascii {
+            "||"
+        } else {
+            "⏸"
+        }
+    }
+
+    pub fn done(&self) -> &'static str {
+        if self.ascii {
+            "OK"
+        } else {
+            "✓"
+        }
+    }
+
+    pub fn total(&self) -> &'static str {
+        if self.ascii {
+            "="
+        } else {
+            "Σ"
+        }
+    }
+
+    pub fn spinner(&self, frame: usize) -> char {
+        let frames: &[char] = if self.ascii {
+            &ASCII_SPINNER_FRAMES
+        } else {
+            &SPINNER_FRAMES
+        };
+        frames[frame % frames.len()]
+    }
+
+    pub fn current() -> &'static Theme {
+        THEME.get_or_init(Theme::default)
+    }
+
+    /// Renders a bar `percent` full.
+    pub fn bar(&self, percent: u8) -> String {
+        let percent = percent.min(100) as usize;
+        let (filled, partial, empty) = match self.style {
+            BarStyle::Ascii => {
+                let filled = (percent * self.width + 50) / 100;
+                ("#".repeat(filled), None, "-".repeat(self.width - filled))
+            }
+            BarStyle::Blocks => {
+                let filled = (percent * self.width + 50) / 100;
+                // Without colors the two halves need different glyphs.
+                let empty = if color::enabled() { "■" } else { "□" };
+                ("■".repeat(filled), None, empty.repeat(self.width - filled))
+            }
+            BarStyle::Braille => {
+                let steps = percent * self.width * 8 / 100;
+                let filled = steps / 8;
+                let partial = match steps % 8 {
+                    0 => None,
+                    dots => Some(BRAILLE_STEPS[dots - 1]),
+                };
+                let empty = self.width - filled - partial.is_some() as usize;
+                ("⣿".repeat(filled), partial, "⣀".repeat(empty))
+            }
+        };
+
+        let partial = partial.map(|c| c.to_string()).unwrap_or_default();
+        match self.style {
+            BarStyle::Ascii => format!(
+                "[{}{}]",
+                paint(filled + &partial, |style| style.with(self.filled)),
+                paint(empty, |style| style.with(self.empty))
+            ),
+            _ => format!(
+                "{}{}",
+                paint(filled + &partial, |style| style.with(self.filled)),
+                paint(empty, |style| style.with(self.empty))
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain(theme: &Theme, percent: u8) -> String {
+        let bar = theme.bar(percent);
+        // Drop the color escapes.
+        let mut out = String::new();
+        let mut chars = bar.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|&c| c == 'm');
+            } else {
+                out.push(c);
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_bar_styles() {
+        let mut theme = Theme {
+            width: 10,
+            ..Theme::default()
+        };
+        assert_eq!(plain(&theme, 50).chars().count(), 10);
+
+        theme.style = BarStyle::Ascii;
+        assert_eq!(plain(&theme, 0), "[----------]");
+        assert_eq!(plain(&theme, 50), "[#####-----]");
+        assert_eq!(plain(&theme, 100), "[##########]");
+
+        theme.style = BarStyle::Braille;
+        assert_eq!(plain(&theme, 0), "⣀".repeat(10));
+        assert_eq!(plain(&theme, 25), "⣿⣿⡇⣀⣀⣀⣀⣀⣀⣀");
+        assert_eq!(plain(&theme, 100), "⣿".repeat(10));
+    }
+
+    #[test]
+    fn test_from_config() {
+        let config =
+            Config::parse("[progress]\nstyle = \"ascii\"\nwidth = 40\nfilled_color = \"green\"")
+                .unwrap();
+        let theme = Theme::from_config(&config).unwrap();
+        assert_eq!(theme.style, BarStyle::Ascii);
+        assert_eq!(theme.width, 40);
+        assert_eq!(theme.filled, Color::Green);
+
+        for bad in [
+            "progress.style = \"dots\"",
+            "progress.width = 0",
+            "progress.empty_color = \"mauve\"",
+        ] {
+            assert!(Theme::from_config(&Config::parse(bad).unwrap()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_ascii_glyphs() {
+        let mut theme = Theme::default();
+        theme.set_ascii();
+        assert!(!theme.live());
+        assert!(matches!(theme.style, BarStyle::Ascii));
+        for glyph in [theme.running(), theme.paused(), theme.done(), theme.total()] {
+            assert!(glyph.is_ascii());
+        }
+        assert!((0..8).all(|frame| theme.spinner(frame).is_ascii()));
+    }
+}
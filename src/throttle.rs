@@ -0,0 +1,55 @@
+use std::{
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Token-bucket limiter shared by everything that reads or writes during a
+/// run. Callers report bytes after moving them and are put to sleep long
+/// enough to keep the average at the configured rate.
+pub struct RateLimiter {
+    bucket: Option<Mutex<Bucket>>,
+}
+
+struct Bucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// How much unused allowance may accumulate, as a fraction of one second.
+const BURST: f64 = 0.25;
+
+impl RateLimiter {
+    pub fn new(bytes_per_sec: Option<u64>) -> Self {
+        let bucket = bytes_per_sec.map(|rate| {
+            let rate = rate as f64;
+            Mutex::new(Bucket {
+                rate,
+                capacity: rate * BURST,
+                tokens: rate * BURST,
+                last_refill: Instant::now(),
+            })
+        });
+        Self { bucket }
+    }
+
+    pub fn consume(&self, bytes: u64) {
+        let Some(bucket) = &self.bucket else {
+            return;
+        };
+        let mut bucket = bucket.lock().unwrap_or_else(|e| e.into_inner());
+
+        let now = Instant::now();
+        let refill = now.duration_since(bucket.last_refill).as_secs_f64() * bucket.rate;
+        bucket.tokens = (bucket.tokens + refill).min(bucket.capacity) - bytes as f64;
+        bucket.last_refill = now;
+
+        // Sleeping with the lock held serializes waiters, which is what a
+        // single global cap wants.
+        if bucket.tokens < 0.0 {
+            thread::sleep(Duration::from_secs_f64(-bucket.tokens / bucket.rate));
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use anyhow::{Context, Result};
+use std::{
+    fmt,
+    fs::File,
+    io::{self, LineWriter, Read, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, OnceLock,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Variable naming a trace file, which turns tracing on without `-vvv`.
+pub const ENV_VAR: &str = "XORTOOL_TRACE";
+
+/// Where `-vvv` traces to when `ENV_VAR` isn't set.
+pub const DEFAULT_PATH: &str = "xortool-trace.log";
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static START: OnceLock<Instant> = OnceLock::new();
+static OUTPUT: Mutex<Option<LineWriter<File>>> = Mutex::new(None);
+
+/// Starts logging every read and write to `path`. Lines are written as
+/// they happen, so the last one shows where a stalled run is stuck.
+pub fn enable(path: &Path) -> Result<()> {
+    let file = File::create(path)
+        .with_context(|| format!("Failed to create trace file: {}", path.display()))?;
+    *OUTPUT.lock().unwrap() = Some(LineWriter::new(file));
+    START.get_or_init(Instant::now);
+    ENABLED.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Logs `op` on `file`, as seconds since tracing started, the thread,
+/// the operation and its details.
+pub fn log(file: &str, op: &str, details: fmt::Arguments) {
+    if !enabled() {
+        return;
+    }
+    let at = START.get().map_or(Duration::ZERO, Instant::elapsed);
+    let thread = thread::current();
+    let thread = match thread.name() {
+        Some(name) => name.to_string(),
+        None => format!("{:?}", thread.id()),
+    };
+    if let Some(output) = OUTPUT.lock().unwrap().as_mut() {
+        let _ = writeln!(
+            output,
+            "{:>12.6} {:<12} {:<8} {} {}",
+            at.as_secs_f64(),
+            thread,
+            op,
+            file,
+            details
+        );
+    }
+}
+
+/// Logs each read or write through it with its offset, length and time
+/// taken.
+pub struct Traced<T> {
+    inner: T,
+    file: String,
+    offset: u64,
+}
+
+impl<T> Traced<T> {
+    pub fn new(inner: T, file: &str) -> Self {
+        Self {
+            inner,
+            file: file.to_string(),
+            offset: 0,
+        }
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    fn record(&mut self, op: &str, result: &io::Result<usize>, start: Option<Instant>) {
+        let Some(start) = start else {
+            return;
+        };
+        let took = start.elapsed().as_secs_f64();
+        match result {
+            Ok(len) => log(
+                &self.file,
+                op,
+                format_args!("offset={} len={} took={:.6}s", self.offset, len, took),
+            ),
+            Err(e) => log(
+                &self.file,
+                op,
+                format_args!("offset={} error=\"{}\" took={:.6}s", self.offset, e, took),
+            ),
+        }
+    }
+}
+
+impl<T: Read> Read for Traced<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let start = enabled().then(Instant::now);
+        let result = self.inner.read(buf);
+        self.record("read", &result, start);
+        self.offset += *result.as_ref().unwrap_or(&0) as u64;
+        result
+    }
+}
+
+impl<T: Write> Write for Traced<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let start = enabled().then(Instant::now);
+        let result = self.inner.write(buf);
+        self.record("write", &result, start);
+        self.offset += *result.as_ref().unwrap_or(&0) as u64;
+        result
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traced_offsets() {
+        let mut reader = Traced::new(&[1u8, 2, 3, 4, 5][..], "input");
+        let mut buffer = [0; 2];
+        reader.read_exact(&mut buffer).unwrap();
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(reader.offset, 4);
+
+        let mut writer = Traced::new(Vec::new(), "output");
+        writer.write_all(&[7; 3]).unwrap();
+        assert_eq!((writer.offset, writer.get_ref().len()), (3, 3));
+    }
+}
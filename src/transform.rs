@@ -0,0 +1,461 @@
+use std::io::{self, Read, Write};
+
+use crate::{
+    cipher::{Cipher, Xor},
+    core::key_phase,
+    simd,
+};
+
+/// Buffer size the CLI uses unless told otherwise.
+pub const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Most of a caller's buffer `XorWriter` transforms per write, which
+/// bounds the copy it keeps.
+const WRITE_CHUNK: usize = 64 * 1024;
+
+/// XOR `data` with the repeating `key`, where `offset` is the position of
+/// `data[0]` within the file so chunk boundaries don't shift the keystream.
+pub fn xor_encrypt(data: &mut [u8], key: &[u8], offset: u64) {
+    if key.is_empty() {
+        return;
+    }
+
+    simd::xor_keystream(data, key, key_phase(key.len(), offset));
+}
+
+/// Transforms `reader` into `writer` until the reader ends, starting at
+/// keystream position `offset`. `progress` gets the position after each
+/// chunk and stops the transform by returning an error, of whatever type
+/// I/O errors convert to. Returns the position reached.
+pub fn stream<E: From<io::Error>>(
+    reader: &mut impl Read,
+    writer: &mut impl Write,
+    key: &[u8],
+    buffer: &mut [u8],
+    offset: u64,
+    progress: impl FnMut(u64) -> Result<(), E>,
+) -> Result<u64, E> {
+    stream_with(reader, writer, &mut Xor::new(key), buffer, offset, progress)
+}
+
+/// `stream` with any cipher in place of XOR.
+pub fn stream_with<E: From<io::Error>>(
+    reader: &mut impl Read,
+    writer: &mut impl Write,
+    cipher: &mut dyn Cipher,
+    buffer: &mut [u8],
+    mut offset: u64,
+    mut progress: impl FnMut(u64) -> Result<(), E>,
+) -> Result<u64, E> {
+    loop {
+        let read_count = reader.read(buffer)?;
+        if read_count == 0 {
+            break;
+        }
+        cipher.apply(offset, &mut buffer[..read_count]);
+        writer.write_all(&buffer[..read_count])?;
+        offset += read_count as u64;
+        progress(offset)?;
+    }
+    writer.flush()?;
+    Ok(offset)
+}
+
+/// Transforms a reader in chunks handed out one at a time, so the caller
+/// sets the pace and can do its own work between them. As an iterator it
+/// yields owned chunks and ends after the first error; `next_chunk`
+/// lends each chunk from an internal buffer instead.
+pub struct EncryptStream<R> {
+    reader: R,
+    key: Vec<u8>,
+    offset: u64,
+    buffer: Vec<u8>,
+    done: bool,
+}
+
+impl<R: Read> EncryptStream<R> {
+    pub fn new(reader: R, key: &[u8]) -> Self {
+        Self::with_chunk_size(reader, key, DEFAULT_BUFFER_SIZE)
+    }
+
+    /// A stream whose chunks are `chunk_size` bytes, except the last.
+    pub fn with_chunk_size(reader: R, key: &[u8], chunk_size: usize) -> Self {
+        Self {
+            reader,
+            key: key.to_vec(),
+            offset: 0,
+            buffer: vec![0; chunk_size.max(1)],
+            done: false,
+        }
+    }
+
+    /// The next transformed chunk, or `None` once the reader is exhausted.
+    pub fn next_chunk(&mut self) -> io::Result<Option<&[u8]>> {
+        if self.done {
+            return Ok(None);
+        }
+        let len =
+            read_full(&mut self.reader, &mut self.buffer).inspect_err(|_| self.done = true)?;
+        if len == 0 {
+            self.done = true;
+            return Ok(None);
+        }
+        let chunk = &mut self.buffer[..len];
+        xor_encrypt(chunk, &self.key, self.offset);
+        self.offset += len as u64;
+        Ok(Some(chunk))
+    }
+
+    /// Bytes transformed so far.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> Iterator for EncryptStream<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_chunk()
+            .map(|chunk| chunk.map(<[u8]>::to_vec))
+            .transpose()
+    }
+}
+
+/// Reads until `buffer` is full or the input ends, returning the length
+/// read.
+pub(crate) fn read_full(reader: &mut impl Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// A reader that applies the keystream to everything read through it, so
+/// encryption can sit in any I/O pipeline: wrap a socket, a decompressor
+/// or a file and read plaintext or ciphertext out of it.
+pub struct XorReader<R> {
+    inner: R,
+    key: Vec<u8>,
+    offset: u64,
+}
+
+impl<R> XorReader<R> {
+    pub fn new(inner: R, key: &[u8]) -> Self {
+        Self::with_offset(inner, key, 0)
+    }
+
+    /// A reader whose first byte is at keystream position `offset`, for
+    /// picking a stream up partway.
+    pub fn with_offset(inner: R, key: &[u8], offset: u64) -> Self {
+        Self {
+            inner,
+            key: key.to_vec(),
+            offset,
+        }
+    }
+
+    /// Keystream position of the next byte read.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for XorReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read_count = self.inner.read(buf)?;
+        xor_encrypt(&mut buf[..read_count], &self.key, self.offset);
+        self.offset += read_count as u64;
+        Ok(read_count)
+    }
+}
+
+/// A writer that applies the keystream to everything written through it
+/// before passing it on, leaving the caller's buffers untouched.
+pub struct XorWriter<W> {
+    inner: W,
+    key: Vec<u8>,
+    offset: u64,
+    scratch: Vec<u8>,
+}
+
+impl<W> XorWriter<W> {
+    pub fn new(inner: W, key: &[u8]) -> Self {
+        Self::with_offset(inner, key, 0)
+    }
+
+    /// A writer whose first byte is at keystream position `offset`, for
+    /// appending to a stream written earlier.
+    pub fn with_offset(inner: W, key: &[u8], offset: u64) -> Self {
+        Self {
+            inner,
+            key: key.to_vec(),
+            offset,
+            scratch: Vec::new(),
+        }
+    }
+
+    /// Keystream position of the next byte written.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for XorWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(WRITE_CHUNK);
+        self.scratch.clear();
+        self.scratch.extend_from_slice(&buf[..len]);
+        xor_encrypt(&mut self.scratch, &self.key, self.offset);
+        // Only what the inner writer took counts; the rest is transformed
+        // again from the caller's data on the next write.
+        let written = self.inner.write(&self.scratch)?;
+        self.offset += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// xorshift64*, enough to drive the property tests without a crate.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+
+        fn bytes(&mut self, len: usize) -> Vec<u8> {
+            (0..len).map(|_| self.next() as u8).collect()
+        }
+    }
+
+    /// Hands out its data in reads of random length, as pipes and
+    /// network filesystems do.
+    struct ShortReads<'a>(&'a [u8], &'a mut Rng);
+
+    impl Read for ShortReads<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = self.0.len().min(buf.len()).min(1 + self.1.below(300));
+            buf[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn test_round_trip_properties() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..500 {
+            let (key_len, plain_len) = (1 + rng.below(70), rng.below(2000));
+            let key = rng.bytes(key_len);
+            let plain = rng.bytes(plain_len);
+            let offset = rng.next() % 10_000;
+            let mut buffer = vec![0; 1 + rng.below(200)];
+
+            // Decrypting what was encrypted gives the input back, however
+            // the reads and buffers split it.
+            let mut cipher = Vec::new();
+            let mut reader = ShortReads(&plain, &mut rng);
+            stream(&mut reader, &mut cipher, &key, &mut buffer, offset, |_| {
+                Ok::<_, io::Error>(())
+            })
+            .unwrap();
+            let mut round_trip = Vec::new();
+            let mut reader = ShortReads(&cipher, &mut rng);
+            stream(
+                &mut reader,
+                &mut round_trip,
+                &key,
+                &mut buffer,
+                offset,
+                |_| Ok::<_, io::Error>(()),
+            )
+            .unwrap();
+            assert_eq!(round_trip, plain);
+
+            // The SIMD paths agree with the byte-wise reference.
+            let mut reference = plain.clone();
+            crate::core::xor(&mut reference, &key, offset);
+            assert_eq!(cipher, reference);
+
+            // A key written as hex parses back to itself.
+            assert_eq!(crate::key::parse_hex_key(&hex::encode(&key)).unwrap(), key);
+        }
+    }
+
+    #[test]
+    fn test_hex_parsers_agree() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        let alphabet = b"0123456789abcdefABCDEFxX g";
+        let mut buffer = [0; 64];
+        for _ in 0..5000 {
+            let text: String = (0..rng.below(12))
+                .map(|_| alphabet[rng.below(alphabet.len())] as char)
+                .collect();
+            let parsed = crate::key::parse_hex_key(&text);
+            let decoded = crate::core::decode_hex_key(&text, &mut buffer);
+            match (&parsed, &decoded) {
+                (Ok(key), Ok(same)) => assert_eq!(key, same),
+                (Err(_), Err(_)) => {}
+                _ => panic!("{:?}: {:?} but {:?}", text, parsed, decoded),
+            }
+        }
+    }
+
+    #[test]
+    fn test_xor_chunk_offsets() {
+        let key = [0x11, 0x22, 0x33];
+        let mut whole: Vec<u8> = (0..100).collect();
+        let mut chunked = whole.clone();
+
+        xor_encrypt(&mut whole, &key, 0);
+        let (head, tail) = chunked.split_at_mut(64);
+        xor_encrypt(head, &key, 0);
+        xor_encrypt(tail, &key, 64);
+
+        assert_eq!(whole, chunked);
+    }
+
+    #[test]
+    fn test_stream_round_trip() {
+        let key = [0xde, 0xad, 0xbe, 0xef, 0x01];
+        let plain: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+        let mut buffer = [0; 64];
+
+        let mut cipher = Vec::new();
+        let mut positions = Vec::new();
+        let end = stream(&mut &plain[..], &mut cipher, &key, &mut buffer, 0, |at| {
+            positions.push(at);
+            Ok::<_, io::Error>(())
+        })
+        .unwrap();
+        assert_eq!(end, 1000);
+        assert_eq!(positions.len(), 16);
+
+        let mut round_trip = Vec::new();
+        stream(
+            &mut &cipher[..],
+            &mut round_trip,
+            &key,
+            &mut buffer,
+            0,
+            |_| Ok::<_, io::Error>(()),
+        )
+        .unwrap();
+        assert_eq!(round_trip, plain);
+    }
+
+    /// Takes at most 7 bytes per write, as sockets and pipes may.
+    struct Trickle(Vec<u8>);
+
+    impl Write for Trickle {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let len = buf.len().min(7);
+            self.0.extend_from_slice(&buf[..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_adapters_round_trip() {
+        let key = [0x13, 0x57, 0x9b];
+        let plain: Vec<u8> = (0..500u32).map(|i| (i * 3) as u8).collect();
+        let mut expected = plain.clone();
+        xor_encrypt(&mut expected, &key, 0);
+
+        let mut writer = XorWriter::new(Trickle(Vec::new()), &key);
+        writer.write_all(&plain).unwrap();
+        assert_eq!(writer.offset(), 500);
+        let cipher = writer.into_inner().0;
+        assert_eq!(cipher, expected);
+
+        let mut reader = XorReader::new(&cipher[..], &key);
+        let mut head = [0; 10];
+        reader.read_exact(&mut head).unwrap();
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!([&head[..], &rest].concat(), plain);
+
+        let mut tail = XorReader::with_offset(&cipher[100..], &key, 100);
+        let mut decrypted = Vec::new();
+        tail.read_to_end(&mut decrypted).unwrap();
+        assert_eq!(decrypted, plain[100..]);
+    }
+
+    #[test]
+    fn test_encrypt_stream() {
+        let key = [0x42, 0x24];
+        let plain: Vec<u8> = (0..250u8).collect();
+        let mut expected = plain.clone();
+        xor_encrypt(&mut expected, &key, 0);
+
+        let chunks: Vec<_> = EncryptStream::with_chunk_size(&plain[..], &key, 100)
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            chunks.iter().map(Vec::len).collect::<Vec<_>>(),
+            [100, 100, 50]
+        );
+        assert_eq!(chunks.concat(), expected);
+
+        let mut stream = EncryptStream::with_chunk_size(&plain[..], &key, 64);
+        let mut lent = Vec::new();
+        while let Some(chunk) = stream.next_chunk().unwrap() {
+            lent.extend_from_slice(chunk);
+        }
+        assert_eq!(lent, expected);
+        assert_eq!(stream.offset(), 250);
+    }
+}
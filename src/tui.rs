@@ -0,0 +1,385 @@
+use anyhow::{bail, Result};
+use crossterm::{
+    cursor, execute, queue,
+    terminal::{self, ClearType},
+};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    cli::shorten_path, color::Stylize, estimate::Throughput, humanize, i18n::Msg, monitor::Event,
+    pause, resize, theme::Theme,
+};
+
+const REDRAW_INTERVAL: Duration = Duration::from_millis(250);
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Throughput samples kept for the sparkline, one per `SAMPLE_INTERVAL`.
+const HISTORY: usize = 240;
+
+/// Lines kept for the log and error panels.
+const KEPT_LINES: usize = 100;
+
+/// Errors shown at most, so that a burst of them can't push everything
+/// else off the screen.
+const MAX_ERROR_LINES: usize = 5;
+
+const SPARK: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const ASCII_SPARK: [char; 8] = ['_', '.', ',', ':', '-', '=', '+', '#'];
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static DASHBOARD: Mutex<Option<Dashboard>> = Mutex::new(None);
+
+/// Everything the dashboard shows, fed by run events.
+struct Dashboard {
+    title: String,
+    start: Instant,
+    files_total: usize,
+    bytes_total: u64,
+    files_done: usize,
+    bytes_done: u64,
+    failed: usize,
+    in_flight: BTreeMap<String, (u64, u64)>,
+    throughput: Throughput,
+    samples: VecDeque<f64>,
+    last_sample: (Instant, u64),
+    log: VecDeque<String>,
+    errors: VecDeque<String>,
+}
+
+/// The full-screen dashboard for `--tui`. It is redrawn on its own thread
+/// until dropped, which restores the screen and prints a summary.
+pub struct Tui {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// Switches to the alternate screen and starts drawing the dashboard.
+pub fn start(title: &str) -> Result<Tui> {
+    if !atty::is(atty::Stream::Stdout) {
+        bail!("--tui needs stdout to be a terminal");
+    }
+
+    *DASHBOARD.lock().unwrap() = Some(Dashboard {
+        title: title.to_string(),
+        start: Instant::now(),
+        files_total: 0,
+        bytes_total: 0,
+        files_done: 0,
+        bytes_done: 0,
+        failed: 0,
+        in_flight: BTreeMap::new(),
+        throughput: Throughput::new(),
+        samples: VecDeque::new(),
+        last_sample: (Instant::now(), 0),
+        log: VecDeque::new(),
+        errors: VecDeque::new(),
+    });
+    execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+    ACTIVE.store(true, Ordering::Relaxed);
+
+    #[cfg(unix)]
+    for signal in [signal_hook::consts::SIGINT, signal_hook::consts::SIGTERM] {
+        // SAFETY: the action only reads an atomic and calls write(2),
+        // which is async-signal-safe. It is registered before the pause
+        // listener's, which counts the interrupt and on the second one
+        // ends the process, so it runs first and sees the earlier count.
+        let _ = unsafe {
+            signal_hook::low_level::register(signal, || {
+                if pause::interrupts() > 0 {
+                    const LEAVE: &[u8] = b"\x1b[?1049l\x1b[?25h";
+                    libc::write(libc::STDOUT_FILENO, LEAVE.as_ptr().cast(), LEAVE.len());
+                }
+            })
+        };
+    }
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread = {
+        let stop = Arc::clone(&stop);
+        thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                let _ = draw();
+                thread::sleep(REDRAW_INTERVAL);
+            }
+        })
+    };
+
+    Ok(Tui {
+        stop,
+        thread: Some(thread),
+    })
+}
+
+pub fn active() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+/// Sets the size of the whole run, once known.
+pub fn set_totals(files: usize, bytes: u64) {
+    if let Some(dashboard) = DASHBOARD.lock().unwrap().as_mut() {
+        dashboard.files_total = files;
+        dashboard.bytes_total = bytes;
+    }
+}
+
+pub fn observe(event: &Event) {
+    let mut dashboard = DASHBOARD.lock().unwrap();
+    let Some(dashboard) = dashboard.as_mut() else {
+        return;
+    };
+
+    match *event {
+        Event::Progress {
+            file,
+            processed,
+            total,
+        } => {
+            dashboard
+                .in_flight
+                .insert(file.to_string(), (processed, total));
+        }
+        Event::Done { file, bytes, .. } => {
+            dashboard.in_flight.remove(file);
+            dashboard.files_done += 1;
+            dashboard.bytes_done += bytes;
+        }
+        Event::Failed { file, error } => {
+            dashboard.in_flight.remove(file);
+            dashboard.failed += 1;
+            push_line(&mut dashboard.errors, format!("{}: {}", file, error));
+        }
+        Event::Skipped { file } => {
+            dashboard.in_flight.remove(file);
+            dashboard.files_done += 1;
+            push_line(&mut dashboard.log, format!("{} {}", Msg::Skipped, file));
+        }
+        Event::Finished { .. } => {}
+    }
+}
+
+/// Adds a line to the dashboard's log panel.
+pub fn log(line: &str) {
+    if let Some(dashboard) = DASHBOARD.lock().unwrap().as_mut() {
+        push_line(&mut dashboard.log, line.to_string());
+    }
+}
+
+/// Leaves the alternate screen, for exits that skip dropping the `Tui`.
+pub fn restore() {
+    if active() {
+        let _ = execute!(io::stdout(), terminal::LeaveAlternateScreen, cursor::Show);
+    }
+}
+
+fn push_line(lines: &mut VecDeque<String>, line: String) {
+    if lines.len() == KEPT_LINES {
+        lines.pop_front();
+    }
+    lines.push_back(line);
+}
+
+fn draw() -> Result<()> {
+    let (_, height) = terminal::size()?;
+    let width = resize::width();
+    let lines = {
+        let mut dashboard = DASHBOARD.lock().unwrap();
+        let Some(dashboard) = dashboard.as_mut() else {
+            return Ok(());
+        };
+        dashboard.sample();
+        dashboard.render(width, height as usize)
+    };
+
+    let mut stdout = io::stdout();
+    for (y, line) in lines.iter().enumerate() {
+        queue!(
+            stdout,
+            cursor::MoveTo(0, y as u16),
+            terminal::Clear(ClearType::CurrentLine)
+        )?;
+        write!(stdout, "{}", resize::fit(line))?;
+    }
+    queue!(
+        stdout,
+        cursor::MoveTo(0, lines.len() as u16),
+        terminal::Clear(ClearType::FromCursorDown)
+    )?;
+    stdout.flush()?;
+    Ok(())
+}
+
+impl Dashboard {
+    fn done_bytes(&self) -> u64 {
+        self.bytes_done + self.in_flight.values().map(|(done, _)| done).sum::<u64>()
+    }
+
+    /// Takes a throughput sample once per `SAMPLE_INTERVAL`.
+    fn sample(&mut self) {
+        let done = self.done_bytes();
+        self.throughput.update(done);
+
+        let (at, bytes) = self.last_sample;
+        let elapsed = at.elapsed();
+        if elapsed >= SAMPLE_INTERVAL {
+            if self.samples.len() == HISTORY {
+                self.samples.pop_front();
+            }
+            self.samples
+                .push_back(done.saturating_sub(bytes) as f64 / elapsed.as_secs_f64());
+            self.last_sample = (Instant::now(), done);
+        }
+    }
+
+    fn render(&self, width: usize, height: usize) -> Vec<String> {
+        let theme = Theme::current();
+        let done = self.done_bytes();
+        let percent = if self.bytes_total > 0 {
+            (done as f64 / self.bytes_total as f64 * 100.0).min(100.0)
+        } else {
+            0.0
+        };
+        let state = if pause::is_paused() {
+            Msg::Paused.yellow().to_string()
+        } else {
+            format!(
+                "{:.0?}",
+                Duration::from_secs(self.start.elapsed().as_secs())
+            )
+        };
+
+        let mut lines = vec![
+            format!(
+                "{} {}  {}",
+                "xortool".bold(),
+                self.title.clone().dim(),
+                state
+            ),
+            Msg::Keys.dim().to_string(),
+            String::new(),
+            format!(
+                "{} {:>5.1}% {} | {}/{} {} | {}/{} | {}: {}s",
+                theme.total().cyan(),
+                percent,
+                theme.bar(percent as u8),
+                self.files_done.to_string().bold(),
+                self.files_total.to_string().dim(),
+                Msg::Files,
+                humanize::size(done).bold(),
+                humanize::size(self.bytes_total).dim(),
+                Msg::Eta,
+                self.throughput.eta(self.bytes_total.saturating_sub(done))
+            ),
+            format!(
+                "{:>11}  {}",
+                humanize::rate(self.throughput.rate()),
+                self.sparkline(width.saturating_sub(14)).cyan()
+            ),
+            String::new(),
+            format!("{} ({})", Msg::InProgress, self.in_flight.len())
+                .bold()
+                .to_string(),
+        ];
+        for (file, (processed, total)) in &self.in_flight {
+            let percent = *processed as f64 / (*total).max(1) as f64 * 100.0;
+            lines.push(format!(
+                "{} {:>5.1}% {} | {:>9}/{:9} | {}",
+                theme.running().cyan(),
+                percent,
+                theme.bar(percent as u8),
+                humanize::size(*processed),
+                humanize::size(*total),
+                shorten_path(file, width.saturating_sub(theme.width + 40).max(10)).dim()
+            ));
+        }
+
+        let mut errors = Vec::new();
+        if !self.errors.is_empty() {
+            errors.push(String::new());
+            errors.push(
+                format!("{} ({})", Msg::Errors, self.failed)
+                    .yellow()
+                    .to_string(),
+            );
+            let shown = self.errors.len().min(MAX_ERROR_LINES);
+            errors.extend(self.errors.iter().skip(self.errors.len() - shown).cloned());
+        }
+
+        // The log gets whatever room the other panels leave.
+        let room = height.saturating_sub(lines.len() + errors.len() + 2);
+        if room > 0 && !self.log.is_empty() {
+            lines.push(String::new());
+            lines.push(Msg::Recent.bold().to_string());
+            let shown = self.log.len().min(room);
+            lines.extend(self.log.iter().skip(self.log.len() - shown).cloned());
+        }
+        lines.extend(errors);
+        lines.truncate(height);
+        lines
+    }
+
+    /// The latest throughput samples as a bar chart `width` columns wide.
+    fn sparkline(&self, width: usize) -> String {
+        let levels = if Theme::current().ascii {
+            &ASCII_SPARK
+        } else {
+            &SPARK
+        };
+        let shown = self.samples.len().min(width);
+        let recent = self.samples.iter().skip(self.samples.len() - shown);
+        let peak = recent.clone().fold(0.0, |peak: f64, &rate| peak.max(rate));
+        recent
+            .map(|&rate| {
+                if peak > 0.0 {
+                    levels[(rate / peak * 7.0).round() as usize]
+                } else {
+                    levels[0]
+                }
+            })
+            .collect()
+    }
+}
+
+impl Drop for Tui {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        restore();
+        ACTIVE.store(false, Ordering::Relaxed);
+
+        let Some(dashboard) = DASHBOARD.lock().unwrap().take() else {
+            return;
+        };
+        let elapsed = dashboard.start.elapsed();
+        let mut summary = format!(
+            "{} {} {} {} ({}) {} {:.1}s ({})",
+            Theme::current().done().green(),
+            Msg::Processed.bold(),
+            dashboard.files_done,
+            Msg::Files,
+            humanize::size(dashboard.bytes_done),
+            Msg::In,
+            elapsed.as_secs_f64(),
+            humanize::rate(dashboard.bytes_done as f64 / elapsed.as_secs_f64())
+        );
+        if dashboard.failed > 0 {
+            summary += &format!(", {} {}", dashboard.failed, Msg::Failed)
+                .yellow()
+                .to_string();
+        }
+        println!("{}", summary);
+        for error in &dashboard.errors {
+            eprintln!("{}", error);
+        }
+    }
+}
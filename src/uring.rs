@@ -0,0 +1,465 @@
+use anyhow::{bail, Context, Result};
+use std::{
+    ffi::CString,
+    fs, io,
+    os::unix::ffi::OsStrExt,
+    path::PathBuf,
+    ptr,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use crate::cipher::Cipher;
+
+/// Submission queue depth; each file needs at most two entries per phase.
+const QUEUE_DEPTH: u32 = 64;
+
+/// Files handled together in one round of opens, reads and writes.
+pub const BATCH_FILES: usize = (QUEUE_DEPTH / 2) as usize;
+
+/// Files up to this size are read with a single request and batched.
+pub const SMALL_FILE_LIMIT: u64 = 1024 * 1024;
+
+const IORING_OP_OPENAT: u8 = 18;
+const IORING_OP_CLOSE: u8 = 19;
+const IORING_OP_READ: u8 = 22;
+const IORING_OP_WRITE: u8 = 23;
+
+const IORING_ENTER_GETEVENTS: u32 = 1;
+
+const IORING_OFF_SQ_RING: i64 = 0;
+const IORING_OFF_CQ_RING: i64 = 0x8000000;
+const IORING_OFF_SQES: i64 = 0x10000000;
+
+#[repr(C)]
+#[derive(Default)]
+struct SqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    flags: u32,
+    dropped: u32,
+    array: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct CqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    overflow: u32,
+    cqes: u32,
+    flags: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Params {
+    sq_entries: u32,
+    cq_entries: u32,
+    flags: u32,
+    sq_thread_cpu: u32,
+    sq_thread_idle: u32,
+    features: u32,
+    wq_fd: u32,
+    resv: [u32; 3],
+    sq_off: SqringOffsets,
+    cq_off: CqringOffsets,
+}
+
+#[repr(C)]
+#[derive(Default, Clone, Copy)]
+struct Sqe {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    off: u64,
+    addr: u64,
+    len: u32,
+    op_flags: u32,
+    user_data: u64,
+    buf_index: u16,
+    personality: u16,
+    splice_fd_in: i32,
+    addr3: u64,
+    pad: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Cqe {
+    user_data: u64,
+    res: i32,
+    flags: u32,
+}
+
+struct RegionMap {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+impl RegionMap {
+    fn new(fd: i32, len: usize, offset: i64) -> io::Result<Self> {
+        // SAFETY: maps a region of the ring fd the kernel told us about.
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_POPULATE,
+                fd,
+                offset,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { ptr, len })
+    }
+
+    /// # Safety
+    /// `offset` must come from the ring parameters for this region.
+    unsafe fn at<T>(&self, offset: u32) -> *mut T {
+        (self.ptr as *mut u8).add(offset as usize) as *mut T
+    }
+}
+
+impl Drop for RegionMap {
+    fn drop(&mut self) {
+        // SAFETY: `ptr`/`len` describe a live mapping owned by `self`.
+        unsafe {
+            libc::munmap(self.ptr, self.len);
+        }
+    }
+}
+
+/// A minimal io_uring instance driven through raw syscalls.
+pub struct Ring {
+    fd: i32,
+    sq: RegionMap,
+    cq: RegionMap,
+    sqes: RegionMap,
+    params: Params,
+    pending: u32,
+}
+
+impl Ring {
+    pub fn new() -> io::Result<Self> {
+        let mut params = Params::default();
+        // SAFETY: `params` is a correctly laid out io_uring_params.
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_io_uring_setup,
+                QUEUE_DEPTH,
+                &mut params as *mut Params,
+            )
+        } as i32;
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let sq_len = params.sq_off.array as usize + params.sq_entries as usize * 4;
+        let cq_len =
+            params.cq_off.cqes as usize + params.cq_entries as usize * std::mem::size_of::<Cqe>();
+        let sqes_len = params.sq_entries as usize * std::mem::size_of::<Sqe>();
+
+        let maps = RegionMap::new(fd, sq_len, IORING_OFF_SQ_RING).and_then(|sq| {
+            let cq = RegionMap::new(fd, cq_len, IORING_OFF_CQ_RING)?;
+            let sqes = RegionMap::new(fd, sqes_len, IORING_OFF_SQES)?;
+            Ok((sq, cq, sqes))
+        });
+        let (sq, cq, sqes) = match maps {
+            Ok(maps) => maps,
+            Err(e) => {
+                // SAFETY: `fd` was returned by io_uring_setup above.
+                unsafe { libc::close(fd) };
+                return Err(e);
+            }
+        };
+
+        Ok(Self {
+            fd,
+            sq,
+            cq,
+            sqes,
+            params,
+            pending: 0,
+        })
+    }
+
+    fn push(&mut self, sqe: Sqe) {
+        let off = &self.params.sq_off;
+        // SAFETY: all offsets come from io_uring_setup; the kernel only
+        // reads entries between head and tail, and we own the tail.
+        unsafe {
+            let tail_ptr = &*self.sq.at::<AtomicU32>(off.tail);
+            let mask = *self.sq.at::<u32>(off.ring_mask);
+            let tail = tail_ptr.load(Ordering::Relaxed);
+            let index = tail & mask;
+
+            *self.sqes.at::<Sqe>(0).add(index as usize) = sqe;
+            *self.sq.at::<u32>(off.array).add(index as usize) = index;
+            tail_ptr.store(tail.wrapping_add(1), Ordering::Release);
+        }
+        self.pending += 1;
+    }
+
+    /// Submits everything queued and waits until all of it has completed.
+    fn submit_and_wait(&mut self) -> io::Result<Vec<Cqe>> {
+        let expected = self.pending;
+        let mut to_submit = self.pending;
+        let mut completions = Vec::with_capacity(expected as usize);
+
+        while completions.len() < expected as usize {
+            // SAFETY: plain io_uring_enter on our own ring fd.
+            let rc = unsafe {
+                libc::syscall(
+                    libc::SYS_io_uring_enter,
+                    self.fd,
+                    to_submit,
+                    1u32,
+                    IORING_ENTER_GETEVENTS,
+                    ptr::null::<libc::c_void>(),
+                    0usize,
+                )
+            };
+            if rc < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(err);
+            }
+            to_submit -= rc as u32;
+            self.reap(&mut completions);
+        }
+
+        self.pending = 0;
+        Ok(completions)
+    }
+
+    fn reap(&mut self, out: &mut Vec<Cqe>) {
+        let off = &self.params.cq_off;
+        // SAFETY: offsets come from io_uring_setup; entries between head
+        // and tail are owned by us until head is advanced.
+        unsafe {
+            let head_ptr = &*self.cq.at::<AtomicU32>(off.head);
+            let tail = (*self.cq.at::<AtomicU32>(off.tail)).load(Ordering::Acquire);
+            let mask = *self.cq.at::<u32>(off.ring_mask);
+            let mut head = head_ptr.load(Ordering::Relaxed);
+
+            while head != tail {
+                out.push(*self.cq.at::<Cqe>(off.cqes).add((head & mask) as usize));
+                head = head.wrapping_add(1);
+            }
+            head_ptr.store(head, Ordering::Release);
+        }
+    }
+
+    /// Pushes one entry per item and returns each result indexed by item.
+    fn run_phase(&mut self, sqes: Vec<Sqe>) -> io::Result<Vec<i32>> {
+        let count = sqes.len();
+        for (i, mut sqe) in sqes.into_iter().enumerate() {
+            sqe.user_data = i as u64;
+            self.push(sqe);
+        }
+
+        let mut results = vec![0; count];
+        for cqe in self.submit_and_wait()? {
+            results[cqe.user_data as usize] = cqe.res;
+        }
+        Ok(results)
+    }
+}
+
+impl Drop for Ring {
+    fn drop(&mut self) {
+        // SAFETY: `fd` is the ring descriptor owned by `self`.
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+/// One small file to transform through the ring.
+pub struct BatchItem {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub size: u64,
+}
+
+fn check(res: i32, what: &str, path: &std::path::Path) -> Result<i32> {
+    if res < 0 {
+        return Err(io::Error::from_raw_os_error(-res))
+            .with_context(|| format!("{} failed: {}", what, path.display()));
+    }
+    Ok(res)
+}
+
+fn open_sqe(path: &CString, flags: i32, mode: u32) -> Sqe {
+    Sqe {
+        opcode: IORING_OP_OPENAT,
+        fd: libc::AT_FDCWD,
+        addr: path.as_ptr() as u64,
+        len: mode,
+        op_flags: (flags | libc::O_CLOEXEC) as u32,
+        ..Default::default()
+    }
+}
+
+fn close_sqe(fd: i32) -> Sqe {
+    Sqe {
+        opcode: IORING_OP_CLOSE,
+        fd,
+        ..Default::default()
+    }
+}
+
+/// Transforms a batch of small files with all opens, reads, writes and
+/// closes of the batch submitted together, one round trip per phase. Each
+/// file gets its own cipher from `new_cipher`.
+pub fn process_batch(
+    ring: &mut Ring,
+    items: &[BatchItem],
+    new_cipher: &dyn Fn() -> Box<dyn Cipher>,
+) -> Result<()> {
+    if items.len() > BATCH_FILES {
+        bail!(
+            "io_uring batch of {} files exceeds {}",
+            items.len(),
+            BATCH_FILES
+        );
+    }
+
+    for item in items {
+        if let Some(parent) = item.output.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+    }
+
+    let c_paths = items
+        .iter()
+        .map(|item| {
+            Ok((
+                CString::new(item.input.as_os_str().as_bytes())?,
+                CString::new(item.output.as_os_str().as_bytes())?,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut sqes = Vec::with_capacity(items.len() * 2);
+    for (input, output) in &c_paths {
+        sqes.push(open_sqe(input, libc::O_RDONLY, 0));
+        sqes.push(open_sqe(
+            output,
+            libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC,
+            0o644,
+        ));
+    }
+    let fds = ring.run_phase(sqes)?;
+
+    let result = transfer(ring, items, new_cipher, &fds);
+
+    let close = fds
+        .iter()
+        .filter(|&&fd| fd >= 0)
+        .map(|&fd| close_sqe(fd))
+        .collect();
+    ring.run_phase(close)?;
+
+    result
+}
+
+fn transfer(
+    ring: &mut Ring,
+    items: &[BatchItem],
+    new_cipher: &dyn Fn() -> Box<dyn Cipher>,
+    fds: &[i32],
+) -> Result<()> {
+    for (i, item) in items.iter().enumerate() {
+        check(fds[i * 2], "Open", &item.input)?;
+        check(fds[i * 2 + 1], "Create", &item.output)?;
+    }
+
+    let mut buffers: Vec<Vec<u8>> = items
+        .iter()
+        .map(|item| vec![0u8; item.size as usize])
+        .collect();
+    let mut done = vec![0usize; items.len()];
+
+    // Short reads are resubmitted for the remainder until every buffer is
+    // full or the file turns out to have shrunk.
+    loop {
+        let pending: Vec<usize> = (0..items.len())
+            .filter(|&i| done[i] < buffers[i].len())
+            .collect();
+        if pending.is_empty() {
+            break;
+        }
+
+        let sqes = pending
+            .iter()
+            .map(|&i| Sqe {
+                opcode: IORING_OP_READ,
+                fd: fds[i * 2],
+                off: done[i] as u64,
+                addr: buffers[i][done[i]..].as_mut_ptr() as u64,
+                len: (buffers[i].len() - done[i]) as u32,
+                ..Default::default()
+            })
+            .collect();
+        let results = ring.run_phase(sqes)?;
+
+        for (&i, &res) in pending.iter().zip(&results) {
+            let read = check(res, "Read", &items[i].input)? as usize;
+            if read == 0 {
+                buffers[i].truncate(done[i]);
+            }
+            done[i] += read;
+        }
+    }
+
+    for buffer in &mut buffers {
+        new_cipher().apply(0, buffer);
+    }
+
+    let mut written = vec![0usize; items.len()];
+    loop {
+        let pending: Vec<usize> = (0..items.len())
+            .filter(|&i| written[i] < buffers[i].len())
+            .collect();
+        if pending.is_empty() {
+            break;
+        }
+
+        let sqes = pending
+            .iter()
+            .map(|&i| Sqe {
+                opcode: IORING_OP_WRITE,
+                fd: fds[i * 2 + 1],
+                off: written[i] as u64,
+                addr: buffers[i][written[i]..].as_ptr() as u64,
+                len: (buffers[i].len() - written[i]) as u32,
+                ..Default::default()
+            })
+            .collect();
+        let results = ring.run_phase(sqes)?;
+
+        for (&i, &res) in pending.iter().zip(&results) {
+            if check(res, "Write", &items[i].output)? == 0 {
+                bail!("Write made no progress: {}", items[i].output.display());
+            }
+            written[i] += res as usize;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use std::path::{Path, PathBuf};
+#[cfg(feature = "cli")]
+use walkdir::DirEntry;
+
+use crate::error::JustError;
+
+/// Name of the directory next to each input that its output goes in.
+pub const OUTPUT_DIR: &str = "xor";
+
+/// Names of the lock and checkpoint files a run keeps in its output
+/// directory. Walks skip them too, for when that directory is the input.
+pub const LOCK_FILE_NAME: &str = ".xortool.lock";
+pub const CHECKPOINT_FILE_NAME: &str = ".xortool.checkpoint";
+
+/// Whether a walk from `root` should visit `entry`: never output
+/// directories or the files a run keeps in them, and subdirectories only
+/// when `recursive`.
+#[cfg(feature = "cli")]
+pub fn filter_entry(entry: &DirEntry, root: &Path, recursive: bool) -> bool {
+    filter_path(entry.path(), entry.file_type().is_dir(), root, recursive)
+}
+
+/// `filter_entry` for a path from a listing of `root` that isn't a
+/// walkdir entry, such as one from a `Storage`.
+pub fn filter_path(path: &Path, is_dir: bool, root: &Path, recursive: bool) -> bool {
+    let name = path.file_name().unwrap_or_default();
+    if (is_dir && path != root && name == OUTPUT_DIR)
+        || name == LOCK_FILE_NAME
+        || name == CHECKPOINT_FILE_NAME
+    {
+        return false;
+    }
+
+    if is_dir {
+        recursive || path == root
+    } else {
+        true
+    }
+}
+
+/// Whether `entry` is where a previous run wrote the outputs for the
+/// files next to it. Every directory gets its own, so these are skipped
+/// at any depth, though the walk's root may be one.
+#[cfg(feature = "cli")]
+pub fn is_output_dir(entry: &DirEntry) -> bool {
+    entry.depth() > 0 && entry.file_type().is_dir() && entry.file_name() == OUTPUT_DIR
+}
+
+/// Where the output of `input_path` goes: `xor/` next to it.
+pub fn build_output_path(input_path: &Path) -> Result<PathBuf, JustError> {
+    let abs_path = normalize_path(input_path)
+        .canonicalize()
+        .map_err(|e| JustError::io(input_path, e))?;
+    output_next_to(&abs_path)
+}
+
+/// `build_output_path` for an input already resolved by its storage.
+pub fn output_next_to(resolved: &Path) -> Result<PathBuf, JustError> {
+    match (resolved.parent(), resolved.file_name()) {
+        (Some(parent), Some(name)) => Ok(parent.join(OUTPUT_DIR).join(name)),
+        _ => Err(JustError::InvalidOptions(format!(
+            "{} has no parent directory",
+            resolved.display()
+        ))),
+    }
+}
+
+/// `path` with `.` components and repeated separators dropped.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    path.components().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{LocalStorage, Storage};
+    use std::{env, fs};
+
+    #[test]
+    fn test_walk_skips_output_dirs() {
+        let root = env::temp_dir().join(format!("xortool-walk-{}", std::process::id()));
+        for path in ["a", "xor/a", "sub/b", "sub/xor/b", "sub/deeper/xor/c"] {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, b"x").unwrap();
+        }
+
+        let found: Vec<_> = LocalStorage
+            .list(&root, &|path, is_dir| {
+                filter_path(path, is_dir, &root, true)
+            })
+            .unwrap()
+            .into_iter()
+            .map(|path| path.strip_prefix(&root).unwrap().to_path_buf())
+            .collect();
+        assert_eq!(found, [Path::new("a"), Path::new("sub/b")]);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
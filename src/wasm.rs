@@ -0,0 +1,55 @@
+use std::{
+    alloc::{self, Layout},
+    ptr,
+};
+
+/// Allocates `len` bytes in the module's memory, for the host to copy an
+/// ArrayBuffer or key into before calling `xortool_encrypt_buffer`.
+/// Returns null for an empty or impossible allocation.
+#[no_mangle]
+pub extern "C" fn xortool_alloc(len: usize) -> *mut u8 {
+    match Layout::array::<u8>(len) {
+        // SAFETY: the layout has a nonzero size.
+        Ok(layout) if len > 0 => unsafe { alloc::alloc(layout) },
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Frees memory from `xortool_alloc`.
+///
+/// # Safety
+///
+/// `data` must be null or come from `xortool_alloc(len)` and not have been
+/// freed already.
+#[no_mangle]
+pub unsafe extern "C" fn xortool_free(data: *mut u8, len: usize) {
+    if data.is_null() {
+        return;
+    }
+    if let Ok(layout) = Layout::array::<u8>(len) {
+        // SAFETY: the caller vouches that `data` was allocated with this
+        // layout.
+        unsafe { alloc::dealloc(data, layout) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffi::{xortool_encrypt_buffer, XORTOOL_OK};
+
+    #[test]
+    fn test_host_round_trip() {
+        let (data, key) = (xortool_alloc(4), xortool_alloc(1));
+        assert!(xortool_alloc(0).is_null());
+        // SAFETY: both allocations are live and as long as written.
+        unsafe {
+            ptr::copy_nonoverlapping(b"wasm".as_ptr(), data, 4);
+            *key = 0x20;
+            assert_eq!(xortool_encrypt_buffer(data, 4, key, 1, 0), XORTOOL_OK);
+            assert_eq!(std::slice::from_raw_parts(data, 4), b"WASM");
+            xortool_free(data, 4);
+            xortool_free(key, 1);
+        }
+    }
+}
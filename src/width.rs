@@ -0,0 +1,199 @@
+/// Characters that take two columns: East Asian wide and fullwidth forms
+/// and emoji presentation, as sorted, inclusive ranges.
+const WIDE: &[(u32, u32)] = &[
+    (0x1100, 0x115F),
+    (0x231A, 0x231B),
+    (0x2329, 0x232A),
+    (0x23E9, 0x23EC),
+    (0x23F0, 0x23F0),
+    (0x23F3, 0x23F3),
+    (0x25FD, 0x25FE),
+    (0x2614, 0x2615),
+    (0x2648, 0x2653),
+    (0x267F, 0x267F),
+    (0x2693, 0x2693),
+    (0x26A1, 0x26A1),
+    (0x26AA, 0x26AB),
+    (0x26BD, 0x26BE),
+    (0x26C4, 0x26C5),
+    (0x26CE, 0x26CE),
+    (0x26D4, 0x26D4),
+    (0x26EA, 0x26EA),
+    (0x26F2, 0x26F3),
+    (0x26F5, 0x26F5),
+    (0x26FA, 0x26FA),
+    (0x26FD, 0x26FD),
+    (0x2705, 0x2705),
+    (0x270A, 0x270B),
+    (0x2728, 0x2728),
+    (0x274C, 0x274C),
+    (0x274E, 0x274E),
+    (0x2753, 0x2755),
+    (0x2757, 0x2757),
+    (0x2795, 0x2797),
+    (0x27B0, 0x27B0),
+    (0x27BF, 0x27BF),
+    (0x2B1B, 0x2B1C),
+    (0x2B50, 0x2B50),
+    (0x2B55, 0x2B55),
+    (0x2E80, 0x303E),
+    (0x3041, 0x3098),
+    (0x309B, 0x33FF),
+    (0x3400, 0x4DBF),
+    (0x4E00, 0x9FFF),
+    (0xA000, 0xA4CF),
+    (0xA960, 0xA97F),
+    (0xAC00, 0xD7A3),
+    (0xF900, 0xFAFF),
+    (0xFE10, 0xFE19),
+    (0xFE30, 0xFE6F),
+    (0xFF00, 0xFF60),
+    (0xFFE0, 0xFFE6),
+    (0x16FE0, 0x16FE4),
+    (0x17000, 0x18CFF),
+    (0x1B000, 0x1B2FF),
+    (0x1F004, 0x1F004),
+    (0x1F0CF, 0x1F0CF),
+    (0x1F18E, 0x1F18E),
+    (0x1F191, 0x1F19A),
+    (0x1F200, 0x1F251),
+    (0x1F300, 0x1F3FA),
+    (0x1F400, 0x1F64F),
+    (0x1F680, 0x1F6FF),
+    (0x1F7E0, 0x1F7EB),
+    (0x1F900, 0x1F9FF),
+    (0x1FA70, 0x1FAFF),
+    (0x20000, 0x2FFFD),
+    (0x30000, 0x3FFFD),
+];
+
+/// Characters drawn on top of the one before them: combining marks,
+/// Hangul vowel and final jamo, variation selectors, emoji skin tones and
+/// zero-width formatting characters.
+const ZERO_WIDTH: &[(u32, u32)] = &[
+    (0x0300, 0x036F),
+    (0x0483, 0x0489),
+    (0x0591, 0x05BD),
+    (0x05BF, 0x05BF),
+    (0x05C1, 0x05C2),
+    (0x05C4, 0x05C5),
+    (0x05C7, 0x05C7),
+    (0x0610, 0x061A),
+    (0x064B, 0x065F),
+    (0x0670, 0x0670),
+    (0x06D6, 0x06DC),
+    (0x06DF, 0x06E4),
+    (0x0E31, 0x0E31),
+    (0x0E34, 0x0E3A),
+    (0x0E47, 0x0E4E),
+    (0x1160, 0x11FF),
+    (0x1AB0, 0x1AFF),
+    (0x1DC0, 0x1DFF),
+    (0x200B, 0x200F),
+    (0x20D0, 0x20FF),
+    (0x3099, 0x309A),
+    (0xFE00, 0xFE0F),
+    (0xFE20, 0xFE2F),
+    (0x1F3FB, 0x1F3FF),
+    (0xE0100, 0xE01EF),
+];
+
+/// Zero width joiner, which glues the character after it to the cluster
+/// before it, as in 👩‍💻.
+pub const ZWJ: char = '\u{200D}';
+
+fn in_table(table: &[(u32, u32)], c: char) -> bool {
+    let c = c as u32;
+    table
+        .binary_search_by(|&(first, last)| {
+            if last < c {
+                std::cmp::Ordering::Less
+            } else if first > c {
+                std::cmp::Ordering::Greater
+            } else {
+                std::cmp::Ordering::Equal
+            }
+        })
+        .is_ok()
+}
+
+/// Columns a character takes up in a terminal.
+pub fn char_width(c: char) -> usize {
+    if c.is_control() || in_table(ZERO_WIDTH, c) {
+        0
+    } else if in_table(WIDE, c) {
+        2
+    } else {
+        1
+    }
+}
+
+/// Splits `text` into what is drawn as one character: a base character
+/// with the combining characters after it, or emoji joined by a ZWJ.
+pub fn clusters(text: &str) -> impl Iterator<Item = &str> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        let mut chars = rest.char_indices();
+        let (_, first) = chars.next()?;
+        let mut joined = first == ZWJ;
+        let mut end = rest.len();
+        for (i, c) in chars {
+            if !joined && c != ZWJ && char_width(c) > 0 {
+                end = i;
+                break;
+            }
+            joined = c == ZWJ;
+        }
+        let (cluster, tail) = rest.split_at(end);
+        rest = tail;
+        Some(cluster)
+    })
+}
+
+/// Columns a cluster takes up: those of its widest character, since the
+/// rest are drawn on top of it.
+fn cluster_width(cluster: &str) -> usize {
+    cluster.chars().map(char_width).max().unwrap_or(0)
+}
+
+/// Columns `text` takes up in a terminal. It must not contain escapes.
+pub fn str_width(text: &str) -> usize {
+    clusters(text).map(cluster_width).sum()
+}
+
+/// The longest start of `text` that fits in `columns`, without splitting
+/// a cluster.
+pub fn take(text: &str, columns: usize) -> &str {
+    let mut width = 0;
+    let mut end = 0;
+    for cluster in clusters(text) {
+        width += cluster_width(cluster);
+        if width > columns {
+            break;
+        }
+        end += cluster.len();
+    }
+    &text[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_width() {
+        assert_eq!(str_width("report.txt"), 10);
+        assert_eq!(str_width("日本語.txt"), 10);
+        assert_eq!(str_width("e\u{301}t\u{301}e\u{301}"), 3);
+        assert_eq!(str_width("👩\u{200D}💻"), 2);
+        assert_eq!(str_width("✓ ▶"), 3);
+    }
+
+    #[test]
+    fn test_take() {
+        assert_eq!(take("日本語", 5), "日本");
+        assert_eq!(take("e\u{301}te", 1), "e\u{301}");
+        assert_eq!(take("👩\u{200D}💻x", 2), "👩\u{200D}💻");
+        assert_eq!(take("abc", 10), "abc");
+    }
+}
@@ -0,0 +1,85 @@
+// Promise-based file and stream transforms for Node and Electron, on the
+// same wasm module as xortool.mjs, so there is no native addon to build
+// per platform:
+//
+//   const xortool = await loadNode();
+//   await xortool.encryptFile("photo.jpg", "xor/photo.jpg", key, {
+//     onProgress: (done, total) => console.log(`${done}/${total}`),
+//   });
+//
+//   input.pipe(xortool.encryptStream(key)).on("progress", (done) => {}).pipe(output);
+
+import { open, readFile, rm, mkdir } from "node:fs/promises";
+import { dirname } from "node:path";
+import { Transform } from "node:stream";
+import { pipeline } from "node:stream/promises";
+import { fileURLToPath } from "node:url";
+import { load } from "./xortool.mjs";
+
+const DEFAULT_WASM = fileURLToPath(
+  new URL("../target/wasm32-unknown-unknown/release/xortool.wasm", import.meta.url),
+);
+
+// Loads the wasm module from `path`, by default where cargo builds it.
+export async function loadNode(path = DEFAULT_WASM) {
+  return bind(await load(await readFile(path)));
+}
+
+// Wraps an engine with `encrypt(data, key, offset)`, as returned by
+// `load`, in the file and stream functions.
+export function bind(engine) {
+  // A Transform that XORs what is piped through it, starting at
+  // keystream position `offset`, and emits `progress` with the position
+  // reached after each chunk.
+  function encryptStream(key, { offset = 0 } = {}) {
+    let done = offset;
+    return new Transform({
+      transform(chunk, _encoding, callback) {
+        let output;
+        try {
+          output = engine.encrypt(chunk, key, done);
+        } catch (e) {
+          callback(e);
+          return;
+        }
+        done += chunk.length;
+        this.emit("progress", done);
+        callback(null, output);
+      },
+    });
+  }
+
+  // Transforms the file `input` into `output`, creating its directory,
+  // and resolves to the number of bytes written. `onProgress(done,
+  // total)` is called after each chunk; aborting `signal` stops the
+  // transform. A failed or aborted transform removes the partial output.
+  async function encryptFile(input, output, key, { onProgress, signal } = {}) {
+    const source = await open(input, "r");
+    try {
+      const { size } = await source.stat();
+      await mkdir(dirname(output), { recursive: true });
+      const stream = encryptStream(key);
+      let done = 0;
+      stream.on("progress", (bytes) => {
+        done = bytes;
+        onProgress?.(bytes, size);
+      });
+      try {
+        await pipeline(
+          source.createReadStream({ autoClose: false }),
+          stream,
+          (await open(output, "w")).createWriteStream(),
+          { signal },
+        );
+      } catch (e) {
+        await rm(output, { force: true });
+        throw e;
+      }
+      return done;
+    } finally {
+      await source.close();
+    }
+  }
+
+  return { encryptFile, encryptStream };
+}
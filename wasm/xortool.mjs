@@ -0,0 +1,60 @@
+// Runs the CLI's transform on ArrayBuffers in a browser or Node, using
+// the module built by
+//
+//   cargo build --lib --release --no-default-features --target wasm32-unknown-unknown
+//
+// at target/wasm32-unknown-unknown/release/xortool.wasm:
+//
+//   const xortool = await load(await fetch("xortool.wasm"));
+//   const encrypted = xortool.encrypt(data, Uint8Array.of(0x1a, 0x2b));
+
+export async function load(source) {
+  const { instance } =
+    source instanceof Response
+      ? await WebAssembly.instantiateStreaming(source, {})
+      : await WebAssembly.instantiate(source, {});
+  const { memory, xortool_alloc, xortool_free, xortool_encrypt_buffer } = instance.exports;
+
+  // Copies `bytes` into the module's memory and returns where.
+  function copyIn(bytes) {
+    const at = xortool_alloc(bytes.length);
+    if (at === 0 && bytes.length > 0) {
+      throw new Error("xortool: out of memory");
+    }
+    new Uint8Array(memory.buffer, at, bytes.length).set(bytes);
+    return at;
+  }
+
+  return {
+    // XORs `data` with the repeating `key`, as the CLI does with
+    // `-k <key>`, and returns the result as a new Uint8Array. `offset` is
+    // the position of `data` within the whole file, for doing a large
+    // file in pieces.
+    encrypt(data, key, offset = 0) {
+      const bytes = new Uint8Array(data);
+      const keyBytes = new Uint8Array(key);
+      if (keyBytes.length === 0) {
+        throw new Error("xortool: key must not be empty");
+      }
+      const dataAt = copyIn(bytes);
+      let keyAt = 0;
+      try {
+        keyAt = copyIn(keyBytes);
+        const status = xortool_encrypt_buffer(
+          dataAt,
+          bytes.length,
+          keyAt,
+          keyBytes.length,
+          BigInt(offset),
+        );
+        if (status !== 0) {
+          throw new Error(`xortool: encrypt failed with status ${status}`);
+        }
+        return new Uint8Array(memory.buffer, dataAt, bytes.length).slice();
+      } finally {
+        xortool_free(dataAt, bytes.length);
+        xortool_free(keyAt, keyBytes.length);
+      }
+    },
+  };
+}